@@ -5,6 +5,7 @@
 
 use crate::synthetic::{LoadConfig, LoadStats, SyntheticGenerator};
 use anyhow::{Context, Result};
+use quantaterm_pty::Terminal;
 use quantaterm_telemetry::Telemetry;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -222,10 +223,17 @@ impl BenchmarkConfig {
     }
 }
 
+/// Width of the terminal synthetic load is replayed into
+const TERMINAL_COLS: u16 = 80;
+/// Height of the terminal synthetic load is replayed into
+const TERMINAL_ROWS: u16 = 24;
+
 /// Performance harness for running benchmarks
 pub struct PerformanceHarness {
     telemetry: Telemetry,
     baseline_results: HashMap<String, BenchmarkResults>,
+    /// Terminal the synthetic output is fed through
+    terminal: Terminal,
 }
 
 impl PerformanceHarness {
@@ -234,6 +242,7 @@ impl PerformanceHarness {
         Self {
             telemetry: Telemetry::new(),
             baseline_results: HashMap::new(),
+            terminal: Terminal::new(TERMINAL_COLS, TERMINAL_ROWS),
         }
     }
 
@@ -258,6 +267,7 @@ impl PerformanceHarness {
 
         let iteration_start = Instant::now();
         self.telemetry.clear();
+        self.terminal = Terminal::new(TERMINAL_COLS, TERMINAL_ROWS);
 
         // Create synthetic data generator
         let mut generator = SyntheticGenerator::new(config.load_config.clone());
@@ -279,8 +289,8 @@ impl PerformanceHarness {
             .generate_load(|data| {
                 let process_start = Instant::now();
 
-                // Simulate PTY data processing
-                self.simulate_pty_processing(&data)?;
+                // Parse the data into the terminal grid
+                self.process_pty_data(&data)?;
 
                 let process_duration = process_start.elapsed();
                 latency_measurements.push(process_duration.as_secs_f64() * 1000.0);
@@ -342,14 +352,14 @@ impl PerformanceHarness {
         })
     }
 
-    /// Simulate PTY data processing (placeholder for actual processing)
-    fn simulate_pty_processing(&mut self, data: &[u8]) -> Result<()> {
+    /// Feed PTY data through the terminal, as the application does on each read
+    fn process_pty_data(&mut self, data: &[u8]) -> Result<()> {
         // Record telemetry
         self.telemetry.start_timing("pty_processing");
 
-        // Simulate parsing and processing overhead
-        let processing_time = Duration::from_micros(data.len() as u64 / 10); // Simulate some processing cost
-        std::thread::sleep(processing_time);
+        // Replies to queries would go back to the shell; nothing listens here
+        let mut replies = Vec::new();
+        self.terminal.process(data, &mut replies);
 
         self.telemetry.end_timing("pty_processing");
         self.telemetry
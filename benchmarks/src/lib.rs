@@ -8,12 +8,14 @@
 #![deny(unsafe_code)]
 
 pub mod harness;
+pub mod parser_throughput;
 pub mod synthetic;
 
 pub use harness::{
     BenchmarkConfig, BenchmarkIteration, BenchmarkResults, BenchmarkSummary, FrameDropStats,
     LatencyStats, MemoryUsage, PerformanceHarness, ThroughputStats,
 };
+pub use parser_throughput::ParserThroughput;
 pub use synthetic::{DataType, LoadConfig, LoadStats, SyntheticGenerator};
 
 use anyhow::Result;
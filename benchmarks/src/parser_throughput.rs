@@ -0,0 +1,192 @@
+//! Parser Throughput Measurement
+//!
+//! This module replays the synthetic workloads through the escape sequence
+//! parser without rate limiting, comparing the collected `Vec<ParseAction>`
+//! API with streaming into a `TerminalHandler`, and the full terminal that
+//! streams into the grid.
+
+use crate::synthetic::{DataType, LoadConfig, SyntheticGenerator};
+use quantaterm_pty::{
+    CsiAction, DcsAction, EscAction, OscAction, OscTerminator, ParserState, Terminal,
+    TerminalHandler, TerminalParser,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::{info, instrument};
+
+/// Synthetic workloads replayed by [`measure_all`]
+pub const WORKLOADS: [DataType; 5] = [
+    DataType::PlainText,
+    DataType::AnsiColors,
+    DataType::LargePaste,
+    DataType::Random,
+    DataType::Scrolling,
+];
+
+/// Parser throughput for one workload
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParserThroughput {
+    /// Workload that was replayed
+    pub data_type: DataType,
+    /// Bytes parsed per pass
+    pub total_bytes: u64,
+    /// Events decoded per pass
+    pub events: u64,
+    /// Bytes per second through `TerminalParser::parse`
+    pub collected_bytes_per_sec: f64,
+    /// Bytes per second through `TerminalParser::advance`
+    pub streaming_bytes_per_sec: f64,
+    /// Bytes per second through `Terminal::process`, including grid updates
+    pub terminal_bytes_per_sec: f64,
+}
+
+impl ParserThroughput {
+    /// Streaming throughput relative to the collected API
+    pub fn streaming_speedup(&self) -> f64 {
+        self.streaming_bytes_per_sec / self.collected_bytes_per_sec
+    }
+}
+
+/// Handler that only counts events, isolating the parser's own cost
+#[derive(Debug, Default)]
+struct CountingHandler {
+    events: u64,
+}
+
+impl TerminalHandler for CountingHandler {
+    fn print(&mut self, _c: char) {
+        self.events += 1;
+    }
+
+    fn execute(&mut self, _byte: u8) {
+        self.events += 1;
+    }
+
+    fn sgr(&mut self, _params: &[&[u16]], _state: &ParserState) {
+        self.events += 1;
+    }
+
+    fn csi_dispatch(&mut self, _action: CsiAction) {
+        self.events += 1;
+    }
+
+    fn esc_dispatch(&mut self, _action: EscAction) {
+        self.events += 1;
+    }
+
+    fn osc_dispatch(&mut self, _action: OscAction, _terminator: OscTerminator) {
+        self.events += 1;
+    }
+
+    fn dcs_dispatch(&mut self, _action: DcsAction) {
+        self.events += 1;
+    }
+}
+
+/// Generate `total_bytes` of a workload split into PTY-sized chunks
+pub fn workload_chunks(data_type: DataType, total_bytes: usize, chunk_size: usize) -> Vec<Vec<u8>> {
+    let mut generator = SyntheticGenerator::new(LoadConfig {
+        chunk_size,
+        data_type,
+        ..Default::default()
+    });
+
+    let mut chunks = Vec::new();
+    let mut generated = 0;
+    while generated < total_bytes {
+        let chunk = generator.generate_chunk();
+        if chunk.is_empty() {
+            break;
+        }
+        generated += chunk.len();
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Measure parser throughput for one workload
+#[instrument(skip_all, fields(data_type = ?data_type))]
+pub fn measure(data_type: DataType, total_bytes: usize, chunk_size: usize) -> ParserThroughput {
+    let chunks = workload_chunks(data_type.clone(), total_bytes, chunk_size);
+    let bytes: u64 = chunks.iter().map(|chunk| chunk.len() as u64).sum();
+
+    let mut collected_events = 0u64;
+    let collected = time(|| {
+        let mut parser = TerminalParser::new();
+        for chunk in &chunks {
+            collected_events += parser.parse(chunk).len() as u64;
+        }
+    });
+
+    let mut handler = CountingHandler::default();
+    let streaming = time(|| {
+        let mut parser = TerminalParser::new();
+        for chunk in &chunks {
+            parser.advance(chunk, &mut handler);
+        }
+    });
+    debug_assert_eq!(collected_events, handler.events);
+
+    let terminal = time(|| {
+        let mut terminal = Terminal::new(80, 24);
+        let mut replies = Vec::new();
+        for chunk in &chunks {
+            terminal.process(chunk, &mut replies);
+        }
+    });
+
+    let result = ParserThroughput {
+        data_type,
+        total_bytes: bytes,
+        events: handler.events,
+        collected_bytes_per_sec: rate(bytes, collected),
+        streaming_bytes_per_sec: rate(bytes, streaming),
+        terminal_bytes_per_sec: rate(bytes, terminal),
+    };
+    info!(result = ?result, "Measured parser throughput");
+    result
+}
+
+/// Measure parser throughput for every synthetic workload
+pub fn measure_all(total_bytes: usize, chunk_size: usize) -> Vec<ParserThroughput> {
+    WORKLOADS
+        .iter()
+        .map(|data_type| measure(data_type.clone(), total_bytes, chunk_size))
+        .collect()
+}
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn rate(bytes: u64, duration: Duration) -> f64 {
+    bytes as f64 / duration.as_secs_f64().max(f64::EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workload_chunks() {
+        let chunks = workload_chunks(DataType::AnsiColors, 10_000, 512);
+        let total: usize = chunks.iter().map(Vec::len).sum();
+        assert!(total >= 10_000);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 512));
+    }
+
+    #[test]
+    fn test_measure_all_workloads() {
+        let results = measure_all(16 * 1024, 1024);
+        assert_eq!(results.len(), WORKLOADS.len());
+        for result in &results {
+            assert!(result.total_bytes >= 16 * 1024);
+            assert!(result.events > 0);
+            assert!(result.collected_bytes_per_sec > 0.0);
+            assert!(result.streaming_bytes_per_sec > 0.0);
+            assert!(result.terminal_bytes_per_sec > 0.0);
+        }
+    }
+}
@@ -5,8 +5,8 @@
 use anyhow::Result;
 use clap::{Args, Parser, Subcommand};
 use quantaterm_benchmarks::{
-    BenchmarkConfig, BenchmarkSuite, LoadConfig, PerformanceHarness, ResultsManager,
-    SyntheticGenerator,
+    parser_throughput, BenchmarkConfig, BenchmarkSuite, LoadConfig, PerformanceHarness,
+    ResultsManager, SyntheticGenerator,
 };
 use std::path::PathBuf;
 use std::time::Duration;
@@ -40,6 +40,8 @@ enum Commands {
     Suite(SuiteArgs),
     /// Analyze existing benchmark results
     Analyze(AnalyzeArgs),
+    /// Measure parser throughput on the synthetic workloads
+    Parser(ParserArgs),
 }
 
 #[derive(Args)]
@@ -162,6 +164,21 @@ struct AnalyzeArgs {
     output: Option<PathBuf>,
 }
 
+#[derive(Args)]
+struct ParserArgs {
+    /// Bytes of each workload to parse
+    #[arg(short, long, default_value = "8388608")]
+    bytes: usize,
+
+    /// Chunk size, as delivered by a single PTY read
+    #[arg(short, long, default_value = "4096")]
+    chunk_size: usize,
+
+    /// Output file for results
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
 fn setup_logging(verbose: bool, log_level: &str) -> Result<()> {
     let level = match log_level.to_lowercase().as_str() {
         "trace" => Level::TRACE,
@@ -228,7 +245,7 @@ async fn run_generate(args: GenerateArgs) -> Result<()> {
             // Simulate some processing delay
             std::thread::sleep(Duration::from_micros(data.len() as u64 / 100));
 
-            // Print some progress  
+            // Print some progress
             #[allow(clippy::pedantic)]
             if total_output.len() % 10000 == 0 {
                 println!("Generated {} bytes", total_output.len());
@@ -444,6 +461,36 @@ async fn run_analyze(args: AnalyzeArgs) -> Result<()> {
     Ok(())
 }
 
+async fn run_parser(args: ParserArgs) -> Result<()> {
+    info!("Measuring parser throughput");
+
+    let results = parser_throughput::measure_all(args.bytes, args.chunk_size);
+
+    println!("\n=== Parser Throughput (MB/s) ===");
+    println!(
+        "{:<12} {:>10} {:>10} {:>10} {:>8}",
+        "workload", "collected", "streaming", "terminal", "speedup"
+    );
+    for result in &results {
+        println!(
+            "{:<12} {:>10.1} {:>10.1} {:>10.1} {:>7.2}x",
+            format!("{:?}", result.data_type),
+            result.collected_bytes_per_sec / 1_000_000.0,
+            result.streaming_bytes_per_sec / 1_000_000.0,
+            result.terminal_bytes_per_sec / 1_000_000.0,
+            result.streaming_speedup()
+        );
+    }
+
+    if let Some(output_path) = args.output {
+        let json = serde_json::to_string_pretty(&results)?;
+        std::fs::write(&output_path, json)?;
+        println!("Results saved to: {}", output_path.display());
+    }
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
@@ -455,6 +502,7 @@ async fn main() -> Result<()> {
         Commands::Benchmark(args) => run_benchmark(args).await,
         Commands::Suite(args) => run_suite(args).await,
         Commands::Analyze(args) => run_analyze(args).await,
+        Commands::Parser(args) => run_parser(args).await,
     };
 
     if let Err(ref e) = result {
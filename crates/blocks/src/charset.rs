@@ -0,0 +1,129 @@
+//! Character set designation and shifting
+//!
+//! Applications designate 94-character sets into the G0-G3 slots with SCS
+//! (`ESC ( 0` and friends) and pick the one mapped into GL with SI/SO or the
+//! locking shifts. Printed characters are translated through the active set,
+//! which is how DEC Special Graphics turns `lqqk` into box-drawing lines.
+
+use quantaterm_core::{Charset, CharsetSlot};
+
+/// Designated character sets and the slot currently invoked into GL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CharsetState {
+    /// Sets designated into G0, G1, G2 and G3
+    slots: [Charset; 4],
+    /// Slot whose set is used for printed characters
+    active: CharsetSlot,
+}
+
+impl CharsetState {
+    /// Create the power-on state: ASCII everywhere, G0 active
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Designate a character set into a slot (SCS)
+    pub fn designate(&mut self, slot: CharsetSlot, charset: Charset) {
+        self.slots[slot as usize] = charset;
+    }
+
+    /// Invoke a slot into GL (SI, SO, LS2, LS3)
+    pub fn invoke(&mut self, slot: CharsetSlot) {
+        self.active = slot;
+    }
+
+    /// Slot currently invoked into GL
+    pub fn active(&self) -> CharsetSlot {
+        self.active
+    }
+
+    /// Character set designated into a slot
+    pub fn charset(&self, slot: CharsetSlot) -> Charset {
+        self.slots[slot as usize]
+    }
+
+    /// Map a printed character through the active set
+    pub fn translate(&self, c: char) -> char {
+        match self.charset(self.active) {
+            Charset::Ascii => c,
+            Charset::Uk => match c {
+                '#' => '£',
+                c => c,
+            },
+            Charset::DecSpecialGraphics => dec_special_graphic(c),
+        }
+    }
+}
+
+/// DEC Special Graphics mapping for `_` through `~`
+fn dec_special_graphic(c: char) -> char {
+    match c {
+        '_' => ' ',
+        '`' => '◆',
+        'a' => '▒',
+        'b' => '␉',
+        'c' => '␌',
+        'd' => '␍',
+        'e' => '␊',
+        'f' => '°',
+        'g' => '±',
+        'h' => '␤',
+        'i' => '␋',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_drawing_translation() {
+        let mut charsets = CharsetState::new();
+        assert_eq!(charsets.translate('q'), 'q');
+
+        charsets.designate(CharsetSlot::G0, Charset::DecSpecialGraphics);
+        let box_top: String = "lqqk".chars().map(|c| charsets.translate(c)).collect();
+        assert_eq!(box_top, "┌──┐");
+
+        // Characters outside the graphics range pass through
+        assert_eq!(charsets.translate('A'), 'A');
+    }
+
+    #[test]
+    fn test_shifts_select_slot() {
+        let mut charsets = CharsetState::new();
+        charsets.designate(CharsetSlot::G1, Charset::DecSpecialGraphics);
+        charsets.designate(CharsetSlot::G3, Charset::Uk);
+
+        assert_eq!(charsets.translate('x'), 'x');
+        charsets.invoke(CharsetSlot::G1);
+        assert_eq!(charsets.translate('x'), '│');
+        charsets.invoke(CharsetSlot::G3);
+        assert_eq!(charsets.translate('#'), '£');
+        charsets.invoke(CharsetSlot::G0);
+        assert_eq!(charsets.active(), CharsetSlot::G0);
+        assert_eq!(charsets.translate('#'), '#');
+    }
+}
@@ -0,0 +1,153 @@
+//! Viewport damage tracking
+//!
+//! Converting the whole viewport for the renderer on every update costs far
+//! more than the handful of rows a typical write touches. The grid marks the
+//! viewport rows that writes, scrolls and selection changes affect, and
+//! [`TerminalGrid::take_damage`](crate::TerminalGrid::take_damage) hands them
+//! over so only those rows are converted and uploaded. Rows are kept one bit
+//! each, like tab stops.
+
+use std::ops::Range;
+
+/// Viewport rows changed since damage was last taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Damage {
+    /// Every row changed, e.g. after a resize
+    full: bool,
+    /// Rows the whole viewport moved up by, applied before the marked rows
+    scrolled: u16,
+    /// One bit per row, least significant bit first
+    bits: Vec<u64>,
+    /// Number of rows covered
+    rows: u16,
+}
+
+impl Damage {
+    /// No damage on a viewport `rows` high
+    pub fn none(rows: u16) -> Self {
+        Self {
+            full: false,
+            scrolled: 0,
+            bits: vec![0; (rows as usize).div_ceil(64)],
+            rows,
+        }
+    }
+
+    /// Every row of a viewport `rows` high is damaged
+    pub fn full(rows: u16) -> Self {
+        Self {
+            full: true,
+            ..Self::none(rows)
+        }
+    }
+
+    /// Whether every row is damaged
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    /// Whether no row is damaged
+    pub fn is_empty(&self) -> bool {
+        !self.full && self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// Rows the viewport moved up by before the damaged rows were changed
+    ///
+    /// Rows that moved in at the bottom are always damaged.
+    pub fn scrolled(&self) -> u16 {
+        self.scrolled
+    }
+
+    /// Whether `row` is damaged
+    pub fn contains(&self, row: u16) -> bool {
+        row < self.rows && (self.full || self.bits[row as usize / 64] & (1 << (row % 64)) != 0)
+    }
+
+    /// Damaged rows in ascending order
+    pub fn rows(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.rows).filter(|&row| self.contains(row))
+    }
+
+    /// Mark `row` as damaged
+    pub fn mark(&mut self, row: u16) {
+        if row < self.rows {
+            self.bits[row as usize / 64] |= 1 << (row % 64);
+        }
+    }
+
+    /// Mark `rows` as damaged
+    pub fn mark_range(&mut self, rows: Range<u16>) {
+        for row in rows {
+            self.mark(row);
+        }
+    }
+
+    /// Record the viewport moving up by `count` rows
+    ///
+    /// Damaged rows move up with their content and the rows moving in at the
+    /// bottom are damaged, so the rows in between need not be sent again.
+    pub fn scroll_up(&mut self, count: u16) {
+        if self.full || count == 0 {
+            return;
+        }
+        if self.scrolled.saturating_add(count) >= self.rows {
+            self.mark_all();
+            return;
+        }
+        let moved: Vec<u16> = self
+            .rows()
+            .filter_map(|row| row.checked_sub(count))
+            .collect();
+        self.bits.fill(0);
+        for row in moved {
+            self.mark(row);
+        }
+        self.mark_range(self.rows - count..self.rows);
+        self.scrolled += count;
+    }
+
+    /// Mark every row as damaged
+    pub fn mark_all(&mut self) {
+        self.full = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_damage_rows() {
+        let mut damage = Damage::none(70);
+        assert!(damage.is_empty());
+        damage.mark(2);
+        damage.mark_range(64..66);
+        damage.mark(70);
+        assert!(!damage.is_empty());
+        assert_eq!(damage.rows().collect::<Vec<_>>(), [2, 64, 65]);
+
+        damage.mark_all();
+        assert!(damage.is_full());
+        assert_eq!(damage.rows().count(), 70);
+        assert!(!damage.contains(70));
+        // Scrolling moves damage up with the rows and damages the new rows
+        let mut damage = Damage::none(5);
+        damage.mark(0);
+        damage.mark(3);
+        damage.scroll_up(1);
+        damage.scroll_up(1);
+        assert_eq!(damage.scrolled(), 2);
+        assert_eq!(damage.rows().collect::<Vec<_>>(), [1, 3, 4]);
+        damage.scroll_up(3);
+        assert!(damage.is_full());
+        let mut damage = Damage::none(5);
+        damage.scroll_up(0);
+        assert!(damage.is_empty());
+
+        assert_eq!(Damage::full(3), {
+            let mut full = Damage::none(3);
+            full.mark_all();
+            full
+        });
+    }
+}
@@ -0,0 +1,191 @@
+//! Grapheme cluster storage
+//!
+//! A cell shows one grapheme cluster: a base character plus any combining
+//! marks, variation selectors or ZWJ-joined characters that follow it. The
+//! base character is stored inline in the cell's `glyph_id`; the few cells
+//! holding more than one code point also refer to the full cluster in a
+//! [`GraphemeTable`], where each distinct cluster is stored once.
+//!
+//! Cells are copied and overwritten everywhere, so they hold no counted
+//! references. Instead the grid collects the table once it has doubled in
+//! size, freeing the clusters no screen row or history page refers to any
+//! more, and again on a reset or when scrollback is cleared.
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Handle to a cluster interned in a [`GraphemeTable`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GraphemeId(NonZeroU32);
+
+impl GraphemeId {
+    /// The id as a non-zero integer, for compact storage
+    pub(crate) fn to_raw(self) -> u32 {
+        self.0.get()
+    }
+
+    /// Rebuild an id from [`to_raw`](Self::to_raw), `None` for zero
+    pub(crate) fn from_raw(raw: u32) -> Option<Self> {
+        NonZeroU32::new(raw).map(Self)
+    }
+}
+
+/// Clusters a table holds before it is first collected
+const MIN_COLLECT_LEN: usize = 1024;
+
+/// Interned multi-code-point grapheme clusters
+#[derive(Debug)]
+pub struct GraphemeTable {
+    /// Clusters by id, offset by one; `None` for free slots
+    clusters: Vec<Option<Box<str>>>,
+    /// Id of each stored cluster
+    ids: HashMap<Box<str>, GraphemeId>,
+    /// Ids free for reuse
+    free: Vec<GraphemeId>,
+    /// Clusters stored when the table is next due for collection
+    collect_at: usize,
+}
+
+impl Default for GraphemeTable {
+    fn default() -> Self {
+        Self {
+            clusters: Vec::new(),
+            ids: HashMap::new(),
+            free: Vec::new(),
+            collect_at: MIN_COLLECT_LEN,
+        }
+    }
+}
+
+impl GraphemeTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a cluster, returning the id of an identical one if already present
+    ///
+    /// Returns `None` if every id is in use.
+    pub fn intern(&mut self, cluster: &str) -> Option<GraphemeId> {
+        if let Some(&id) = self.ids.get(cluster) {
+            return Some(id);
+        }
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.clusters[id.0.get() as usize - 1] = Some(cluster.into());
+                id
+            }
+            None => {
+                let id = u32::try_from(self.clusters.len() + 1)
+                    .ok()
+                    .and_then(GraphemeId::from_raw)?;
+                self.clusters.push(Some(cluster.into()));
+                id
+            }
+        };
+        self.ids.insert(cluster.into(), id);
+        Some(id)
+    }
+
+    /// The cluster stored under `id`, empty if it was freed
+    pub fn get(&self, id: GraphemeId) -> &str {
+        self.clusters
+            .get(id.0.get() as usize - 1)
+            .and_then(Option::as_deref)
+            .unwrap_or_default()
+    }
+
+    /// Number of distinct clusters stored
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Whether no cluster is stored
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Whether the table has grown enough since it was last collected
+    pub(crate) fn is_due_for_collection(&self) -> bool {
+        self.len() >= self.collect_at
+    }
+
+    /// Free every cluster whose id `live` rejects, for reuse by later clusters
+    pub(crate) fn collect(&mut self, live: impl Fn(GraphemeId) -> bool) {
+        let free = &mut self.free;
+        let clusters = &mut self.clusters;
+        self.ids.retain(|_, id| {
+            let keep = live(*id);
+            if !keep {
+                clusters[id.0.get() as usize - 1] = None;
+                free.push(*id);
+            }
+            keep
+        });
+        self.collect_at = (self.ids.len() * 2).max(MIN_COLLECT_LEN);
+    }
+}
+
+/// Whether `c` continues the grapheme cluster `cluster` instead of starting a new one
+pub fn extends_cluster(cluster: &str, c: char) -> bool {
+    // Nothing in ASCII extends a cluster, and ASCII is nearly all output
+    if c.is_ascii() || cluster.is_empty() {
+        return false;
+    }
+
+    let mut buf = [0u8; 64];
+    let joined = cluster.len() + c.len_utf8();
+    if joined <= buf.len() {
+        buf[..cluster.len()].copy_from_slice(cluster.as_bytes());
+        c.encode_utf8(&mut buf[cluster.len()..]);
+        let joined = std::str::from_utf8(&buf[..joined]).expect("joined from valid UTF-8");
+        is_single_cluster(joined)
+    } else {
+        let mut joined = String::with_capacity(joined);
+        joined.push_str(cluster);
+        joined.push(c);
+        is_single_cluster(&joined)
+    }
+}
+
+fn is_single_cluster(text: &str) -> bool {
+    text.graphemes(true).nth(1).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+        let mut table = GraphemeTable::new();
+        let accented = table.intern("e\u{301}").unwrap();
+        let family = table.intern("👨\u{200D}👩\u{200D}👧").unwrap();
+        assert_ne!(accented, family);
+        assert_eq!(table.intern("e\u{301}"), Some(accented));
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(family), "👨\u{200D}👩\u{200D}👧");
+
+        // Collected clusters free their ids for new ones
+        table.collect(|id| id == family);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get(accented), "");
+        assert_eq!(table.intern("a\u{301}"), Some(accented));
+        assert_eq!(table.get(family), "👨\u{200D}👩\u{200D}👧");
+        assert!(!table.is_due_for_collection());
+    }
+
+    #[test]
+    fn test_cluster_extension() {
+        assert!(extends_cluster("e", '\u{301}'));
+        assert!(extends_cluster("❤", '\u{FE0F}'));
+        assert!(extends_cluster("👨", '\u{200D}'));
+        assert!(extends_cluster("👨\u{200D}", '👩'));
+        assert!(extends_cluster("🇺", '🇸'));
+        assert!(!extends_cluster("🇺🇸", '🇩'));
+        assert!(!extends_cluster("a", 'b'));
+        assert!(!extends_cluster("中", '文'));
+        assert!(!extends_cluster("", '\u{301}'));
+    }
+}
@@ -0,0 +1,1000 @@
+//! Compact storage for lines scrolled off the screen
+//!
+//! Screen rows are edited in place and stay as plain [`Cell`] vectors, but a
+//! line that scrolls into history never changes again, so it is packed: its
+//! characters as UTF-8, its colors and attributes as runs pointing into a
+//! shared [`StyleTable`], and the rare cells with layout flags or grapheme
+//! clusters in a short side list. Trailing blank cells are not stored at all.
+//! A typical 300-column line shrinks from about 10 KB to a few hundred bytes.
+//!
+//! Lines are grouped in pages of [`PAGE_LINES`]. History is limited by a byte
+//! budget as well as a line count, and whole pages are dropped from the front
+//! when over budget. With compression on, full pages older than the newest
+//! [`HOT_PAGES`] are deflated and only inflated again when read.
+
+use crate::{Cell, CellAttrs, CellColor, CellFlags, CellRow, GraphemeId, ROW_FLAGS};
+use quantaterm_telemetry::{Telemetry, TelemetryEvent};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+use std::ops::Range;
+
+/// Lines per history page
+pub const PAGE_LINES: usize = 256;
+
+/// Newest full pages kept uncompressed, so scrolling back a little stays cheap
+pub const HOT_PAGES: usize = 2;
+
+/// History byte budget used unless configured otherwise
+pub const DEFAULT_SCROLLBACK_BYTES: usize = 64 * 1024 * 1024;
+
+/// Deflate level for compressed pages; history is written far more than read
+const COMPRESSION_LEVEL: u8 = 1;
+
+/// Colors and attributes shared by a run of cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Style {
+    fg: CellColor,
+    bg: CellColor,
+    attrs: CellAttrs,
+    underline_color: Option<CellColor>,
+}
+
+impl Style {
+    fn of(cell: &Cell) -> Self {
+        Self {
+            fg: cell.fg_color,
+            bg: cell.bg_color,
+            attrs: cell.attrs,
+            underline_color: cell.underline_color,
+        }
+    }
+
+    fn apply(self, cell: &mut Cell) {
+        cell.fg_color = self.fg;
+        cell.bg_color = self.bg;
+        cell.attrs = self.attrs;
+        cell.underline_color = self.underline_color;
+    }
+}
+
+/// Reference-counted interned styles
+///
+/// Each page holds one reference per style id it lists, released when the
+/// page is dropped, so styles only used by evicted output are freed.
+#[derive(Debug, Default)]
+struct StyleTable {
+    /// Styles by id
+    styles: Vec<Style>,
+    /// References to each id; zero for free slots
+    refs: Vec<u32>,
+    /// Ids free for reuse
+    free: Vec<u32>,
+    /// Id of each live style
+    ids: HashMap<Style, u32>,
+}
+
+impl StyleTable {
+    /// Intern `style` and take a reference to it
+    fn acquire(&mut self, style: Style) -> u32 {
+        let id = match self.ids.get(&style) {
+            Some(&id) => id,
+            None => {
+                let id = match self.free.pop() {
+                    Some(id) => {
+                        self.styles[id as usize] = style;
+                        id
+                    }
+                    None => {
+                        self.styles.push(style);
+                        self.refs.push(0);
+                        (self.styles.len() - 1) as u32
+                    }
+                };
+                self.ids.insert(style, id);
+                id
+            }
+        };
+        self.refs[id as usize] += 1;
+        id
+    }
+
+    /// Drop a reference taken by [`acquire`](Self::acquire)
+    fn release(&mut self, id: u32) {
+        let refs = &mut self.refs[id as usize];
+        *refs -= 1;
+        if *refs == 0 {
+            self.ids.remove(&self.styles[id as usize]);
+            self.free.push(id);
+        }
+    }
+
+    fn get(&self, id: u32) -> Style {
+        self.styles[id as usize]
+    }
+
+    /// Styles currently referenced
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Approximate heap use
+    fn bytes(&self) -> usize {
+        self.styles.capacity() * size_of::<Style>()
+            + self.refs.capacity() * size_of::<u32>()
+            + self.free.capacity() * size_of::<u32>()
+            + self.ids.capacity() * (size_of::<Style>() + size_of::<u32>())
+    }
+}
+
+/// Counted references history holds to grapheme clusters
+///
+/// The clusters live in the grid's [`GraphemeTable`](crate::GraphemeTable),
+/// which keeps those history refers to when it is collected. Like styles,
+/// each page holds one reference per cluster it lists.
+#[derive(Debug, Default)]
+struct GraphemeRefs {
+    refs: HashMap<GraphemeId, u32>,
+}
+
+impl GraphemeRefs {
+    fn acquire(&mut self, id: GraphemeId) {
+        *self.refs.entry(id).or_default() += 1;
+    }
+
+    fn release(&mut self, id: GraphemeId) {
+        if let Some(refs) = self.refs.get_mut(&id) {
+            *refs -= 1;
+            if *refs == 0 {
+                self.refs.remove(&id);
+            }
+        }
+    }
+}
+
+/// A stored cell that plain text and style runs cannot describe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SparseCell {
+    col: u16,
+    /// Cell flags other than the row flags
+    flags: CellFlags,
+    grapheme: Option<GraphemeId>,
+    /// The glyph, kept here as well in case it is not a valid `char`
+    glyph: u32,
+}
+
+/// One history line
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CompactLine {
+    /// Cells in the row when it was stored
+    width: u16,
+    /// Flags describing the whole row (soft wrap, line size)
+    row_flags: CellFlags,
+    /// One character per stored cell; trailing blanks are cut
+    text: Box<str>,
+    /// First column and style id of each run of equally styled cells
+    runs: Box<[(u16, u32)]>,
+    /// Cells with layout flags, a grapheme cluster or an invalid glyph
+    sparse: Box<[SparseCell]>,
+}
+
+/// Whether a cell is a plain blank that need not be stored
+fn is_blank(cell: &Cell) -> bool {
+    cell.glyph_id == b' ' as u32
+        && cell.fg_color == CellColor::Default
+        && cell.bg_color == CellColor::Default
+        && cell.attrs.is_empty()
+        && cell.underline_color.is_none()
+        && cell.grapheme.is_none()
+        && cell.flags.difference(ROW_FLAGS).is_empty()
+}
+
+impl CompactLine {
+    /// Pack `row`, taking a style reference for each run into `refs`
+    fn encode(row: &[Cell], styles: &mut StyleTable, refs: &mut Vec<u32>) -> Self {
+        let row_flags = row
+            .last()
+            .map_or(CellFlags::empty(), |cell| cell.flags & ROW_FLAGS);
+        let len = row
+            .iter()
+            .rposition(|cell| !is_blank(cell))
+            .map_or(0, |i| i + 1);
+
+        let mut text = String::with_capacity(len);
+        let mut runs: Vec<(u16, u32)> = Vec::new();
+        let mut sparse = Vec::new();
+        let mut current = None;
+        for (col, cell) in row[..len].iter().enumerate() {
+            let glyph = char::from_u32(cell.glyph_id);
+            text.push(glyph.unwrap_or(' '));
+
+            let style = Style::of(cell);
+            if current != Some(style) {
+                let id = styles.acquire(style);
+                refs.push(id);
+                runs.push((col as u16, id));
+                current = Some(style);
+            }
+
+            let flags = cell.flags.difference(ROW_FLAGS);
+            if !flags.is_empty() || cell.grapheme.is_some() || glyph.is_none() {
+                sparse.push(SparseCell {
+                    col: col as u16,
+                    flags,
+                    grapheme: cell.grapheme,
+                    glyph: cell.glyph_id,
+                });
+            }
+        }
+
+        Self {
+            width: row.len() as u16,
+            row_flags,
+            text: text.into(),
+            runs: runs.into(),
+            sparse: sparse.into(),
+        }
+    }
+
+    /// Unpack to a full row of cells
+    fn decode(&self, styles: &StyleTable) -> CellRow {
+        let mut row = Vec::with_capacity(self.width as usize);
+        let mut chars = self.text.chars();
+        let mut template = Cell::empty();
+        for (index, &(start, id)) in self.runs.iter().enumerate() {
+            styles.get(id).apply(&mut template);
+            let count = self
+                .runs
+                .get(index + 1)
+                .map_or(usize::MAX, |&(next, _)| (next - start) as usize);
+            row.extend(chars.by_ref().take(count).map(|c| Cell {
+                glyph_id: c as u32,
+                ..template
+            }));
+        }
+        for sparse in self.sparse.iter() {
+            let cell = &mut row[sparse.col as usize];
+            cell.flags = sparse.flags;
+            cell.grapheme = sparse.grapheme;
+            cell.glyph_id = sparse.glyph;
+        }
+        row.resize(self.width as usize, Cell::empty());
+        if let Some(last) = row.last_mut() {
+            last.flags |= self.row_flags;
+        }
+        row
+    }
+
+    /// Heap and inline bytes used
+    fn bytes(&self) -> usize {
+        size_of::<Self>()
+            + self.text.len()
+            + self.runs.len() * size_of::<(u16, u32)>()
+            + self.sparse.len() * size_of::<SparseCell>()
+    }
+
+    /// Append the line to a page being compressed
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.width.to_le_bytes());
+        out.push(self.row_flags.bits());
+        out.extend_from_slice(&(self.text.len() as u32).to_le_bytes());
+        out.extend_from_slice(self.text.as_bytes());
+        out.extend_from_slice(&(self.runs.len() as u16).to_le_bytes());
+        for &(start, id) in self.runs.iter() {
+            out.extend_from_slice(&start.to_le_bytes());
+            out.extend_from_slice(&id.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.sparse.len() as u16).to_le_bytes());
+        for sparse in self.sparse.iter() {
+            out.extend_from_slice(&sparse.col.to_le_bytes());
+            out.push(sparse.flags.bits());
+            out.extend_from_slice(&sparse.grapheme.map_or(0, GraphemeId::to_raw).to_le_bytes());
+            out.extend_from_slice(&sparse.glyph.to_le_bytes());
+        }
+    }
+
+    /// Read a line written by [`write`](Self::write)
+    fn read(input: &mut &[u8]) -> Option<Self> {
+        let width = u16::from_le_bytes(take(input)?);
+        let row_flags = CellFlags::from_bits_retain(take::<1>(input)?[0]);
+        let text_len = u32::from_le_bytes(take(input)?) as usize;
+        let text = std::str::from_utf8(input.get(..text_len)?).ok()?.into();
+        *input = &input[text_len..];
+
+        let runs = (0..u16::from_le_bytes(take(input)?))
+            .map(|_| {
+                Some((
+                    u16::from_le_bytes(take(input)?),
+                    u32::from_le_bytes(take(input)?),
+                ))
+            })
+            .collect::<Option<_>>()?;
+        let sparse = (0..u16::from_le_bytes(take(input)?))
+            .map(|_| {
+                Some(SparseCell {
+                    col: u16::from_le_bytes(take(input)?),
+                    flags: CellFlags::from_bits_retain(take::<1>(input)?[0]),
+                    grapheme: GraphemeId::from_raw(u32::from_le_bytes(take(input)?)),
+                    glyph: u32::from_le_bytes(take(input)?),
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            width,
+            row_flags,
+            text,
+            runs,
+            sparse,
+        })
+    }
+}
+
+/// Split `N` bytes off the front of `input`
+fn take<const N: usize>(input: &mut &[u8]) -> Option<[u8; N]> {
+    let bytes = input.get(..N)?.try_into().ok()?;
+    *input = &input[N..];
+    Some(bytes)
+}
+
+/// Lines of a page, as stored
+#[derive(Debug)]
+enum PageLines {
+    Plain(Vec<CompactLine>),
+    Compressed { data: Box<[u8]>, lines: usize },
+}
+
+/// Up to [`PAGE_LINES`] consecutive history lines
+#[derive(Debug)]
+struct Page {
+    lines: PageLines,
+    /// Style ids referenced by the lines, one table reference each
+    styles: Vec<u32>,
+    /// Grapheme clusters referenced by the lines, one reference each
+    graphemes: Vec<GraphemeId>,
+    /// Bytes used by the lines
+    bytes: usize,
+}
+
+impl Page {
+    fn new() -> Self {
+        Self {
+            lines: PageLines::Plain(Vec::with_capacity(PAGE_LINES)),
+            styles: Vec::new(),
+            graphemes: Vec::new(),
+            bytes: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &self.lines {
+            PageLines::Plain(lines) => lines.len(),
+            PageLines::Compressed { lines, .. } => *lines,
+        }
+    }
+
+    fn is_compressed(&self) -> bool {
+        matches!(self.lines, PageLines::Compressed { .. })
+    }
+
+    /// Inflate the lines of a compressed page
+    fn inflate(data: &[u8], lines: usize) -> Vec<CompactLine> {
+        let raw = miniz_oxide::inflate::decompress_to_vec(data).expect("page was deflated here");
+        let mut input = raw.as_slice();
+        (0..lines)
+            .map(|_| CompactLine::read(&mut input).expect("page was written here"))
+            .collect()
+    }
+
+    /// The page's lines, inflated if needed
+    fn plain_lines(&self) -> std::borrow::Cow<'_, [CompactLine]> {
+        match &self.lines {
+            PageLines::Plain(lines) => lines.as_slice().into(),
+            PageLines::Compressed { data, lines } => Self::inflate(data, *lines).into(),
+        }
+    }
+
+    /// Inflate a compressed page for editing, returning the change in bytes used
+    fn decompress(&mut self) -> isize {
+        let PageLines::Compressed { data, lines } = &self.lines else {
+            return 0;
+        };
+        let plain = Self::inflate(data, *lines);
+        let before = self.bytes;
+        self.bytes = plain.iter().map(CompactLine::bytes).sum();
+        self.lines = PageLines::Plain(plain);
+        self.bytes as isize - before as isize
+    }
+
+    /// Lines of a page inflated by [`decompress`](Self::decompress)
+    fn plain_lines_mut(&mut self) -> &mut Vec<CompactLine> {
+        match &mut self.lines {
+            PageLines::Plain(lines) => lines,
+            PageLines::Compressed { .. } => unreachable!("page is decompressed before editing"),
+        }
+    }
+
+    /// Deflate the lines, returning the change in bytes used
+    fn compress(&mut self) -> isize {
+        let PageLines::Plain(lines) = &self.lines else {
+            return 0;
+        };
+        let mut raw = Vec::with_capacity(self.bytes);
+        for line in lines {
+            line.write(&mut raw);
+        }
+        let data: Box<[u8]> = miniz_oxide::deflate::compress_to_vec(&raw, COMPRESSION_LEVEL).into();
+        let before = self.bytes;
+        self.bytes = data.len();
+        self.lines = PageLines::Compressed {
+            data,
+            lines: lines.len(),
+        };
+        self.bytes as isize - before as isize
+    }
+
+    /// Hold a single table reference per style
+    fn dedup_styles(&mut self, table: &mut StyleTable) {
+        self.styles.sort_unstable();
+        let mut unique = Vec::with_capacity(self.styles.len());
+        for &id in &self.styles {
+            if unique.last() == Some(&id) {
+                table.release(id);
+            } else {
+                unique.push(id);
+            }
+        }
+        self.styles = unique;
+    }
+
+    /// Take a reference to each grapheme cluster in `line`
+    fn acquire_graphemes(&mut self, line: &CompactLine, refs: &mut GraphemeRefs) {
+        for id in line.sparse.iter().filter_map(|cell| cell.grapheme) {
+            refs.acquire(id);
+            self.graphemes.push(id);
+        }
+    }
+
+    /// Hold a single reference per style and grapheme cluster the page's
+    /// lines still use, releasing those of lines that were replaced or removed
+    fn retain_used_refs(&mut self, table: &mut StyleTable, graphemes: &mut GraphemeRefs) {
+        let (used, mut used_graphemes) = {
+            let lines = self.plain_lines();
+            let mut used: Vec<u32> = lines
+                .iter()
+                .flat_map(|line| line.runs.iter().map(|&(_, id)| id))
+                .collect();
+            used.sort_unstable();
+            used.dedup();
+            let graphemes: HashSet<GraphemeId> = lines
+                .iter()
+                .flat_map(|line| line.sparse.iter().filter_map(|cell| cell.grapheme))
+                .collect();
+            (used, graphemes)
+        };
+
+        self.styles.sort_unstable();
+        for &id in &self.styles {
+            if used.binary_search(&id).is_err() {
+                table.release(id);
+            }
+        }
+        self.styles.retain(|id| used.binary_search(id).is_ok());
+        self.dedup_styles(table);
+
+        // Keeps the first reference to each cluster still used
+        self.graphemes.retain(|&id| {
+            let keep = used_graphemes.remove(&id);
+            if !keep {
+                graphemes.release(id);
+            }
+            keep
+        });
+    }
+
+    /// Release the page's style and grapheme references
+    fn release(self, table: &mut StyleTable, graphemes: &mut GraphemeRefs) {
+        for id in self.styles {
+            table.release(id);
+        }
+        for id in self.graphemes {
+            graphemes.release(id);
+        }
+    }
+}
+
+/// Memory used by history, for telemetry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScrollbackStats {
+    /// Lines in history
+    pub lines: usize,
+    /// Bytes used by history lines, pages and interned styles
+    pub bytes: usize,
+    /// Byte budget history is kept within
+    pub budget: usize,
+    /// Pages of lines
+    pub pages: usize,
+    /// Pages stored compressed
+    pub compressed_pages: usize,
+    /// Distinct styles referenced
+    pub styles: usize,
+}
+
+impl ScrollbackStats {
+    /// Average bytes used per history line
+    pub fn bytes_per_line(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.bytes as f64 / self.lines as f64
+        }
+    }
+
+    /// Report these figures to a telemetry collector
+    pub fn record(&self, telemetry: &mut Telemetry) {
+        telemetry.record_event(TelemetryEvent::ScrollbackMemory {
+            lines: self.lines as u64,
+            bytes: self.bytes as u64,
+            bytes_per_line: self.bytes_per_line(),
+            compressed_pages: self.compressed_pages as u64,
+        });
+    }
+}
+
+/// Lines that scrolled off the top of the primary screen, oldest first
+#[derive(Debug)]
+pub(crate) struct History {
+    /// Pages oldest first; all but the newest are full
+    pages: VecDeque<Page>,
+    /// Lines already dropped from the front of the oldest page
+    skip: usize,
+    /// Lines in history
+    len: usize,
+    /// Bytes used by all pages
+    bytes: usize,
+    styles: StyleTable,
+    graphemes: GraphemeRefs,
+    /// Most lines kept
+    max_lines: usize,
+    /// Most bytes kept, counting pages and styles
+    max_bytes: usize,
+    /// Whether old pages are compressed
+    compress: bool,
+    /// Lines ever dropped from the front, so lines can be numbered stably
+    dropped: usize,
+}
+
+impl History {
+    pub(crate) fn new(max_lines: usize, max_bytes: usize, compress: bool) -> Self {
+        Self {
+            pages: VecDeque::new(),
+            skip: 0,
+            len: 0,
+            bytes: 0,
+            styles: StyleTable::default(),
+            graphemes: GraphemeRefs::default(),
+            max_lines,
+            max_bytes,
+            compress,
+            dropped: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Lines dropped from the front since history was created
+    ///
+    /// Adding this to a line's index numbers it stably as lines come and go.
+    pub(crate) fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    pub(crate) fn max_lines(&self) -> usize {
+        self.max_lines
+    }
+
+    /// Change the limits, dropping the oldest lines if now over them
+    pub(crate) fn set_limits(&mut self, max_lines: usize, max_bytes: usize) {
+        self.max_lines = max_lines;
+        self.max_bytes = max_bytes;
+        self.trim();
+    }
+
+    /// Turn compression of old pages on or off
+    ///
+    /// Pages already compressed stay so until they are read for editing.
+    pub(crate) fn set_compress(&mut self, compress: bool) {
+        self.compress = compress;
+        if compress {
+            for index in 0..self.pages.len() {
+                self.compress_if_old(index);
+            }
+        }
+    }
+
+    /// Bytes used, counting the style table
+    fn total_bytes(&self) -> usize {
+        self.bytes + self.styles.bytes() + self.pages.len() * size_of::<Page>()
+    }
+
+    /// Append a line scrolled off the screen
+    pub(crate) fn push(&mut self, row: &[Cell]) {
+        if self.max_lines == 0 {
+            return;
+        }
+        if !self
+            .pages
+            .back()
+            .is_some_and(|page| page.len() < PAGE_LINES)
+        {
+            if let Some(full) = self.pages.back_mut() {
+                full.dedup_styles(&mut self.styles);
+            }
+            self.pages.push_back(Page::new());
+            if let Some(index) = self.pages.len().checked_sub(HOT_PAGES + 2) {
+                self.compress_if_old(index);
+            }
+        }
+        self.decompress_page(self.pages.len() - 1);
+        let page = self.pages.back_mut().expect("page pushed above");
+        let line = CompactLine::encode(row, &mut self.styles, &mut page.styles);
+        page.acquire_graphemes(&line, &mut self.graphemes);
+        page.bytes += line.bytes();
+        self.bytes += line.bytes();
+        page.plain_lines_mut().push(line);
+        self.len += 1;
+        self.trim();
+    }
+
+    /// Inflate page `index` so its lines can be edited
+    fn decompress_page(&mut self, index: usize) {
+        let delta = self.pages[index].decompress();
+        self.bytes = self.bytes.saturating_add_signed(delta);
+    }
+
+    /// Compress page `index` if compression is on and it is old enough
+    fn compress_if_old(&mut self, index: usize) {
+        if !self.compress || index + HOT_PAGES + 1 >= self.pages.len() {
+            return;
+        }
+        let page = &mut self.pages[index];
+        if !page.is_compressed() {
+            self.bytes = self.bytes.saturating_add_signed(page.compress());
+        }
+    }
+
+    /// Drop the oldest lines until within the line limit and byte budget
+    fn trim(&mut self) {
+        while self.len > self.max_lines {
+            self.drop_front(1);
+        }
+        // The newest page is kept so the line just pushed is not lost
+        while self.total_bytes() > self.max_bytes && self.pages.len() > 1 {
+            let lines = self.pages[0].len() - self.skip;
+            self.drop_front(lines);
+        }
+    }
+
+    /// Drop `count` lines from the front, freeing pages that empty
+    fn drop_front(&mut self, count: usize) {
+        let count = count.min(self.len);
+        self.len -= count;
+        self.skip += count;
+        self.dropped += count;
+        while let Some(page) = self.pages.front() {
+            if self.skip < page.len() {
+                break;
+            }
+            self.skip -= page.len();
+            let page = self.pages.pop_front().expect("front page exists");
+            self.bytes -= page.bytes;
+            page.release(&mut self.styles, &mut self.graphemes);
+        }
+        if self.len == 0 {
+            self.clear();
+        }
+    }
+
+    /// Remove every line
+    pub(crate) fn clear(&mut self) {
+        self.dropped += self.len;
+        for page in std::mem::take(&mut self.pages) {
+            page.release(&mut self.styles, &mut self.graphemes);
+        }
+        self.skip = 0;
+        self.len = 0;
+        self.bytes = 0;
+    }
+
+    /// Page and offset holding line `index`
+    fn locate(&self, index: usize) -> (usize, usize) {
+        let index = index + self.skip;
+        (index / PAGE_LINES, index % PAGE_LINES)
+    }
+
+    /// Lines in `range`, 0 being the oldest, inflating each page involved once
+    pub(crate) fn lines(&self, range: Range<usize>) -> Vec<CellRow> {
+        let range = range.start.min(self.len)..range.end.min(self.len);
+        let mut rows = Vec::with_capacity(range.len());
+        let mut index = range.start;
+        while index < range.end {
+            let (page, offset) = self.locate(index);
+            let lines = self.pages[page].plain_lines();
+            let count = (PAGE_LINES - offset).min(range.end - index);
+            let count = count.min(lines.len() - offset);
+            rows.extend(
+                lines[offset..offset + count]
+                    .iter()
+                    .map(|line| line.decode(&self.styles)),
+            );
+            index += count;
+        }
+        rows
+    }
+
+    /// Index of the first line a resize can rewrap without inflating a page
+    ///
+    /// Lines up to the end of the newest compressed page keep the width they
+    /// were stored with, as does a logical line running on past that page.
+    pub(crate) fn rewrap_start(&self) -> usize {
+        let Some(last) = self.pages.iter().rposition(Page::is_compressed) else {
+            return 0;
+        };
+        let mut start = self
+            .pages
+            .iter()
+            .take(last + 1)
+            .map(Page::len)
+            .sum::<usize>()
+            - self.skip;
+        while start < self.len && self.is_wrapped(start - 1) {
+            start += 1;
+        }
+        start
+    }
+
+    /// Whether line `index` continues on the next line
+    fn is_wrapped(&self, index: usize) -> bool {
+        let (page, offset) = self.locate(index);
+        self.pages[page].plain_lines()[offset]
+            .row_flags
+            .contains(CellFlags::WRAPPED)
+    }
+
+    /// Whether a line in history shows grapheme cluster `id`
+    pub(crate) fn holds_grapheme(&self, id: GraphemeId) -> bool {
+        self.graphemes.refs.contains_key(&id)
+    }
+
+    /// Replace line `index`, e.g. after a cell in view was edited
+    pub(crate) fn replace(&mut self, index: usize, row: &[Cell]) {
+        if index >= self.len {
+            return;
+        }
+        let (page, offset) = self.locate(index);
+        self.decompress_page(page);
+        let page = &mut self.pages[page];
+        let line = CompactLine::encode(row, &mut self.styles, &mut page.styles);
+        page.acquire_graphemes(&line, &mut self.graphemes);
+        let added = line.bytes();
+        let freed = std::mem::replace(&mut page.plain_lines_mut()[offset], line).bytes();
+        page.retain_used_refs(&mut self.styles, &mut self.graphemes);
+        page.bytes = page.bytes + added - freed;
+        self.bytes = self.bytes + added - freed;
+    }
+
+    /// Remove and return the lines from `at` on, oldest first
+    pub(crate) fn split_off(&mut self, at: usize) -> Vec<CellRow> {
+        let rows = self.lines(at..self.len);
+        while self.len > at {
+            self.decompress_page(self.pages.len() - 1);
+            let page = self.pages.back_mut().expect("history is not empty");
+            let count = (self.len - at).min(page.len());
+            let lines = page.plain_lines_mut();
+            let freed: usize = lines
+                .drain(lines.len() - count..)
+                .map(|line| line.bytes())
+                .sum();
+            page.bytes -= freed;
+            self.bytes -= freed;
+            self.len -= count;
+            if page.len() == 0 {
+                let page = self.pages.pop_back().expect("checked above");
+                page.release(&mut self.styles, &mut self.graphemes);
+            } else {
+                page.retain_used_refs(&mut self.styles, &mut self.graphemes);
+            }
+        }
+        if self.len == 0 {
+            self.clear();
+        }
+        rows
+    }
+
+    /// Memory used
+    pub(crate) fn stats(&self) -> ScrollbackStats {
+        ScrollbackStats {
+            lines: self.len,
+            bytes: self.total_bytes(),
+            budget: self.max_bytes,
+            pages: self.pages.len(),
+            compressed_pages: self
+                .pages
+                .iter()
+                .filter(|page| page.is_compressed())
+                .count(),
+            styles: self.styles.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    fn styled_row(text: &str, width: usize) -> CellRow {
+        let mut row: CellRow = text.chars().map(|c| Cell::new(c as u32)).collect();
+        if let Some(cell) = row.get_mut(1) {
+            cell.fg_color = CellColor::Indexed(1);
+            cell.attrs = CellAttrs::BOLD;
+        }
+        row.resize(width, Cell::empty());
+        row
+    }
+
+    #[test]
+    fn test_lines_round_trip() {
+        let mut history = History::new(100, usize::MAX, false);
+        let mut wide = styled_row("a中 é", 20);
+        wide[1].flags = CellFlags::WIDE;
+        wide[2].flags = CellFlags::WIDE_SPACER;
+        wide[4].grapheme = Some(GraphemeId::from_raw(3).unwrap());
+        wide[7].bg_color = CellColor::Rgb(Color::rgb(1, 2, 3));
+        wide[19].flags = CellFlags::WRAPPED | CellFlags::DOUBLE_WIDTH;
+        let mut invalid = styled_row("x", 5);
+        invalid[2].glyph_id = 0xD800;
+
+        for row in [&wide, &invalid, &vec![Cell::empty(); 8], &Vec::new()] {
+            history.push(row);
+        }
+        assert_eq!(history.len(), 4);
+        assert_eq!(
+            history.lines(0..4),
+            [wide, invalid, vec![Cell::empty(); 8], Vec::new()]
+        );
+    }
+
+    #[test]
+    fn test_trailing_blanks_not_stored() {
+        let mut history = History::new(100, usize::MAX, false);
+        history.push(&styled_row("hi", 300));
+        let line_bytes = history.stats().bytes;
+        assert!(line_bytes < 300 * size_of::<Cell>() / 10, "{line_bytes}");
+        assert_eq!(history.lines(0..1)[0].len(), 300);
+    }
+
+    #[test]
+    fn test_line_limit_and_styles_released() {
+        let mut history = History::new(300, usize::MAX, false);
+        for i in 0..600u32 {
+            let mut row = styled_row("line", 10);
+            row[0].fg_color = CellColor::Rgb(Color::rgb(i as u8, (i >> 8) as u8, 0));
+            history.push(&row);
+        }
+        assert_eq!(history.len(), 300);
+        assert_eq!(history.dropped(), 300);
+        assert_eq!(history.lines(0..1)[0][0].fg_color, {
+            CellColor::Rgb(Color::rgb(44, 1, 0))
+        });
+        // Only pages still holding lines keep their styles alive
+        let stats = history.stats();
+        assert_eq!(stats.pages, 2);
+        assert!(stats.styles <= 2 * PAGE_LINES + 2, "{stats:?}");
+    }
+
+    #[test]
+    fn test_byte_budget_drops_pages() {
+        let mut history = History::new(usize::MAX, 64 * 1024, false);
+        for _ in 0..PAGE_LINES * 8 {
+            history.push(&styled_row(&"x".repeat(80), 80));
+        }
+        let stats = history.stats();
+        assert!(stats.bytes <= 64 * 1024, "{stats:?}");
+        assert_eq!(history.len() % PAGE_LINES, 0);
+        assert!(history.len() >= PAGE_LINES);
+    }
+
+    #[test]
+    fn test_old_pages_compressed() {
+        let mut plain = History::new(usize::MAX, usize::MAX, false);
+        let mut compressed = History::new(usize::MAX, usize::MAX, true);
+        let rows: Vec<CellRow> = (0..PAGE_LINES * 5)
+            .map(|i| styled_row(&format!("line {i} of some repetitive output"), 80))
+            .collect();
+        for row in &rows {
+            plain.push(row);
+            compressed.push(row);
+        }
+
+        let stats = compressed.stats();
+        assert_eq!(stats.pages, 5);
+        assert_eq!(stats.compressed_pages, 5 - HOT_PAGES - 1);
+        assert!(stats.bytes < plain.stats().bytes);
+        assert_eq!(compressed.lines(0..rows.len()), rows);
+
+        // Editing a compressed line inflates its page
+        compressed.replace(3, &rows[4]);
+        assert_eq!(compressed.lines(3..4)[0], rows[4]);
+        assert_eq!(compressed.stats().compressed_pages, 5 - HOT_PAGES - 2);
+    }
+
+    #[test]
+    fn test_replace_releases_styles() {
+        let mut history = History::new(100, usize::MAX, false);
+        history.push(&styled_row("keep", 10));
+        history.push(&styled_row("edit", 10));
+        let styles = history.stats().styles;
+        for i in 0..50u8 {
+            let mut row = styled_row("edit", 10);
+            row[0].fg_color = CellColor::Rgb(Color::rgb(i, 0, 0));
+            history.replace(1, &row);
+        }
+        assert_eq!(history.stats().styles, styles + 1);
+
+        // Styles used by both lines stay until neither does
+        history.replace(1, &vec![Cell::empty(); 10]);
+        assert_eq!(history.stats().styles, styles);
+        assert_eq!(history.lines(0..1)[0], styled_row("keep", 10));
+        history.split_off(0);
+        assert_eq!(history.stats().styles, 0);
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut history = History::new(1000, usize::MAX, false);
+        let rows: Vec<CellRow> = (0..PAGE_LINES + 3)
+            .map(|i| styled_row(&i.to_string(), 6))
+            .collect();
+        for row in &rows {
+            history.push(row);
+        }
+        assert_eq!(history.split_off(PAGE_LINES - 1), rows[PAGE_LINES - 1..]);
+        assert_eq!(history.len(), PAGE_LINES - 1);
+        assert_eq!(history.stats().pages, 1);
+
+        history.push(&rows[0]);
+        assert_eq!(history.lines(PAGE_LINES - 1..PAGE_LINES), [rows[0].clone()]);
+        assert_eq!(history.split_off(0).len(), PAGE_LINES);
+        assert_eq!(history.len(), 0);
+        assert_eq!(history.stats().styles, 0);
+        assert!(history.split_off(0).is_empty());
+    }
+
+    #[test]
+    fn test_stats_reported_to_telemetry() {
+        let mut history = History::new(100, usize::MAX, false);
+        history.push(&styled_row("hello", 10));
+        let stats = history.stats();
+        assert_eq!(stats.lines, 1);
+        assert!(stats.bytes_per_line() > 0.0);
+
+        let mut telemetry = Telemetry::new();
+        stats.record(&mut telemetry);
+        match telemetry.recent_events().last() {
+            Some(&TelemetryEvent::ScrollbackMemory {
+                lines,
+                bytes,
+                bytes_per_line,
+                compressed_pages,
+            }) => {
+                assert_eq!(lines, 1);
+                assert_eq!(bytes, stats.bytes as u64);
+                assert_eq!(bytes_per_line, stats.bytes_per_line());
+                assert_eq!(compressed_pages, 0);
+            }
+            other => panic!("expected scrollback memory event, got {:?}", other),
+        }
+    }
+}
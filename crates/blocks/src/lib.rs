@@ -6,11 +6,39 @@
 #![deny(unsafe_code)]
 
 use bitflags::bitflags;
+use grapheme::extends_cluster;
+use history::History;
+use quantaterm_core::{
+    AmbiguousWidth, CharsetSlot, CursorShape, CursorStyle, DecMode, DisplayErase, EscAction,
+    LineErase, LineSize, Mode, ModeAction, TabClear,
+};
 use std::collections::VecDeque;
 use tracing::{debug, instrument, trace, warn};
 
+pub mod charset;
+pub mod damage;
+pub mod grapheme;
+pub mod history;
+pub mod modes;
+pub mod palette;
+pub mod search;
+pub mod tabs;
+pub mod width;
+
+pub use charset::CharsetState;
+pub use damage::Damage;
+pub use grapheme::{GraphemeId, GraphemeTable};
+pub use history::{ScrollbackStats, DEFAULT_SCROLLBACK_BYTES};
+pub use modes::TerminalModes;
+pub use palette::{CellColor, Palette};
+pub use search::{
+    SearchCancel, SearchDirection, SearchError, SearchMatch, SearchPoint, SearchQuery,
+};
+pub use tabs::TabStops;
+pub use width::{char_width, cluster_width};
+
 /// A color representation for terminal cells
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Color {
     /// Red component (0-255)
     pub r: u8,
@@ -64,7 +92,7 @@ impl Default for Color {
 
 bitflags! {
     /// Cell attribute flags for styling
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct CellAttrs: u32 {
         /// Bold text
         const BOLD = 1 << 0;
@@ -80,6 +108,24 @@ bitflags! {
         const REVERSE = 1 << 5;
         /// Hidden/invisible text
         const HIDDEN = 1 << 6;
+        /// Dim (faint) text
+        const DIM = 1 << 7;
+        /// Double underline
+        const DOUBLE_UNDERLINE = 1 << 8;
+        /// Curly (wavy) underline
+        const CURLY_UNDERLINE = 1 << 9;
+        /// Dotted underline
+        const DOTTED_UNDERLINE = 1 << 10;
+        /// Dashed underline
+        const DASHED_UNDERLINE = 1 << 11;
+        /// Line above the text
+        const OVERLINE = 1 << 12;
+        /// Every underline style; at most one is set at a time
+        const ALL_UNDERLINES = Self::UNDERLINE.bits()
+            | Self::DOUBLE_UNDERLINE.bits()
+            | Self::CURLY_UNDERLINE.bits()
+            | Self::DOTTED_UNDERLINE.bits()
+            | Self::DASHED_UNDERLINE.bits();
     }
 }
 
@@ -89,17 +135,46 @@ impl Default for CellAttrs {
     }
 }
 
+bitflags! {
+    /// Layout flags for a cell, independent of its styling
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct CellFlags: u8 {
+        /// First column of a double-width character
+        const WIDE = 1 << 0;
+        /// Second column of a double-width character; holds no glyph of its own
+        const WIDE_SPACER = 1 << 1;
+        /// Last column of a row that continues on the next row (soft wrap)
+        const WRAPPED = 1 << 2;
+        /// Blank last column left by a double-width character that wrapped
+        const WIDE_PADDING = 1 << 3;
+        /// Kept by selective erase (DECSCA)
+        const PROTECTED = 1 << 4;
+        /// Last column of a double-width row (DECDWL)
+        const DOUBLE_WIDTH = 1 << 5;
+        /// Last column of the top half of a double-height row (DECDHL)
+        const DOUBLE_HEIGHT_TOP = 1 << 6;
+        /// Last column of the bottom half of a double-height row (DECDHL)
+        const DOUBLE_HEIGHT_BOTTOM = 1 << 7;
+    }
+}
+
 /// A terminal cell containing character data and formatting
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cell {
-    /// Unicode glyph identifier
+    /// Unicode glyph identifier; the base character when `grapheme` is set
     pub glyph_id: u32,
     /// Foreground color
-    pub fg_color: Color,
+    pub fg_color: CellColor,
     /// Background color
-    pub bg_color: Color,
+    pub bg_color: CellColor,
     /// Formatting attributes
     pub attrs: CellAttrs,
+    /// Underline color, or `None` to use the foreground color
+    pub underline_color: Option<CellColor>,
+    /// Layout flags such as double-width halves
+    pub flags: CellFlags,
+    /// Full grapheme cluster, for cells holding more than one code point
+    pub grapheme: Option<GraphemeId>,
 }
 
 impl Cell {
@@ -107,19 +182,30 @@ impl Cell {
     pub fn new(glyph_id: u32) -> Self {
         Self {
             glyph_id,
-            fg_color: Color::DEFAULT_FG,
-            bg_color: Color::DEFAULT_BG,
+            fg_color: CellColor::Default,
+            bg_color: CellColor::Default,
             attrs: CellAttrs::default(),
+            underline_color: None,
+            flags: CellFlags::empty(),
+            grapheme: None,
         }
     }
 
     /// Create a cell with custom colors and attributes
-    pub fn with_style(glyph_id: u32, fg_color: Color, bg_color: Color, attrs: CellAttrs) -> Self {
+    pub fn with_style(
+        glyph_id: u32,
+        fg_color: CellColor,
+        bg_color: CellColor,
+        attrs: CellAttrs,
+    ) -> Self {
         Self {
             glyph_id,
             fg_color,
             bg_color,
             attrs,
+            underline_color: None,
+            flags: CellFlags::empty(),
+            grapheme: None,
         }
     }
 
@@ -129,11 +215,23 @@ impl Cell {
     }
 
     /// Check if this cell is empty (space with default styling)
+    ///
+    /// Wrap flags do not count: they describe the row, not the cell.
     pub fn is_empty(&self) -> bool {
         self.glyph_id == b' ' as u32
-            && self.fg_color == Color::DEFAULT_FG
-            && self.bg_color == Color::DEFAULT_BG
+            && self.fg_color == CellColor::Default
+            && self.bg_color == CellColor::Default
             && self.attrs.is_empty()
+            && self.underline_color.is_none()
+            && !self
+                .flags
+                .intersects(CellFlags::WIDE | CellFlags::WIDE_SPACER)
+            && self.grapheme.is_none()
+    }
+
+    /// Whether this cell is the second half of a double-width character
+    pub fn is_wide_spacer(&self) -> bool {
+        self.flags.contains(CellFlags::WIDE_SPACER)
     }
 }
 
@@ -214,6 +312,38 @@ impl Selection {
     }
 }
 
+/// Cursor state saved by DECSC or mode 1048 and restored by DECRC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavedCursor {
+    /// Cursor position (col, row)
+    pub position: (u16, u16),
+    /// Foreground color in effect when saved
+    pub fg_color: CellColor,
+    /// Background color in effect when saved
+    pub bg_color: CellColor,
+    /// Attributes in effect when saved
+    pub attrs: CellAttrs,
+    /// Underline color in effect when saved
+    pub underline_color: Option<CellColor>,
+    /// Whether origin mode was enabled when saved
+    pub origin: bool,
+    /// Character set designations and shift state when saved
+    pub charsets: CharsetState,
+    /// Whether printed characters were protected from selective erase
+    pub protected: bool,
+}
+
+/// Lines and saved cursor of the screen buffer that is not displayed
+#[derive(Debug, Default)]
+struct InactiveBuffer {
+    /// Screen rows; the primary screen's history stays in the grid
+    lines: VecDeque<CellRow>,
+    /// Cursor saved while this buffer was active
+    saved_cursor: Option<SavedCursor>,
+    /// Viewport offset to restore when the buffer becomes active again
+    viewport_offset: usize,
+}
+
 /// Terminal grid with scrollback buffer and viewport management
 #[derive(Debug)]
 pub struct TerminalGrid {
@@ -221,22 +351,64 @@ pub struct TerminalGrid {
     pub cols: u16,
     /// Number of rows in the terminal viewport
     pub rows: u16,
-    /// Ring buffer for scrollback history
-    scrollback: VecDeque<CellRow>,
+    /// Rows of the active screen, always `rows` of them
+    screen: VecDeque<CellRow>,
+    /// Lines scrolled off the top of the primary screen
+    history: History,
+    /// Decoded history lines shown at the top of the viewport while scrolled back
+    history_view: Vec<CellRow>,
     /// Current viewport offset from the bottom of scrollback
     pub viewport_offset: usize,
-    /// Maximum scrollback lines to keep
-    max_scrollback: usize,
     /// Current cursor position (col, row)
     cursor_pos: (u16, u16),
+    /// A character was printed in the last column and the next one wraps first
+    pending_wrap: bool,
+    /// Cursor shape; blinking is the `cursor_blink` mode
+    cursor_shape: CursorShape,
+    /// Style restored by DECSCUSR 0 and RIS
+    default_cursor_style: CursorStyle,
     /// Current formatting state for new cells
-    current_fg: Color,
+    current_fg: CellColor,
     /// Current background formatting state for new cells  
-    current_bg: Color,
+    current_bg: CellColor,
     /// Current attributes for new cells
     current_attrs: CellAttrs,
+    /// Current underline color for new cells
+    current_underline_color: Option<CellColor>,
+    /// New cells are protected from selective erase (DECSCA)
+    current_protected: bool,
     /// Current text selection, if any
     selection: Option<Selection>,
+    /// Last graphic character printed, used by REP
+    last_printed: Option<char>,
+    /// ANSI and DEC private mode state
+    modes: TerminalModes,
+    /// Whether the alternate screen is displayed
+    alt_screen_active: bool,
+    /// Cursor saved for the active buffer
+    saved_cursor: Option<SavedCursor>,
+    /// Scrolling region as inclusive (top, bottom) screen rows (DECSTBM)
+    scroll_region: (u16, u16),
+    /// Designated character sets used to translate printed characters
+    charsets: CharsetState,
+    /// Columns with a tab stop
+    tab_stops: TabStops,
+    /// Width given to East Asian Ambiguous characters
+    ambiguous_width: AmbiguousWidth,
+    /// Grapheme clusters referenced by cells in either buffer
+    graphemes: GraphemeTable,
+    /// Colors indexed cells are drawn with, as changed by OSC 4
+    palette: Palette,
+    /// Palette that OSC 104 and RIS restore
+    theme: Palette,
+    /// The buffer that is not displayed (alternate while on primary and vice versa)
+    inactive: InactiveBuffer,
+    /// Viewport rows changed since the renderer last took them
+    damage: Damage,
+    /// Search matches to highlight, in buffer order
+    search_matches: Vec<SearchMatch>,
+    /// Match highlighted as the current one
+    current_match: Option<SearchMatch>,
 }
 
 impl TerminalGrid {
@@ -246,32 +418,58 @@ impl TerminalGrid {
     }
 
     /// Create a new terminal grid with custom scrollback size
+    ///
+    /// Sizes are clamped to at least one cell, as in [`resize`](Self::resize).
+    /// History is also kept within [`DEFAULT_SCROLLBACK_BYTES`]; see
+    /// [`set_scrollback_limits`](Self::set_scrollback_limits).
     pub fn with_scrollback(cols: u16, rows: u16, max_scrollback: usize) -> Self {
-        let mut grid = Self {
+        let cols = cols.max(1);
+        let rows = rows.max(1);
+        Self {
             cols,
             rows,
-            scrollback: VecDeque::with_capacity(max_scrollback + rows as usize),
+            screen: (0..rows)
+                .map(|_| vec![Cell::empty(); cols as usize])
+                .collect(),
+            history: History::new(max_scrollback, DEFAULT_SCROLLBACK_BYTES, false),
+            history_view: Vec::new(),
             viewport_offset: 0,
-            max_scrollback,
             cursor_pos: (0, 0),
-            current_fg: Color::DEFAULT_FG,
-            current_bg: Color::DEFAULT_BG,
+            pending_wrap: false,
+            cursor_shape: CursorShape::default(),
+            default_cursor_style: CursorStyle::default(),
+            current_fg: CellColor::Default,
+            current_bg: CellColor::Default,
             current_attrs: CellAttrs::empty(),
+            current_underline_color: None,
+            current_protected: false,
             selection: None,
-        };
-
-        // Initialize with empty rows
-        for _ in 0..rows {
-            grid.scrollback
-                .push_back(vec![Cell::empty(); cols as usize]);
+            last_printed: None,
+            modes: TerminalModes::default(),
+            alt_screen_active: false,
+            saved_cursor: None,
+            scroll_region: (0, rows.saturating_sub(1)),
+            charsets: CharsetState::new(),
+            tab_stops: TabStops::new(cols),
+            ambiguous_width: AmbiguousWidth::default(),
+            graphemes: GraphemeTable::new(),
+            palette: Palette::new(),
+            theme: Palette::new(),
+            inactive: InactiveBuffer::default(),
+            damage: Damage::full(rows),
+            search_matches: Vec::new(),
+            current_match: None,
         }
-
-        grid
     }
 
     /// Resize the terminal grid
+    ///
+    /// Sizes are clamped to at least one cell; reflowing into zero columns
+    /// would never finish.
     #[instrument(name = "grid_resize", skip(self))]
     pub fn resize(&mut self, new_cols: u16, new_rows: u16) {
+        let new_cols = new_cols.max(1);
+        let new_rows = new_rows.max(1);
         let old_cols = self.cols;
         let old_rows = self.rows;
 
@@ -286,136 +484,197 @@ impl TerminalGrid {
 
         self.cols = new_cols;
         self.rows = new_rows;
-
-        // Handle column changes - need to resize all existing rows
+        self.damage = Damage::full(new_rows);
+        self.scroll_region = (0, new_rows.saturating_sub(1));
+        // A pending wrap anchors the cursor just past the last character printed
+        let wrap_pending = std::mem::take(&mut self.pending_wrap);
         if new_cols != old_cols {
-            if new_cols > old_cols {
-                // Expand rows with empty cells
-                trace!(
-                    subsystem = "blocks",
-                    old_cols = old_cols,
-                    new_cols = new_cols,
-                    "Expanding grid columns"
-                );
-                for row in &mut self.scrollback {
-                    row.resize(new_cols as usize, Cell::empty());
-                }
-            } else {
-                // Shrinking columns - check if we need to rewrap or just truncate
-                let needs_rewrapping = self.scrollback.iter().any(|row| {
-                    // Check if any row has content beyond the new column width
-                    row.iter()
-                        .skip(new_cols as usize)
-                        .any(|cell| !cell.is_empty())
-                });
-
-                if needs_rewrapping {
-                    debug!(
-                        subsystem = "blocks",
-                        old_cols = old_cols,
-                        new_cols = new_cols,
-                        "Rewrapping lines due to content beyond new width"
-                    );
-                    self.rewrap_lines(old_cols, new_cols);
-                } else {
-                    trace!(
-                        subsystem = "blocks",
-                        old_cols = old_cols,
-                        new_cols = new_cols,
-                        "Truncating columns without rewrapping"
-                    );
-                    // Just truncate rows since no content would be lost
-                    for row in &mut self.scrollback {
-                        row.truncate(new_cols as usize);
-                    }
-                }
-            }
+            self.tab_stops = TabStops::new(new_cols);
         }
 
-        // Handle row changes
-        if new_rows > old_rows {
-            // Add new empty rows at the bottom
-            let rows_to_add = new_rows - old_rows;
-            for _ in 0..rows_to_add {
-                self.scrollback
-                    .push_back(vec![Cell::empty(); new_cols as usize]);
-            }
-        } else if new_rows < old_rows {
-            // Remove rows from the bottom, but preserve scrollback
-            let rows_to_remove = old_rows - new_rows;
-            for _ in 0..rows_to_remove {
-                if self.scrollback.len() > new_rows as usize {
-                    self.scrollback.pop_back();
-                }
+        if self.alt_screen_active {
+            // Full-screen apps redraw after a resize, so the alternate screen
+            // is only padded or cut to size
+            self.resize_alt_buffer(old_rows);
+            self.cursor_pos.0 = self.cursor_pos.0.min(new_cols.saturating_sub(1));
+            self.cursor_pos.1 = self.cursor_pos.1.min(new_rows.saturating_sub(1));
+
+            // Reflow the primary screen too so leaving the TUI restores it intact
+            std::mem::swap(&mut self.screen, &mut self.inactive.lines);
+            self.alt_screen_active = false;
+            let anchor = self
+                .inactive
+                .saved_cursor
+                .map_or(self.cursor_pos, |saved| saved.position);
+            let (col, row) = self.reflow(old_cols, anchor);
+            if let Some(saved) = &mut self.inactive.saved_cursor {
+                saved.position = (col.min(new_cols.saturating_sub(1)), row);
             }
+            self.alt_screen_active = true;
+            std::mem::swap(&mut self.screen, &mut self.inactive.lines);
+            self.inactive.viewport_offset = 0;
+        } else {
+            let (col, row) = self.cursor_pos;
+            let (col, row) = self.reflow(old_cols, (col + u16::from(wrap_pending), row));
+            self.cursor_pos = (col.min(new_cols.saturating_sub(1)), row);
+            self.pending_wrap = col >= new_cols && self.modes.autowrap;
+            // The alternate screen is simply recreated on the next switch
+            self.inactive.lines.clear();
         }
 
-        // Ensure we maintain scrollback limits
-        self.limit_scrollback();
-
-        // Adjust cursor position if needed
-        self.cursor_pos.0 = self.cursor_pos.0.min(new_cols.saturating_sub(1));
-        self.cursor_pos.1 = self.cursor_pos.1.min(new_rows.saturating_sub(1));
+        self.viewport_offset = self.viewport_offset.min(self.scrollback_len());
+        self.refresh_history_view();
+        if new_cols != old_cols {
+            // Rewrapped lines no longer line up with selections or matches
+            self.selection = None;
+            self.clear_search_highlights();
+        }
     }
 
-    /// Rewrap lines when terminal width changes
-    fn rewrap_lines(&mut self, old_cols: u16, new_cols: u16) {
-        if new_cols >= old_cols {
-            return; // No rewrapping needed when expanding
+    /// Pad or cut the alternate screen to the current dimensions
+    fn resize_alt_buffer(&mut self, old_rows: u16) {
+        let cols = self.cols as usize;
+        for line in &mut self.screen {
+            let size = row_size(line);
+            take_row_flags(line);
+            line.truncate(cols);
+            split_wide_char(line, cols);
+            line.resize(cols, Cell::empty());
+            set_row_size(line, size);
         }
+        if self.rows > old_rows {
+            for _ in old_rows..self.rows {
+                self.screen.push_back(self.blank_line());
+            }
+        } else {
+            self.screen.truncate(self.rows as usize);
+        }
+    }
 
-        let mut new_rows = Vec::new();
-
-        // Process each existing row
-        for row in self.scrollback.drain(..) {
-            // Check if this row has content beyond the new column width
-            let content_beyond_width = row
-                .iter()
-                .skip(new_cols as usize)
-                .any(|cell| !cell.is_empty());
-
-            if !content_beyond_width {
-                // Row doesn't need rewrapping, just truncate
-                let mut truncated_row = row;
-                truncated_row.truncate(new_cols as usize);
-                new_rows.push(truncated_row);
-            } else {
-                // Row needs rewrapping - collect all content and flow it
-                let mut content = Vec::new();
-
-                // Find the last non-empty cell
-                let last_non_empty = row
-                    .iter()
-                    .rposition(|cell| !cell.is_empty())
-                    .map(|i| i + 1)
-                    .unwrap_or(0);
-
-                // Collect non-empty content
-                for (i, cell) in row.into_iter().enumerate() {
-                    if i < last_non_empty {
-                        content.push(cell);
+    /// Rewrap the primary buffer to the current dimensions
+    ///
+    /// Soft-wrapped rows are joined back into logical lines and split again at
+    /// the new width, and rows cut short by an earlier resize flow onto the
+    /// next row. The cursor stays on the character it was on, and on the same
+    /// screen row where possible: history moves on or off the screen to make
+    /// room, and blank rows below the cursor are added or dropped. Returns the
+    /// new cursor position, whose column is `cols` when the cursor ends up
+    /// just past a full row.
+    ///
+    /// Only history past the newest compressed page is rewrapped, so a resize
+    /// inflates no pages; older lines keep their width and are cut or padded
+    /// when shown. When only the height changes, just the lines that may come
+    /// back on screen are taken out of history.
+    fn reflow(&mut self, old_cols: u16, cursor: (u16, u16)) -> (u16, u16) {
+        let cols = self.cols as usize;
+        let rows = self.rows as usize;
+        let keep = if cols == old_cols as usize {
+            self.history.len().saturating_sub(rows)
+        } else {
+            self.history.rewrap_start()
+        };
+        let mut buffer: VecDeque<CellRow> = self.history.split_off(keep).into();
+        let cursor_index = buffer.len() + cursor.1 as usize;
+        buffer.append(&mut self.screen);
+
+        let mut lines: VecDeque<CellRow>;
+        let mut cursor_line;
+        let mut cursor_col;
+        if cols == old_cols as usize {
+            lines = buffer;
+            cursor_line = cursor_index.min(lines.len().saturating_sub(1));
+            cursor_col = cursor.0 as usize;
+        } else {
+            debug!(
+                subsystem = "blocks",
+                old_cols = old_cols,
+                new_cols = cols,
+                lines = buffer.len(),
+                "Reflowing lines to new width"
+            );
+            lines = VecDeque::with_capacity(buffer.len());
+            (cursor_line, cursor_col) = (0, 0);
+            let mut logical = Vec::new();
+            let mut cursor_offset = None;
+            for (index, mut row) in buffer.into_iter().enumerate() {
+                // Double-size rows are cut or padded to the new width, not rewrapped
+                let size = row_size(&row);
+                if size.is_double() {
+                    if !logical.is_empty() {
+                        let moved = wrap_line(&mut logical, cols, cursor_offset.take(), &mut lines);
+                        if let Some((line, col)) = moved {
+                            (cursor_line, cursor_col) = (line, col);
+                        }
+                    }
+                    if index == cursor_index {
+                        (cursor_line, cursor_col) = (lines.len(), cursor.0 as usize);
                     }
+                    take_row_flags(&mut row);
+                    row.truncate(cols);
+                    split_wide_char(&mut row, cols);
+                    row.resize(cols, Cell::empty());
+                    set_row_size(&mut row, size);
+                    lines.push_back(row);
+                    continue;
                 }
-
-                // Flow content into new rows of new_cols width
-                while !content.is_empty() {
-                    let mut new_row = Vec::new();
-                    let take_count = (new_cols as usize).min(content.len());
-
-                    new_row.extend(content.drain(..take_count));
-                    new_row.resize(new_cols as usize, Cell::empty());
-                    new_rows.push(new_row);
+                let wrapped = is_wrapped(&row);
+                if index == cursor_index {
+                    cursor_offset = Some(logical.len() + cursor.0 as usize);
+                }
+                if wrapped {
+                    set_wrapped(&mut row, false);
+                    if row
+                        .last()
+                        .is_some_and(|cell| cell.flags.contains(CellFlags::WIDE_PADDING))
+                    {
+                        row.pop();
+                    }
+                }
+                logical.extend(row);
+                if !wrapped {
+                    let moved = wrap_line(&mut logical, cols, cursor_offset.take(), &mut lines);
+                    if let Some((line, col)) = moved {
+                        (cursor_line, cursor_col) = (line, col);
+                    }
                 }
             }
+            if !logical.is_empty() {
+                let moved = wrap_line(&mut logical, cols, cursor_offset, &mut lines);
+                if let Some((line, col)) = moved {
+                    (cursor_line, cursor_col) = (line, col);
+                }
+            }
+        }
+
+        if lines.is_empty() {
+            lines.push_back(vec![Cell::empty(); cols]);
         }
 
-        // Ensure we have at least enough rows for the viewport
-        while new_rows.len() < self.rows as usize {
-            new_rows.push(vec![Cell::empty(); new_cols as usize]);
+        // Keep the cursor on its screen row by adding or dropping blank rows below it
+        let target_row = (cursor.1 as usize).min(rows.saturating_sub(1));
+        let target_below = rows.saturating_sub(1) - target_row;
+        while lines.len() - 1 - cursor_line > target_below
+            && lines
+                .back()
+                .is_some_and(|line| line.iter().all(Cell::is_empty))
+        {
+            lines.pop_back();
+        }
+        // Content further below than the screen reaches is lost, as in xterm
+        lines.truncate(cursor_line + rows.max(1));
+        while lines.len() - 1 - cursor_line < target_below || lines.len() < rows {
+            lines.push_back(vec![Cell::empty(); cols]);
         }
 
-        // Replace scrollback with rewrapped content
-        self.scrollback = new_rows.into();
+        let row = (cursor_line + rows).saturating_sub(lines.len());
+        for line in lines.drain(..lines.len() - rows) {
+            self.history.push(&line);
+        }
+        self.screen = lines;
+        (
+            (cursor_col as u16).min(self.cols),
+            (row as u16).min(self.rows.saturating_sub(1)),
+        )
     }
 
     /// Get a cell at the given position (col, row) in the current viewport
@@ -424,44 +683,83 @@ impl TerminalGrid {
             return None;
         }
 
-        let scrollback_row = self.viewport_row_to_scrollback_index(row)?;
-        self.scrollback.get(scrollback_row)?.get(col as usize)
+        self.get_viewport_line(row)?.get(col as usize)
     }
 
     /// Set a cell at the given position (col, row) in the current viewport
+    ///
+    /// A cell on a history row in view is written back to history.
     pub fn set_cell(&mut self, col: u16, row: u16, cell: Cell) -> bool {
         if col >= self.cols || row >= self.rows {
             return false;
         }
 
-        if let Some(scrollback_row) = self.viewport_row_to_scrollback_index(row) {
-            if let Some(target_row) = self.scrollback.get_mut(scrollback_row) {
-                if let Some(target_cell) = target_row.get_mut(col as usize) {
-                    *target_cell = cell;
-                    return true;
-                }
-            }
+        let target_row = match (row as usize).checked_sub(self.viewport_offset) {
+            Some(screen_row) => self.screen.get_mut(screen_row),
+            None => self.history_view.get_mut(row as usize),
+        };
+        let Some(target_cell) = target_row.and_then(|line| line.get_mut(col as usize)) else {
+            return false;
+        };
+        *target_cell = cell;
+        if (row as usize) < self.viewport_offset {
+            let index = self.history.len() - self.viewport_offset + row as usize;
+            self.history
+                .replace(index, &self.history_view[row as usize]);
         }
-        false
+        self.damage.mark(row);
+        true
     }
 
-    /// Convert viewport row index to scrollback index
-    fn viewport_row_to_scrollback_index(&self, viewport_row: u16) -> Option<usize> {
-        let total_rows = self.scrollback.len();
-        if total_rows < self.rows as usize {
-            return Some(viewport_row as usize);
-        }
-
-        let visible_start = total_rows
-            .saturating_sub(self.rows as usize)
-            .saturating_sub(self.viewport_offset);
-        let scrollback_index = visible_start + viewport_row as usize;
-
-        if scrollback_index < total_rows {
-            Some(scrollback_index)
+    /// Decode the history lines the viewport currently shows
+    fn refresh_history_view(&mut self) {
+        let shown = self.viewport_offset.min(self.rows as usize);
+        let start = self.history.len().saturating_sub(self.viewport_offset);
+        self.history_view = if shown == 0 {
+            Vec::new()
         } else {
-            None
+            self.history_lines(start..start + shown)
+        };
+    }
+
+    /// History lines in `range`, cut or padded to the current width
+    ///
+    /// Lines a resize did not rewrap keep their width in history.
+    fn history_lines(&self, range: std::ops::Range<usize>) -> Vec<CellRow> {
+        let cols = self.cols as usize;
+        let mut lines = self.history.lines(range);
+        for line in lines.iter_mut().filter(|line| line.len() != cols) {
+            let flags = take_row_flags(line);
+            line.truncate(cols);
+            split_wide_char(line, cols);
+            line.resize(cols, Cell::empty());
+            restore_row_flags(line, flags);
+        }
+        lines
+    }
+
+    /// Update the decoded history lines after the viewport moved from `old_offset`
+    ///
+    /// Lines still in view are kept, so scrolling a few lines decodes only those.
+    fn scroll_history_view(&mut self, old_offset: usize) {
+        let len = self.history.len();
+        let old_start = len.saturating_sub(old_offset);
+        let old_end = old_start + self.history_view.len();
+        let start = len.saturating_sub(self.viewport_offset);
+        let end = start + self.viewport_offset.min(self.rows as usize);
+        let (keep_start, keep_end) = (start.max(old_start), end.min(old_end));
+        if keep_start >= keep_end {
+            self.refresh_history_view();
+            return;
         }
+
+        let mut view = self.history_lines(start..keep_start);
+        view.extend(
+            self.history_view
+                .drain(keep_start - old_start..keep_end - old_start),
+        );
+        view.extend(self.history_lines(keep_end..end));
+        self.history_view = view;
     }
 
     /// Scroll the viewport up by the given number of lines
@@ -471,11 +769,13 @@ impl TerminalGrid {
             return;
         }
 
-        let max_offset = self.scrollback.len().saturating_sub(self.rows as usize);
+        let max_offset = self.scrollback_len();
         let old_offset = self.viewport_offset;
         self.viewport_offset = (self.viewport_offset + lines).min(max_offset);
 
         if old_offset != self.viewport_offset {
+            self.scroll_history_view(old_offset);
+            self.damage.mark_all();
             trace!(
                 subsystem = "blocks",
                 lines = lines,
@@ -498,6 +798,8 @@ impl TerminalGrid {
         self.viewport_offset = self.viewport_offset.saturating_sub(lines);
 
         if old_offset != self.viewport_offset {
+            self.scroll_history_view(old_offset);
+            self.damage.mark_all();
             trace!(
                 subsystem = "blocks",
                 lines = lines,
@@ -523,10 +825,12 @@ impl TerminalGrid {
     /// Scroll to the top of available scrollback
     #[instrument(name = "grid_scroll_to_top", skip(self))]
     pub fn scroll_to_top(&mut self) {
-        let max_offset = self.scrollback.len().saturating_sub(self.rows as usize);
+        let max_offset = self.scrollback_len();
         if self.viewport_offset != max_offset {
             let old_offset = self.viewport_offset;
             self.viewport_offset = max_offset;
+            self.scroll_history_view(old_offset);
+            self.damage.mark_all();
             trace!(
                 subsystem = "blocks",
                 old_offset = old_offset,
@@ -543,6 +847,8 @@ impl TerminalGrid {
         self.viewport_offset = 0;
 
         if old_offset != 0 {
+            self.history_view.clear();
+            self.damage.mark_all();
             trace!(
                 subsystem = "blocks",
                 old_offset = old_offset,
@@ -560,34 +866,63 @@ impl TerminalGrid {
         trace!(
             subsystem = "blocks",
             line_cells = line.len(),
-            total_scrollback = self.scrollback.len(),
+            total_scrollback = self.history.len(),
             "Adding line to terminal grid"
         );
 
-        self.scrollback.push_back(line);
-        self.limit_scrollback();
+        if let Some(top) = self.screen.pop_front() {
+            if !self.alt_screen_active {
+                self.history.push(&top);
+            }
+        }
+        self.screen.push_back(line);
 
         // Reset viewport to bottom when new content is added
         self.viewport_offset = 0;
+        self.history_view.clear();
+        self.damage.mark_all();
+    }
+
+    /// Move a line that scrolled off the top of the primary screen into history
+    ///
+    /// A viewport scrolled back keeps showing the same lines while output
+    /// arrives, unless they are dropped from history.
+    fn push_history(&mut self, line: &[Cell]) {
+        self.history.push(line);
+        if self.viewport_offset > 0 {
+            let offset = (self.viewport_offset + 1).min(self.history.len());
+            let moved = offset != self.viewport_offset + 1;
+            self.viewport_offset = offset;
+            if moved {
+                self.refresh_history_view();
+            } else if self.history_view.len() < offset.min(self.rows as usize) {
+                // The line was on screen in view and is now shown from history
+                self.history_view.push(line.to_vec());
+            }
+        }
     }
 
-    /// Limit scrollback to maximum size
-    fn limit_scrollback(&mut self) {
-        let target_size = self.max_scrollback + self.rows as usize;
-        let removed_count = self.scrollback.len().saturating_sub(target_size);
-
-        if removed_count > 0 {
-            for _ in 0..removed_count {
-                self.scrollback.pop_front();
-            }
-            trace!(
-                subsystem = "blocks",
-                removed_lines = removed_count,
-                remaining_lines = self.scrollback.len(),
-                target_size = target_size,
-                "Limited scrollback buffer size"
-            );
+    /// Limit history to `max_lines` lines and about `max_bytes` bytes
+    ///
+    /// Lines are dropped a page at a time, oldest first, to meet the byte
+    /// budget; the newest page is always kept.
+    pub fn set_scrollback_limits(&mut self, max_lines: usize, max_bytes: usize) {
+        self.history.set_limits(max_lines, max_bytes);
+        if self.viewport_offset > self.scrollback_len() {
+            self.viewport_offset = self.scrollback_len();
+            self.damage.mark_all();
         }
+        self.refresh_history_view();
+    }
+
+    /// Compress pages of history that are no longer near the bottom, or not
+    pub fn set_scrollback_compression(&mut self, compress: bool) {
+        self.history.set_compress(compress);
+    }
+
+    /// Memory used by scrollback history
+    pub fn scrollback_stats(&self) -> ScrollbackStats {
+        self.history.stats()
     }
 
     /// Get current cursor position
@@ -595,79 +930,153 @@ impl TerminalGrid {
         self.cursor_pos
     }
 
+    /// Cursor shape and whether it blinks
+    pub fn cursor_style(&self) -> CursorStyle {
+        CursorStyle {
+            shape: self.cursor_shape,
+            blinking: self.modes.cursor_blink,
+        }
+    }
+
+    /// Change the cursor style (DECSCUSR), `None` restoring the default
+    ///
+    /// Blinking is shared with DEC private mode 12.
+    pub fn set_cursor_style(&mut self, style: Option<CursorStyle>) {
+        let style = style.unwrap_or(self.default_cursor_style);
+        trace!(subsystem = "blocks", style = ?style, "Set cursor style");
+        self.cursor_shape = style.shape;
+        self.modes.cursor_blink = style.blinking;
+    }
+
+    /// Set the configured cursor style and switch to it
+    pub fn set_default_cursor_style(&mut self, style: CursorStyle) {
+        self.default_cursor_style = style;
+        self.set_cursor_style(None);
+    }
+
     /// Set cursor position
     pub fn set_cursor_position(&mut self, col: u16, row: u16) {
-        self.cursor_pos = (
-            col.min(self.cols.saturating_sub(1)),
-            row.min(self.rows.saturating_sub(1)),
-        );
+        self.pending_wrap = false;
+        let row = row.min(self.rows.saturating_sub(1));
+        self.cursor_pos = (col.min(self.line_cols(row).saturating_sub(1)), row);
+    }
+
+    /// Move cursor to a position given by CUP/HVP
+    ///
+    /// In origin mode the row is relative to the top margin and the cursor
+    /// cannot leave the scrolling region.
+    pub fn cursor_goto(&mut self, col: u16, row: u16) {
+        self.cursor_goto_row(row);
+        self.move_cursor_to_column(col);
+    }
+
+    /// Move cursor to a row given by VPA, honouring origin mode
+    pub fn cursor_goto_row(&mut self, row: u16) {
+        self.pending_wrap = false;
+        if self.modes.origin {
+            let (top, bottom) = self.scroll_region;
+            self.cursor_pos.1 = top.saturating_add(row).min(bottom);
+        } else {
+            self.move_cursor_to_row(row);
+        }
     }
 
     /// Move cursor up by the specified number of lines
+    ///
+    /// The cursor stops at the top margin when it starts inside the region.
     pub fn move_cursor_up(&mut self, lines: u16) {
-        self.cursor_pos.1 = self.cursor_pos.1.saturating_sub(lines);
+        self.pending_wrap = false;
+        let top = self.scroll_region.0;
+        let limit = if self.cursor_pos.1 >= top { top } else { 0 };
+        self.cursor_pos.1 = self.cursor_pos.1.saturating_sub(lines).max(limit);
     }
 
     /// Move cursor down by the specified number of lines
+    ///
+    /// The cursor stops at the bottom margin when it starts inside the region.
     pub fn move_cursor_down(&mut self, lines: u16) {
-        self.cursor_pos.1 = (self.cursor_pos.1 + lines).min(self.rows.saturating_sub(1));
+        self.pending_wrap = false;
+        let bottom = self.scroll_region.1;
+        let limit = if self.cursor_pos.1 <= bottom {
+            bottom
+        } else {
+            self.rows.saturating_sub(1)
+        };
+        self.cursor_pos.1 = self.cursor_pos.1.saturating_add(lines).min(limit);
     }
 
     /// Move cursor left by the specified number of columns
     pub fn move_cursor_left(&mut self, cols: u16) {
+        self.pending_wrap = false;
         self.cursor_pos.0 = self.cursor_pos.0.saturating_sub(cols);
     }
 
     /// Move cursor right by the specified number of columns
     pub fn move_cursor_right(&mut self, cols: u16) {
-        self.cursor_pos.0 = (self.cursor_pos.0 + cols).min(self.cols.saturating_sub(1));
+        self.pending_wrap = false;
+        let last_col = self.line_cols(self.cursor_pos.1).saturating_sub(1);
+        self.cursor_pos.0 = (self.cursor_pos.0 + cols).min(last_col);
     }
 
     /// Move cursor to specific column on current row
     pub fn move_cursor_to_column(&mut self, col: u16) {
-        self.cursor_pos.0 = col.min(self.cols.saturating_sub(1));
+        self.pending_wrap = false;
+        self.cursor_pos.0 = col.min(self.line_cols(self.cursor_pos.1).saturating_sub(1));
     }
 
     /// Move cursor to specific row on current column
     pub fn move_cursor_to_row(&mut self, row: u16) {
+        self.pending_wrap = false;
         self.cursor_pos.1 = row.min(self.rows.saturating_sub(1));
     }
 
     /// Move cursor to beginning of current line
     pub fn move_cursor_to_line_start(&mut self) {
+        self.pending_wrap = false;
         self.cursor_pos.0 = 0;
     }
 
     /// Move cursor to end of current line
     pub fn move_cursor_to_line_end(&mut self) {
-        self.cursor_pos.0 = self.cols.saturating_sub(1);
+        self.pending_wrap = false;
+        self.cursor_pos.0 = self.line_cols(self.cursor_pos.1).saturating_sub(1);
     }
 
     /// Clear the entire grid
     pub fn clear(&mut self) {
-        self.scrollback.clear();
-        for _ in 0..self.rows {
-            self.scrollback
-                .push_back(vec![Cell::empty(); self.cols as usize]);
+        if !self.alt_screen_active {
+            self.history.clear();
         }
+        self.screen = (0..self.rows)
+            .map(|_| vec![Cell::empty(); self.cols as usize])
+            .collect();
         self.viewport_offset = 0;
+        self.history_view.clear();
         self.cursor_pos = (0, 0);
+        self.pending_wrap = false;
         self.selection = None;
+        self.clear_search_highlights();
+        self.damage.mark_all();
     }
 
     /// Get the number of scrollback lines available
     pub fn scrollback_len(&self) -> usize {
-        self.scrollback.len().saturating_sub(self.rows as usize)
+        if self.alt_screen_active {
+            // The alternate screen keeps no history
+            0
+        } else {
+            self.history.len()
+        }
     }
 
     /// Get the maximum scrollback capacity
     pub fn max_scrollback(&self) -> usize {
-        self.max_scrollback
+        self.history.max_lines()
     }
 
     /// Get the current total lines in the buffer (including viewport)
     pub fn total_lines(&self) -> usize {
-        self.scrollback.len()
+        self.scrollback_len() + self.screen.len()
     }
 
     /// Check if the viewport is at the bottom (normal terminal state)
@@ -685,12 +1094,8 @@ impl TerminalGrid {
         let mut viewport = Vec::with_capacity(self.rows as usize);
 
         for row in 0..self.rows {
-            if let Some(scrollback_row) = self.viewport_row_to_scrollback_index(row) {
-                if let Some(line) = self.scrollback.get(scrollback_row) {
-                    viewport.push(line.clone());
-                } else {
-                    viewport.push(vec![Cell::empty(); self.cols as usize]);
-                }
+            if let Some(line) = self.get_viewport_line(row) {
+                viewport.push(line.clone());
             } else {
                 viewport.push(vec![Cell::empty(); self.cols as usize]);
             }
@@ -706,127 +1111,404 @@ impl TerminalGrid {
             .iter()
             .map(|row| {
                 // Convert each row to a string, handling Unicode properly
-                row.iter()
-                    .map(|cell| {
-                        // For now, just treat glyph_id as ASCII
-                        // In a full implementation, this would handle Unicode properly
-                        if cell.glyph_id == 0 || cell.is_empty() {
-                            ' '
-                        } else {
-                            (cell.glyph_id as u8) as char
-                        }
-                    })
-                    .collect()
+                let mut text = String::with_capacity(row.len());
+                for cell in row.iter().filter(|cell| !cell.is_wide_spacer()) {
+                    push_cell_text(&self.graphemes, cell, &mut text);
+                }
+                text
             })
             .collect()
     }
 
     /// Update the renderer with current viewport content
     /// This provides integration with the renderer for color and attribute display
-    pub fn update_renderer(&self, renderer: &mut quantaterm_renderer::Renderer) {
-        let viewport = self.get_viewport();
+    ///
+    /// Cell colors are resolved through the current palette here, so palette
+    /// changes apply to everything already on screen. Only rows damaged
+    /// since the last update are converted and sent.
+    pub fn update_renderer(&mut self, renderer: &mut quantaterm_renderer::Renderer) {
+        let damage = self.take_damage();
+        if damage.is_full() {
+            renderer.update_viewport((0..self.rows).map(|row| self.renderer_row(row)).collect());
+        } else if !damage.is_empty() {
+            trace!(
+                subsystem = "blocks",
+                scrolled = damage.scrolled(),
+                rows = damage.rows().count(),
+                "Updating damaged renderer rows"
+            );
+            renderer.scroll_viewport(damage.scrolled() as usize);
+            renderer.update_rows(
+                damage
+                    .rows()
+                    .map(|row| (row as usize, self.renderer_row(row)))
+                    .collect(),
+            );
+        }
+        // Line sizes only change on damaged rows
+        if !damage.is_empty() {
+            renderer.update_line_sizes((0..self.rows).map(|row| self.line_size(row)).collect());
+        }
+        renderer.update_cursor(self.renderer_cursor());
+    }
 
-        // Convert blocks cells to renderer cells
-        let renderer_viewport: Vec<Vec<quantaterm_renderer::RendererCell>> = viewport
-            .iter()
-            .map(|row| {
-                row.iter()
-                    .map(|cell| {
-                        quantaterm_renderer::RendererCell::with_style(
-                            cell.glyph_id,
-                            quantaterm_renderer::RendererColor::rgb(
-                                cell.fg_color.r,
-                                cell.fg_color.g,
-                                cell.fg_color.b,
-                            ),
-                            quantaterm_renderer::RendererColor::rgb(
-                                cell.bg_color.r,
-                                cell.bg_color.g,
-                                cell.bg_color.b,
-                            ),
-                            // Convert cell attributes to renderer attributes
-                            {
-                                let mut renderer_attrs =
-                                    quantaterm_renderer::RendererCellAttrs::empty();
-                                if cell.attrs.contains(CellAttrs::BOLD) {
-                                    renderer_attrs |= quantaterm_renderer::RendererCellAttrs::BOLD;
-                                }
-                                if cell.attrs.contains(CellAttrs::ITALIC) {
-                                    renderer_attrs |=
-                                        quantaterm_renderer::RendererCellAttrs::ITALIC;
-                                }
-                                if cell.attrs.contains(CellAttrs::UNDERLINE) {
-                                    renderer_attrs |=
-                                        quantaterm_renderer::RendererCellAttrs::UNDERLINE;
-                                }
-                                if cell.attrs.contains(CellAttrs::STRIKETHROUGH) {
-                                    renderer_attrs |=
-                                        quantaterm_renderer::RendererCellAttrs::STRIKETHROUGH;
-                                }
-                                if cell.attrs.contains(CellAttrs::BLINK) {
-                                    renderer_attrs |= quantaterm_renderer::RendererCellAttrs::BLINK;
-                                }
-                                if cell.attrs.contains(CellAttrs::REVERSE) {
-                                    renderer_attrs |=
-                                        quantaterm_renderer::RendererCellAttrs::REVERSE;
-                                }
-                                if cell.attrs.contains(CellAttrs::HIDDEN) {
-                                    renderer_attrs |=
-                                        quantaterm_renderer::RendererCellAttrs::HIDDEN;
-                                }
-                                renderer_attrs
-                            },
-                        )
-                    })
-                    .collect()
+    /// Convert viewport row `row` to renderer cells
+    fn renderer_row(&self, row: u16) -> quantaterm_renderer::RendererCellRow {
+        let Some(line) = self.get_viewport_line(row) else {
+            return vec![quantaterm_renderer::RendererCell::new(b' ' as u32); self.cols as usize];
+        };
+        let selection = self.selection.as_ref();
+        let search = self.search_attrs(row);
+        line.iter()
+            .enumerate()
+            .map(|(col, cell)| {
+                quantaterm_renderer::RendererCell::with_style(
+                    cell.glyph_id,
+                    renderer_color(self.palette.resolve_fg(cell.fg_color)),
+                    renderer_color(self.palette.resolve_bg(cell.bg_color)),
+                    // Convert cell attributes to renderer attributes
+                    {
+                        let mut renderer_attrs = quantaterm_renderer::RendererCellAttrs::empty();
+                        if cell.attrs.contains(CellAttrs::BOLD) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::BOLD;
+                        }
+                        if cell.attrs.contains(CellAttrs::ITALIC) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::ITALIC;
+                        }
+                        if cell.attrs.contains(CellAttrs::UNDERLINE) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::UNDERLINE;
+                        }
+                        if cell.attrs.contains(CellAttrs::STRIKETHROUGH) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::STRIKETHROUGH;
+                        }
+                        if cell.attrs.contains(CellAttrs::BLINK) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::BLINK;
+                        }
+                        if cell.attrs.contains(CellAttrs::REVERSE) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::REVERSE;
+                        }
+                        if cell.attrs.contains(CellAttrs::HIDDEN) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::HIDDEN;
+                        }
+                        if cell.attrs.contains(CellAttrs::DIM) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::DIM;
+                        }
+                        if cell.attrs.contains(CellAttrs::DOUBLE_UNDERLINE) {
+                            renderer_attrs |=
+                                quantaterm_renderer::RendererCellAttrs::DOUBLE_UNDERLINE;
+                        }
+                        if cell.attrs.contains(CellAttrs::CURLY_UNDERLINE) {
+                            renderer_attrs |=
+                                quantaterm_renderer::RendererCellAttrs::CURLY_UNDERLINE;
+                        }
+                        if cell.attrs.contains(CellAttrs::DOTTED_UNDERLINE) {
+                            renderer_attrs |=
+                                quantaterm_renderer::RendererCellAttrs::DOTTED_UNDERLINE;
+                        }
+                        if cell.attrs.contains(CellAttrs::DASHED_UNDERLINE) {
+                            renderer_attrs |=
+                                quantaterm_renderer::RendererCellAttrs::DASHED_UNDERLINE;
+                        }
+                        if cell.attrs.contains(CellAttrs::OVERLINE) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::OVERLINE;
+                        }
+                        if selection.is_some_and(|selection| {
+                            selection.contains(Position::new(col as u16, row))
+                        }) {
+                            renderer_attrs |= quantaterm_renderer::RendererCellAttrs::SELECTED;
+                        }
+                        if let Some(&search) = search.get(col) {
+                            renderer_attrs |= search;
+                        }
+                        renderer_attrs
+                    },
+                )
+                .with_underline_color(
+                    cell.underline_color
+                        .map(|color| renderer_color(self.palette.resolve_fg(color))),
+                )
             })
-            .collect();
+            .collect()
+    }
 
-        renderer.update_viewport(renderer_viewport);
+    /// The cursor as the renderer draws it, unless hidden (DECTCEM) or
+    /// scrolled out of the viewport
+    fn renderer_cursor(&self) -> Option<quantaterm_renderer::RendererCursor> {
+        if !self.modes.cursor_visible {
+            return None;
+        }
+        let (col, row) = self.cursor_pos;
+        let col = col.min(self.line_cols(row).saturating_sub(1));
+        let row = self.screen_to_viewport_row(row)?;
+        let wide = self
+            .get_cell(col, row)
+            .is_some_and(|cell| cell.flags.contains(CellFlags::WIDE));
+        Some(quantaterm_renderer::RendererCursor {
+            col,
+            row,
+            width: if wide { 2 } else { 1 },
+            style: self.cursor_style(),
+            color: renderer_color(self.palette.foreground()),
+        })
     }
 
     /// Apply SGR (Select Graphic Rendition) formatting attributes
     /// This updates the current formatting state for new cells
-    pub fn apply_sgr(&mut self, fg_color: Color, bg_color: Color, attrs: CellAttrs) {
+    pub fn apply_sgr(&mut self, fg_color: CellColor, bg_color: CellColor, attrs: CellAttrs) {
         self.current_fg = fg_color;
         self.current_bg = bg_color;
         self.current_attrs = attrs;
 
         debug!(
             subsystem = "blocks",
-            fg_r = fg_color.r, fg_g = fg_color.g, fg_b = fg_color.b,
-            bg_r = bg_color.r, bg_g = bg_color.g, bg_b = bg_color.b,
+            fg = ?fg_color,
+            bg = ?bg_color,
             attrs = ?attrs,
             "Applied SGR formatting"
         );
     }
 
+    /// Set the underline color for new cells (SGR 58/59)
+    pub fn set_underline_color(&mut self, color: Option<CellColor>) {
+        self.current_underline_color = color;
+    }
+
+    /// Current underline color for new cells
+    pub fn current_underline_color(&self) -> Option<CellColor> {
+        self.current_underline_color
+    }
+
     /// Print a character at the cursor position with current formatting
+    ///
+    /// Double-width characters take the cursor cell and a spacer cell after
+    /// it. One that does not fit in the last column wraps as a whole, leaving
+    /// that column blank.
     pub fn print_char(&mut self, c: char) {
-        let glyph_id = c as u32;
+        let c = self.charsets.translate(c);
+        if self.extend_previous_cluster(c) {
+            return;
+        }
+        // The previous character filled the last column; wrap before this one
+        if std::mem::take(&mut self.pending_wrap) && self.modes.autowrap {
+            self.soft_wrap();
+        }
+
+        // A vertical move may have left the cursor past the edge of a double-size row
+        let cols = self.line_cols(self.cursor_pos.1);
+        self.cursor_pos.0 = self.cursor_pos.0.min(cols - 1);
+        // Zero-width characters with nothing to attach to take a cell of their own
+        let width = char_width(c, self.ambiguous_width).clamp(1, cols.min(2) as usize) as u16;
+
+        if width == 2 && self.cursor_pos.0 + 1 >= cols {
+            if self.modes.autowrap {
+                let (col, row) = self.cursor_pos;
+                self.erase_row_range(row, col, cols, false);
+                let padding = self
+                    .screen_line_mut(row)
+                    .and_then(|line| line.get_mut(cols as usize - 1));
+                if let Some(padding) = padding {
+                    padding.flags |= CellFlags::WIDE_PADDING;
+                }
+                self.soft_wrap();
+                let cols = self.line_cols(self.cursor_pos.1);
+                if cols < 2 {
+                    // Nowhere left that the character fits
+                    return;
+                }
+            } else {
+                self.cursor_pos.0 = cols - 2;
+            }
+        }
+
+        if self.modes.insert {
+            self.insert_chars(width);
+        }
 
         // Create cell with current formatting
         let cell = Cell {
-            glyph_id,
+            glyph_id: c as u32,
             fg_color: self.current_fg,
             bg_color: self.current_bg,
             attrs: self.current_attrs,
+            underline_color: self.current_underline_color,
+            flags: if width == 2 {
+                CellFlags::WIDE
+            } else {
+                CellFlags::empty()
+            } | self.protection_flag(),
+            grapheme: None,
         };
 
         // Set cell at cursor position
-        self.set_cell(self.cursor_pos.0, self.cursor_pos.1, cell);
+        let (col, row) = self.cursor_pos;
+        if let Some(line) = self.screen_line_mut(row) {
+            // Overwriting the last column must not clear the row's soft wrap
+            let row_flags = take_row_flags(line);
+            let col = col as usize;
+            split_wide_char(line, col);
+            split_wide_char(line, col + width as usize);
+            if width == 2 {
+                if let Some(spacer) = line.get_mut(col + 1) {
+                    *spacer = Cell {
+                        glyph_id: b' ' as u32,
+                        flags: CellFlags::WIDE_SPACER | (cell.flags & CellFlags::PROTECTED),
+                        ..cell.clone()
+                    };
+                }
+            }
+            if let Some(target) = line.get_mut(col) {
+                *target = cell;
+            }
+            restore_row_flags(line, row_flags);
+        }
+        self.last_printed = Some(c);
+        self.advance_cursor(width);
+    }
+
+    /// Append `c` to the cluster in the cell before the cursor if it belongs there
+    ///
+    /// Combining marks, variation selectors and ZWJ-joined characters extend
+    /// the previous character instead of taking a cell. A cluster that becomes
+    /// double-width, such as an emoji presentation sequence, claims the next
+    /// cell as its spacer.
+    fn extend_previous_cluster(&mut self, c: char) -> bool {
+        let (col, row) = self.cursor_pos;
+        // With a wrap pending, the character just printed is under the cursor
+        let Some(col) = (if self.pending_wrap {
+            Some(col)
+        } else {
+            col.checked_sub(1)
+        }) else {
+            return false;
+        };
+        if c.is_ascii() {
+            return false;
+        }
+        let Some(index) = self.screen_row_index(row) else {
+            return false;
+        };
+        self.damage_screen_row(row);
+        let line = &mut self.screen[index];
+        let Some(prev) = line.get(col as usize) else {
+            return false;
+        };
+        let prev_col = wide_char_start(line, col) as usize;
+        let prev = if prev.is_wide_spacer() {
+            &line[prev_col]
+        } else {
+            prev
+        };
+
+        let mut cluster = String::new();
+        push_cell_text(&self.graphemes, prev, &mut cluster);
+        if !extends_cluster(&cluster, c) {
+            return false;
+        }
+        cluster.push(c);
+
+        if self.graphemes.is_due_for_collection() {
+            self.collect_graphemes();
+        }
+        // With no id left, the character takes a cell of its own
+        let Some(id) = self.graphemes.intern(&cluster) else {
+            return false;
+        };
+        let line = &mut self.screen[index];
+        let widen = !line[prev_col].flags.contains(CellFlags::WIDE)
+            && cluster_width(&cluster, self.ambiguous_width) == 2
+            && prev_col + 1 < line.len();
+        let cell = &mut line[prev_col];
+        cell.grapheme = Some(id);
+        if widen {
+            cell.flags |= CellFlags::WIDE;
+            let spacer = Cell {
+                glyph_id: b' ' as u32,
+                flags: CellFlags::WIDE_SPACER,
+                grapheme: None,
+                ..cell.clone()
+            };
+            split_wide_char(line, prev_col + 2);
+            line[prev_col + 1] = spacer;
+            self.advance_cursor(1);
+        }
+        true
+    }
+
+    /// Free the grapheme clusters no cell in either buffer or history refers to
+    fn collect_graphemes(&mut self) {
+        let live: std::collections::HashSet<GraphemeId> = self
+            .screen
+            .iter()
+            .chain(&self.inactive.lines)
+            .chain(&self.history_view)
+            .flatten()
+            .filter_map(|cell| cell.grapheme)
+            .collect();
+        let before = self.graphemes.len();
+        let history = &self.history;
+        self.graphemes
+            .collect(|id| live.contains(&id) || history.holds_grapheme(id));
+        debug!(
+            subsystem = "blocks",
+            freed = before - self.graphemes.len(),
+            kept = self.graphemes.len(),
+            "Collected grapheme clusters"
+        );
+    }
+
+    /// Full text of a cell: its grapheme cluster, or a space for blank cells
+    pub fn cell_text(&self, cell: &Cell) -> String {
+        let mut text = String::new();
+        push_cell_text(&self.graphemes, cell, &mut text);
+        text
+    }
+
+    /// Whether a viewport row was soft-wrapped onto the next row
+    pub fn is_line_wrapped(&self, row: u16) -> bool {
+        self.get_viewport_line(row)
+            .is_some_and(|line| is_wrapped(line))
+    }
+
+    /// Interned grapheme clusters referenced by cells
+    pub fn graphemes(&self) -> &GraphemeTable {
+        &self.graphemes
+    }
+
+    /// Width given to East Asian Ambiguous characters
+    pub fn ambiguous_width(&self) -> AmbiguousWidth {
+        self.ambiguous_width
+    }
+
+    /// Set the width of East Asian Ambiguous characters printed from now on
+    pub fn set_ambiguous_width(&mut self, width: AmbiguousWidth) {
+        self.ambiguous_width = width;
+    }
 
-        // Advance cursor
-        self.advance_cursor();
+    /// Repeat the last printed character the given number of times (REP)
+    pub fn repeat_last_char(&mut self, count: u16) {
+        if let Some(c) = self.last_printed {
+            for _ in 0..count {
+                self.print_char(c);
+            }
+        }
     }
 
     /// Execute a control character (like newline, tab, etc.)
     pub fn execute_control(&mut self, byte: u8) {
         match byte {
-            b'\n' => self.newline(),
+            b'\n' | b'\x0b' | b'\x0c' => {
+                // LF, VT and FF all move down a line
+                if self.modes.linefeed_newline {
+                    self.newline();
+                } else {
+                    self.linefeed();
+                }
+            }
             b'\r' => self.carriage_return(),
             b'\t' => self.tab(),
             b'\x08' => self.backspace(), // Backspace
+            b'\x0e' => self.charsets.invoke(CharsetSlot::G1), // SO
+            b'\x0f' => self.charsets.invoke(CharsetSlot::G0), // SI
             _ => {
                 trace!("Unhandled control character: {:#x}", byte);
             }
@@ -881,7 +1563,7 @@ impl TerminalGrid {
                 trace!(subsystem = "blocks", col = col, "Moved cursor to column");
             }
             CsiAction::CursorPosition(row, col) => {
-                self.set_cursor_position(*col, *row);
+                self.cursor_goto(*col, *row);
                 trace!(
                     subsystem = "blocks",
                     row = row,
@@ -889,6 +1571,42 @@ impl TerminalGrid {
                     "Set cursor position"
                 );
             }
+            CsiAction::LinePositionAbsolute(row) => {
+                self.cursor_goto_row(*row);
+                trace!(subsystem = "blocks", row = row, "Moved cursor to row");
+            }
+            CsiAction::CursorForwardTab(count) => self.tab_forward(*count),
+            CsiAction::CursorBackwardTab(count) => self.tab_backward(*count),
+            CsiAction::TabClear(clear) => self.clear_tab_stops(*clear),
+            CsiAction::EraseInDisplay(mode) => {
+                self.erase_in_display(*mode);
+                trace!(subsystem = "blocks", mode = ?mode, "Erased in display");
+            }
+            CsiAction::EraseInLine(mode) => {
+                self.erase_in_line(*mode);
+                trace!(subsystem = "blocks", mode = ?mode, "Erased in line");
+            }
+            CsiAction::SelectiveEraseInDisplay(mode) => self.selective_erase_in_display(*mode),
+            CsiAction::SelectiveEraseInLine(mode) => self.selective_erase_in_line(*mode),
+            CsiAction::SetCharProtection(protected) => self.set_protected(*protected),
+            CsiAction::SetCursorStyle(style) => self.set_cursor_style(*style),
+            CsiAction::InsertChars(count) => self.insert_chars(*count),
+            CsiAction::DeleteChars(count) => self.delete_chars(*count),
+            CsiAction::EraseChars(count) => self.erase_chars(*count),
+            CsiAction::InsertLines(count) => self.insert_lines(*count),
+            CsiAction::DeleteLines(count) => self.delete_lines(*count),
+            CsiAction::ScrollUp(count) => self.scroll_screen_up(*count),
+            CsiAction::ScrollDown(count) => self.scroll_screen_down(*count),
+            CsiAction::RepeatChar(count) => self.repeat_last_char(*count),
+            CsiAction::SetScrollRegion { top, bottom } => self.set_scroll_region(*top, *bottom),
+            CsiAction::SoftReset => self.soft_reset(),
+            // The title is kept by the PTY layer, not the grid
+            CsiAction::PushTitle | CsiAction::PopTitle => {}
+            CsiAction::Mode(action) => self.handle_mode_action(action),
+            CsiAction::Query(query) => {
+                // Replies are produced by the PTY layer from the grid state
+                trace!(subsystem = "blocks", query = ?query, "Received terminal query");
+            }
             CsiAction::Other { command, params } => {
                 debug!(
                     subsystem = "blocks",
@@ -900,90 +1618,832 @@ impl TerminalGrid {
         }
     }
 
-    /// Move to next line (newline)
-    pub fn newline(&mut self) {
-        self.cursor_pos.0 = 0; // Reset column to start of line
-        self.cursor_pos.1 += 1;
-        if self.cursor_pos.1 >= self.rows {
-            // Need to scroll
-            self.scroll_up_one_line();
-            self.cursor_pos.1 = self.rows - 1;
+    /// Handle ESC sequence actions from the parser
+    pub fn handle_esc_action(&mut self, action: &EscAction) {
+        match *action {
+            EscAction::SaveCursor => self.save_cursor(),
+            EscAction::RestoreCursor => self.restore_cursor(),
+            EscAction::Index => self.index(),
+            EscAction::NextLine => self.next_line(),
+            EscAction::ReverseIndex => self.reverse_index(),
+            EscAction::HorizontalTabSet => self.set_tab_stop(),
+            EscAction::ApplicationKeypad => self.modes.application_keypad = true,
+            EscAction::NumericKeypad => self.modes.application_keypad = false,
+            EscAction::DesignateCharset(slot, charset) => {
+                self.charsets.designate(slot, charset);
+                trace!(subsystem = "blocks", slot = ?slot, charset = ?charset, "Designated charset");
+            }
+            EscAction::LockingShift(slot) => self.charsets.invoke(slot),
+            EscAction::LineSize(size) => self.set_line_size(size),
+            EscAction::Reset => self.full_reset(),
+            EscAction::Other(_) => {
+                debug!(subsystem = "blocks", action = ?action, "Unhandled ESC action");
+            }
         }
     }
 
-    /// Move cursor to start of current line (carriage return)
-    pub fn carriage_return(&mut self) {
-        self.cursor_pos.0 = 0;
+    /// Character set designations and the active slot
+    pub fn charsets(&self) -> &CharsetState {
+        &self.charsets
     }
 
-    /// Move cursor to next tab stop
-    pub fn tab(&mut self) {
-        // Simple tab implementation - move to next multiple of 8
-        self.cursor_pos.0 = ((self.cursor_pos.0 / 8) + 1) * 8;
-        if self.cursor_pos.0 >= self.cols {
-            self.cursor_pos.0 = self.cols - 1;
-        }
+    /// Terminal mode state
+    pub fn modes(&self) -> &TerminalModes {
+        &self.modes
     }
 
-    /// Move cursor back one position
-    pub fn backspace(&mut self) {
-        if self.cursor_pos.0 > 0 {
-            self.cursor_pos.0 -= 1;
+    /// Apply a mode set/reset/save/restore from the parser
+    pub fn handle_mode_action(&mut self, action: &ModeAction) {
+        match *action {
+            ModeAction::Set(mode) => self.set_mode(mode, true),
+            ModeAction::Reset(mode) => self.set_mode(mode, false),
+            ModeAction::Save(mode) => self.modes.save(mode),
+            ModeAction::Restore(mode) => {
+                if let Some(value) = self.modes.saved(mode) {
+                    self.set_mode(mode, value);
+                }
+            }
         }
     }
 
-    /// Advance cursor to next position
-    fn advance_cursor(&mut self) {
-        self.cursor_pos.0 += 1;
-        if self.cursor_pos.0 >= self.cols {
-            // Wrap to next line
-            self.cursor_pos.0 = 0;
-            self.newline();
+    /// Set or reset a single mode and apply its side effects
+    pub fn set_mode(&mut self, mode: Mode, enabled: bool) {
+        match (mode, enabled) {
+            (Mode::Dec(DecMode::AltScreen), true) => return self.enter_alt_screen(false),
+            (Mode::Dec(DecMode::AltScreen), false) => return self.exit_alt_screen(false),
+            (Mode::Dec(DecMode::AltScreenClear), true) => return self.enter_alt_screen(false),
+            (Mode::Dec(DecMode::AltScreenClear), false) => return self.exit_alt_screen(true),
+            (Mode::Dec(DecMode::SaveCursor), true) => return self.save_cursor(),
+            (Mode::Dec(DecMode::SaveCursor), false) => return self.restore_cursor(),
+            (Mode::Dec(DecMode::AltScreenSaveCursor), true) => {
+                self.save_cursor();
+                return self.enter_alt_screen(true);
+            }
+            (Mode::Dec(DecMode::AltScreenSaveCursor), false) => {
+                self.exit_alt_screen(false);
+                return self.restore_cursor();
+            }
+            _ => {}
         }
-    }
 
-    /// Scroll the terminal up by one line
-    fn scroll_up_one_line(&mut self) {
-        if self.scrollback.len() >= self.max_scrollback {
-            self.scrollback.pop_front();
+        if !self.modes.set(mode, enabled) {
+            debug!(subsystem = "blocks", mode = ?mode, enabled, "Unhandled mode");
+            return;
         }
-        self.scrollback
-            .push_back(vec![Cell::empty(); self.cols as usize]);
-    }
 
-    /// Get current formatting state
-    pub fn current_formatting(&self) -> (Color, Color, CellAttrs) {
-        (self.current_fg, self.current_bg, self.current_attrs)
+        // Changing origin mode homes the cursor
+        if mode == Mode::Dec(DecMode::Origin) {
+            self.cursor_goto(0, 0);
+        }
+        trace!(subsystem = "blocks", mode = ?mode, enabled, "Changed mode");
     }
 
-    /// Reset formatting to defaults
-    pub fn reset_formatting(&mut self) {
-        self.current_fg = Color::DEFAULT_FG;
-        self.current_bg = Color::DEFAULT_BG;
-        self.current_attrs = CellAttrs::empty();
+    /// Whether the alternate screen is currently displayed
+    pub fn is_alt_screen_active(&self) -> bool {
+        self.alt_screen_active
     }
 
-    // Text Selection Methods
+    /// Switch to the alternate screen, optionally clearing it first
+    ///
+    /// The primary screen and its scrollback are kept untouched until
+    /// `exit_alt_screen` is called. The cursor position carries over.
+    pub fn enter_alt_screen(&mut self, clear: bool) {
+        if self.alt_screen_active {
+            if clear {
+                self.erase_in_display(DisplayErase::All);
+            }
+            return;
+        }
 
-    /// Start a new text selection at the given position
-    pub fn start_selection(&mut self, position: Position) {
-        let clamped_position = self.clamp_position(position);
-        self.selection = Some(Selection::new(clamped_position, clamped_position));
-        trace!(
-            subsystem = "blocks",
-            col = clamped_position.col,
-            row = clamped_position.row,
-            "Started text selection"
-        );
+        self.swap_buffers();
+        self.alt_screen_active = true;
+
+        let cols = self.cols as usize;
+        let stale = self.screen.len() != self.rows as usize
+            || self.screen.iter().any(|line| line.len() != cols);
+        if clear || stale {
+            self.screen = (0..self.rows).map(|_| self.blank_line()).collect();
+        }
+        self.selection = None;
+        self.damage.mark_all();
+        debug!(subsystem = "blocks", clear, "Entered alternate screen");
+    }
+
+    /// Switch back to the primary screen, optionally clearing the alternate one
+    pub fn exit_alt_screen(&mut self, clear: bool) {
+        if !self.alt_screen_active {
+            return;
+        }
+
+        if clear {
+            self.erase_in_display(DisplayErase::All);
+        }
+        self.swap_buffers();
+        self.alt_screen_active = false;
+        self.viewport_offset = self.viewport_offset.min(self.scrollback_len());
+        self.selection = None;
+        self.damage.mark_all();
+        debug!(subsystem = "blocks", clear, "Left alternate screen");
+    }
+
+    /// Exchange the active buffer with the inactive one
+    fn swap_buffers(&mut self) {
+        std::mem::swap(&mut self.screen, &mut self.inactive.lines);
+        std::mem::swap(&mut self.saved_cursor, &mut self.inactive.saved_cursor);
+        std::mem::swap(
+            &mut self.viewport_offset,
+            &mut self.inactive.viewport_offset,
+        );
+        self.refresh_history_view();
+        // Matches are numbered by the lines of the buffer they were found in
+        self.clear_search_highlights();
+    }
+
+    /// Save the cursor position, formatting, protection, origin mode and charsets (DECSC)
+    pub fn save_cursor(&mut self) {
+        self.saved_cursor = Some(SavedCursor {
+            position: self.cursor_pos,
+            fg_color: self.current_fg,
+            bg_color: self.current_bg,
+            attrs: self.current_attrs,
+            underline_color: self.current_underline_color,
+            origin: self.modes.origin,
+            charsets: self.charsets,
+            protected: self.current_protected,
+        });
+    }
+
+    /// Restore the state saved by `save_cursor` (DECRC)
+    ///
+    /// Without a saved state the cursor is homed and formatting reset.
+    pub fn restore_cursor(&mut self) {
+        let saved = self.saved_cursor.unwrap_or(SavedCursor {
+            position: (0, 0),
+            fg_color: CellColor::Default,
+            bg_color: CellColor::Default,
+            attrs: CellAttrs::empty(),
+            underline_color: None,
+            origin: false,
+            charsets: CharsetState::new(),
+            protected: false,
+        });
+        self.current_fg = saved.fg_color;
+        self.current_bg = saved.bg_color;
+        self.current_attrs = saved.attrs;
+        self.current_underline_color = saved.underline_color;
+        self.modes.origin = saved.origin;
+        self.charsets = saved.charsets;
+        self.current_protected = saved.protected;
+        self.set_cursor_position(saved.position.0, saved.position.1);
+    }
+
+    /// Reset the terminal to its power-on state (RIS)
+    ///
+    /// Leaves the alternate screen, clears the screen and homes the cursor,
+    /// and resets modes, cursor style, formatting, margins, tab stops,
+    /// charsets, palette changes and saved cursors. Lines already in the
+    /// scrollback history are kept.
+    pub fn full_reset(&mut self) {
+        self.exit_alt_screen(false);
+        self.inactive = InactiveBuffer::default();
+        self.saved_cursor = None;
+        self.modes = TerminalModes::default();
+        self.set_cursor_style(None);
+        self.reset_formatting();
+        self.current_protected = false;
+        self.scroll_region = (0, self.rows.saturating_sub(1));
+        self.tab_stops = TabStops::new(self.cols);
+        self.charsets = CharsetState::new();
+        self.palette = self.theme.clone();
+        self.last_printed = None;
+        self.selection = None;
+        self.clear_search_highlights();
+        self.viewport_offset = 0;
+        self.history_view.clear();
+        self.damage.mark_all();
+        self.erase_in_display(DisplayErase::All);
+        self.cursor_pos = (0, 0);
+        self.pending_wrap = false;
+        self.collect_graphemes();
+        debug!(subsystem = "blocks", "Full terminal reset");
+    }
+
+    /// Reset modes and formatting without touching the screen (DECSTR)
+    ///
+    /// Follows the VT220 soft reset, except that autowrap returns to its
+    /// power-on value rather than being turned off, as xterm does. The cursor
+    /// stays where it is and screen contents are untouched.
+    pub fn soft_reset(&mut self) {
+        let defaults = TerminalModes::default();
+        self.modes.cursor_visible = defaults.cursor_visible;
+        self.modes.insert = defaults.insert;
+        self.modes.origin = defaults.origin;
+        self.modes.autowrap = defaults.autowrap;
+        self.modes.application_cursor_keys = defaults.application_cursor_keys;
+        self.modes.application_keypad = defaults.application_keypad;
+        self.reset_formatting();
+        self.scroll_region = (0, self.rows.saturating_sub(1));
+        self.charsets = CharsetState::new();
+        self.current_protected = false;
+        self.saved_cursor = None;
+        debug!(subsystem = "blocks", "Soft terminal reset");
+    }
+
+    /// The state saved for the active buffer, if any
+    pub fn saved_cursor(&self) -> Option<&SavedCursor> {
+        self.saved_cursor.as_ref()
+    }
+
+    /// Current scrolling region as inclusive (top, bottom) screen rows
+    pub fn scroll_region(&self) -> (u16, u16) {
+        self.scroll_region
+    }
+
+    /// Set the scrolling margins (DECSTBM) and home the cursor
+    ///
+    /// A missing bottom margin means the last row. Regions of fewer than two
+    /// lines are ignored.
+    pub fn set_scroll_region(&mut self, top: u16, bottom: Option<u16>) {
+        let last_row = self.rows.saturating_sub(1);
+        let bottom = bottom.unwrap_or(last_row).min(last_row);
+        if top >= bottom {
+            debug!(
+                subsystem = "blocks",
+                top, bottom, "Ignoring invalid scroll region"
+            );
+            return;
+        }
+
+        self.scroll_region = (top, bottom);
+        self.cursor_goto(0, 0);
+        trace!(subsystem = "blocks", top, bottom, "Set scroll region");
+    }
+
+    /// Check whether the scrolling region covers the whole screen
+    fn scroll_region_is_full(&self) -> bool {
+        self.scroll_region == (0, self.rows.saturating_sub(1))
+    }
+
+    /// Move to next line (newline)
+    pub fn newline(&mut self) {
+        self.cursor_pos.0 = 0; // Reset column to start of line
+        self.linefeed();
+    }
+
+    /// Move down one line, keeping the column, scrolling at the bottom margin
+    pub fn linefeed(&mut self) {
+        self.pending_wrap = false;
+        if self.cursor_pos.1 == self.scroll_region.1 {
+            self.scroll_region_up(1);
+        } else if self.cursor_pos.1 + 1 < self.rows {
+            self.cursor_pos.1 += 1;
+        }
+    }
+
+    /// Move down one line, scrolling at the bottom margin (IND)
+    pub fn index(&mut self) {
+        self.linefeed();
+    }
+
+    /// Move to the first column of the next line (NEL)
+    pub fn next_line(&mut self) {
+        self.newline();
+    }
+
+    /// Move up one line, scrolling down at the top margin (RI)
+    pub fn reverse_index(&mut self) {
+        self.pending_wrap = false;
+        let (top, bottom) = self.scroll_region;
+        if self.cursor_pos.1 == top {
+            self.scroll_rows_down(top, bottom, 1);
+        } else {
+            self.cursor_pos.1 = self.cursor_pos.1.saturating_sub(1);
+        }
+    }
+
+    /// Move cursor to start of current line (carriage return)
+    pub fn carriage_return(&mut self) {
+        self.pending_wrap = false;
+        self.cursor_pos.0 = 0;
+    }
+
+    /// Move cursor to next tab stop
+    pub fn tab(&mut self) {
+        self.tab_forward(1);
+    }
+
+    /// Move cursor forward by the given number of tab stops (CHT)
+    ///
+    /// Stops at the last column when no further tab stop exists.
+    pub fn tab_forward(&mut self, count: u16) {
+        self.pending_wrap = false;
+        let last_col = self.line_cols(self.cursor_pos.1).saturating_sub(1);
+        for _ in 0..count {
+            self.cursor_pos.0 = self
+                .tab_stops
+                .next(self.cursor_pos.0)
+                .map_or(last_col, |stop| stop.min(last_col));
+            if self.cursor_pos.0 >= last_col {
+                break;
+            }
+        }
+    }
+
+    /// Move cursor backward by the given number of tab stops (CBT)
+    pub fn tab_backward(&mut self, count: u16) {
+        self.pending_wrap = false;
+        for _ in 0..count {
+            if self.cursor_pos.0 == 0 {
+                break;
+            }
+            self.cursor_pos.0 = self.tab_stops.prev(self.cursor_pos.0).unwrap_or(0);
+        }
+    }
+
+    /// Set a tab stop at the cursor column (HTS)
+    pub fn set_tab_stop(&mut self) {
+        self.tab_stops.set(self.cursor_pos.0);
+    }
+
+    /// Clear the tab stop at the cursor column or all of them (TBC)
+    pub fn clear_tab_stops(&mut self, clear: TabClear) {
+        match clear {
+            TabClear::Current => self.tab_stops.clear(self.cursor_pos.0),
+            TabClear::All => self.tab_stops.clear_all(),
+        }
+    }
+
+    /// Columns with a tab stop
+    pub fn tab_stops(&self) -> &TabStops {
+        &self.tab_stops
+    }
+
+    /// Move cursor back one position
+    pub fn backspace(&mut self) {
+        self.pending_wrap = false;
+        if self.cursor_pos.0 > 0 {
+            self.cursor_pos.0 -= 1;
+        }
+    }
+
+    /// Move the cursor past a character `width` columns wide that was just printed
+    ///
+    /// At the right margin the cursor stays on the last column. With autowrap
+    /// on, a wrap is left pending and only happens when the next character is
+    /// printed, so a line of exactly `cols` characters followed by CR LF does
+    /// not leave a blank line. Without autowrap further characters overwrite
+    /// the last column.
+    fn advance_cursor(&mut self, width: u16) {
+        let cols = self.line_cols(self.cursor_pos.1);
+        let col = self.cursor_pos.0 + width;
+        if col < cols {
+            self.cursor_pos.0 = col;
+        } else {
+            self.cursor_pos.0 = cols.saturating_sub(1);
+            self.pending_wrap = self.modes.autowrap;
+        }
+    }
+
+    /// Continue output on the next line, remembering that the row continues there
+    fn soft_wrap(&mut self) {
+        if let Some(line) = self.screen_line_mut(self.cursor_pos.1) {
+            set_wrapped(line, true);
+        }
+        self.newline();
+    }
+
+    /// Whether the next printed character wraps to the next line first
+    pub fn wrap_pending(&self) -> bool {
+        self.pending_wrap
+    }
+
+    /// Columns addressable on screen row `row`: half as many on double-size rows
+    fn line_cols(&self, row: u16) -> u16 {
+        match self.screen_row_index(row) {
+            Some(index) if row_size(&self.screen[index]).is_double() => (self.cols / 2).max(1),
+            _ => self.cols,
+        }
+    }
+
+    /// Size a viewport row is drawn at
+    pub fn line_size(&self, row: u16) -> LineSize {
+        self.get_viewport_line(row)
+            .map_or(LineSize::SingleWidth, |line| row_size(line))
+    }
+
+    /// Change the size of the cursor row (DECSWL, DECDWL, DECDHL)
+    ///
+    /// Characters beyond the new right margin of a double-size row are lost
+    /// and the cursor moves back inside it.
+    pub fn set_line_size(&mut self, size: LineSize) {
+        let row = self.cursor_pos.1;
+        if size.is_double() {
+            let half = (self.cols / 2).max(1);
+            self.erase_row_range(row, half, self.cols, false);
+        }
+        if let Some(line) = self.screen_line_mut(row) {
+            set_row_size(line, size);
+        }
+        let last_col = self.line_cols(row).saturating_sub(1);
+        if self.cursor_pos.0 > last_col {
+            self.cursor_pos.0 = last_col;
+            self.pending_wrap = false;
+        }
+        trace!(subsystem = "blocks", row = row, size = ?size, "Set line size");
+    }
+
+    /// Convert a screen row to a buffer index, ignoring the viewport offset
+    fn screen_row_index(&self, row: u16) -> Option<usize> {
+        if row >= self.rows {
+            return None;
+        }
+        (row < self.rows && (row as usize) < self.screen.len()).then_some(row as usize)
+    }
+
+    /// Get a mutable screen line, independent of where the viewport is scrolled
+    ///
+    /// The row is marked as damaged.
+    fn screen_line_mut(&mut self, row: u16) -> Option<&mut CellRow> {
+        let index = self.screen_row_index(row)?;
+        self.damage_screen_row(row);
+        self.screen.get_mut(index)
+    }
+
+    /// Mark screen row `row` as damaged if it is in view
+    fn damage_screen_row(&mut self, row: u16) {
+        if let Some(row) = self.screen_to_viewport_row(row) {
+            self.damage.mark(row);
+        }
+    }
+
+    /// Viewport row showing screen row `row`, if it is in view
+    fn screen_to_viewport_row(&self, row: u16) -> Option<u16> {
+        let row = row.checked_add(u16::try_from(self.viewport_offset).ok()?)?;
+        (row < self.rows).then_some(row)
+    }
+
+    /// Viewport rows changed since the last call, clearing the record
+    ///
+    /// The first call after creation or a resize reports every row.
+    pub fn take_damage(&mut self) -> Damage {
+        std::mem::replace(&mut self.damage, Damage::none(self.rows))
+    }
+
+    /// Cell used to fill erased and newly exposed areas
+    ///
+    /// Keeps the current background color (BCE) but no other attributes, as
+    /// xterm does, so apps that set a background and clear get a solid fill.
+    fn blank_cell(&self) -> Cell {
+        Cell {
+            bg_color: self.current_bg,
+            ..Cell::empty()
+        }
+    }
+
+    /// Line used to fill newly exposed rows
+    fn blank_line(&self) -> CellRow {
+        vec![self.blank_cell(); self.cols as usize]
+    }
+
+    /// Blank the cells `start..end` of a screen row
+    ///
+    /// A selective erase leaves cells protected by DECSCA alone and erases
+    /// double-width characters it touches as a whole.
+    fn erase_row_range(&mut self, row: u16, start: u16, end: u16, selective: bool) {
+        let blank = self.blank_cell();
+        if let Some(line) = self.screen_line_mut(row) {
+            let size = row_size(line);
+            let end = (end as usize).min(line.len());
+            let start = (start as usize).min(end);
+            if selective {
+                let start = wide_char_start(line, start as u16) as usize;
+                let end = end + usize::from(line.get(end).is_some_and(Cell::is_wide_spacer));
+                for cell in &mut line[start..end] {
+                    if !cell.flags.contains(CellFlags::PROTECTED) {
+                        *cell = blank.clone();
+                    }
+                }
+            } else {
+                split_wide_char(line, start);
+                split_wide_char(line, end);
+                line[start..end].fill(blank);
+            }
+            set_row_size(line, size);
+        }
+    }
+
+    /// Erase whole rows for ED and DECSED
+    ///
+    /// Rows erased by ED go back to single width, as on the VT100.
+    fn erase_rows(&mut self, rows: std::ops::Range<u16>, selective: bool) {
+        for row in rows {
+            self.erase_row_range(row, 0, self.cols, selective);
+            if !selective {
+                if let Some(line) = self.screen_line_mut(row) {
+                    set_row_size(line, LineSize::SingleWidth);
+                }
+            }
+        }
+    }
+
+    /// Erase part or all of the display (ED)
+    pub fn erase_in_display(&mut self, mode: DisplayErase) {
+        self.erase_display(mode, false);
+    }
+
+    /// Erase unprotected characters in part or all of the display (DECSED)
+    pub fn selective_erase_in_display(&mut self, mode: DisplayErase) {
+        self.erase_display(mode, true);
+    }
+
+    /// Shared body of ED and DECSED
+    fn erase_display(&mut self, mode: DisplayErase, selective: bool) {
+        let (col, row) = self.cursor_pos;
+        match mode {
+            DisplayErase::Below => {
+                self.erase_row_range(row, col, self.cols, selective);
+                self.erase_rows(row + 1..self.rows, selective);
+            }
+            DisplayErase::Above => {
+                self.erase_rows(0..row, selective);
+                self.erase_row_range(row, 0, col + 1, selective);
+            }
+            DisplayErase::All => self.erase_rows(0..self.rows, selective),
+            // History cannot be protected, so DECSED clears it like ED
+            DisplayErase::Scrollback => {
+                if !self.alt_screen_active {
+                    self.history.clear();
+                }
+                if self.viewport_offset != 0 {
+                    self.viewport_offset = 0;
+                    self.history_view.clear();
+                    self.damage.mark_all();
+                }
+                self.collect_graphemes();
+            }
+        }
+    }
+
+    /// Erase part or all of the cursor line (EL)
+    pub fn erase_in_line(&mut self, mode: LineErase) {
+        self.erase_line(mode, false);
+    }
+
+    /// Erase unprotected characters in part or all of the cursor line (DECSEL)
+    pub fn selective_erase_in_line(&mut self, mode: LineErase) {
+        self.erase_line(mode, true);
+    }
+
+    /// Shared body of EL and DECSEL
+    fn erase_line(&mut self, mode: LineErase, selective: bool) {
+        let (col, row) = self.cursor_pos;
+        match mode {
+            LineErase::Right => self.erase_row_range(row, col, self.cols, selective),
+            LineErase::Left => self.erase_row_range(row, 0, col + 1, selective),
+            LineErase::All => self.erase_row_range(row, 0, self.cols, selective),
+        }
+    }
+
+    /// Erase characters from the cursor without moving it (ECH)
+    pub fn erase_chars(&mut self, count: u16) {
+        let (col, row) = self.cursor_pos;
+        self.erase_row_range(row, col, col.saturating_add(count), false);
+    }
+
+    /// Insert blank characters at the cursor, shifting the rest of the line right (ICH)
+    pub fn insert_chars(&mut self, count: u16) {
+        let (col, row) = self.cursor_pos;
+        let cols = self.cols as usize;
+        let blank = self.blank_cell();
+        if let Some(line) = self.screen_line_mut(row) {
+            let col = col as usize;
+            let count = (count as usize).min(cols.saturating_sub(col));
+            let row_flags = take_row_flags(line);
+            split_wide_char(line, col);
+            line.splice(col..col, std::iter::repeat(blank).take(count));
+            line.truncate(cols);
+            // A double-width character pushed to the edge loses its spacer
+            split_wide_char(line, cols);
+            restore_row_flags(line, row_flags);
+        }
+    }
+
+    /// Delete characters at the cursor, shifting the rest of the line left (DCH)
+    pub fn delete_chars(&mut self, count: u16) {
+        let (col, row) = self.cursor_pos;
+        let cols = self.cols as usize;
+        let blank = self.blank_cell();
+        if let Some(line) = self.screen_line_mut(row) {
+            let col = (col as usize).min(line.len());
+            let count = (count as usize).min(line.len() - col);
+            let row_flags = take_row_flags(line);
+            split_wide_char(line, col);
+            split_wide_char(line, col + count);
+            line.drain(col..col + count);
+            line.resize(cols, blank);
+            restore_row_flags(line, row_flags);
+        }
+    }
+
+    /// Insert blank lines at the cursor row, pushing lines below down (IL)
+    pub fn insert_lines(&mut self, count: u16) {
+        self.pending_wrap = false;
+        let (top, bottom) = self.scroll_region;
+        let row = self.cursor_pos.1;
+        if row < top || row > bottom {
+            return;
+        }
+        self.scroll_rows_down(row, bottom, count);
+        self.cursor_pos.0 = 0;
+    }
+
+    /// Delete lines at the cursor row, pulling lines below up (DL)
+    pub fn delete_lines(&mut self, count: u16) {
+        self.pending_wrap = false;
+        let (top, bottom) = self.scroll_region;
+        let row = self.cursor_pos.1;
+        if row < top || row > bottom {
+            return;
+        }
+        self.scroll_rows_up(row, bottom, count);
+        self.cursor_pos.0 = 0;
+    }
+
+    /// Scroll the scrolling region up (SU)
+    pub fn scroll_screen_up(&mut self, count: u16) {
+        self.scroll_region_up(count);
+    }
+
+    /// Scroll the scrolling region down, inserting blank lines at the top (SD)
+    pub fn scroll_screen_down(&mut self, count: u16) {
+        let (top, bottom) = self.scroll_region;
+        self.scroll_rows_down(top, bottom, count);
+    }
+
+    /// Scroll the scrolling region up by `count` lines
+    ///
+    /// Only a full-screen region feeds scrollback; lines leaving a partial
+    /// region are discarded.
+    fn scroll_region_up(&mut self, count: u16) {
+        if self.scroll_region_is_full() {
+            for _ in 0..count.min(self.rows) {
+                self.scroll_up_one_line();
+            }
+        } else {
+            let (top, bottom) = self.scroll_region;
+            self.scroll_rows_up(top, bottom, count);
+        }
+    }
+
+    /// Shift screen rows `top..=bottom` up by `count`, blanking rows at the bottom
+    fn scroll_rows_up(&mut self, top: u16, bottom: u16, count: u16) {
+        let (Some(top_index), Some(bottom_index)) =
+            (self.screen_row_index(top), self.screen_row_index(bottom))
+        else {
+            return;
+        };
+        if top_index > bottom_index {
+            return;
+        }
+
+        self.damage_screen_rows(top..bottom + 1);
+        let count = (count as usize).min(bottom_index - top_index + 1);
+        for _ in 0..count {
+            self.screen.remove(top_index);
+            let blank = self.blank_line();
+            self.screen.insert(bottom_index, blank);
+        }
+    }
+
+    /// Shift screen rows `top..=bottom` down by `count`, blanking rows at the top
+    fn scroll_rows_down(&mut self, top: u16, bottom: u16, count: u16) {
+        let (Some(top_index), Some(bottom_index)) =
+            (self.screen_row_index(top), self.screen_row_index(bottom))
+        else {
+            return;
+        };
+        if top_index > bottom_index {
+            return;
+        }
+
+        self.damage_screen_rows(top..bottom + 1);
+        let count = (count as usize).min(bottom_index - top_index + 1);
+        for _ in 0..count {
+            self.screen.remove(bottom_index);
+            let blank = self.blank_line();
+            self.screen.insert(top_index, blank);
+        }
+    }
+
+    /// Mark screen rows `rows` as damaged where they are in view
+    fn damage_screen_rows(&mut self, rows: std::ops::Range<u16>) {
+        for row in rows {
+            self.damage_screen_row(row);
+        }
+    }
+
+    /// Scroll the terminal up by one line
+    ///
+    /// The viewport moves up a row unless it is scrolled back and keeps
+    /// showing the same lines.
+    fn scroll_up_one_line(&mut self) {
+        let offset = self.viewport_offset;
+        let top = self.screen.pop_front();
+        // The alternate screen keeps no history
+        if let Some(top) = top.filter(|_| !self.alt_screen_active) {
+            self.push_history(&top);
+        }
+        if self.viewport_offset != offset + 1 {
+            self.damage.scroll_up(1);
+        }
+        let blank = self.blank_line();
+        self.screen.push_back(blank);
+    }
+
+    /// Protect characters printed from now on from selective erase, or not (DECSCA)
+    pub fn set_protected(&mut self, protected: bool) {
+        self.current_protected = protected;
+    }
+
+    /// Flag marking cells printed now as protected, if they are
+    fn protection_flag(&self) -> CellFlags {
+        if self.current_protected {
+            CellFlags::PROTECTED
+        } else {
+            CellFlags::empty()
+        }
+    }
+
+    /// Colors that indexed and default cell colors are drawn with
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Switch to a new color theme
+    ///
+    /// Cells keep palette indices rather than resolved colors, so output
+    /// already on screen and in the scrollback is recolored on the next
+    /// render. Entries changed with OSC 4 are replaced.
+    pub fn set_theme(&mut self, theme: Palette) {
+        self.palette = theme.clone();
+        self.theme = theme;
+        self.damage.mark_all();
+        debug!(subsystem = "blocks", "Switched color theme");
+    }
+
+    /// Change palette entry `index` (OSC 4)
+    pub fn set_palette_color(&mut self, index: u8, color: Color) {
+        trace!(subsystem = "blocks", index, color = ?color, "Set palette color");
+        self.palette.set(index, color);
+        self.damage.mark_all();
+    }
+
+    /// Restore palette entry `index` to the theme's color (OSC 104)
+    pub fn reset_palette_color(&mut self, index: u8) {
+        self.palette.set(index, self.theme.get(index));
+        self.damage.mark_all();
+    }
+
+    /// Restore every palette entry to the theme's colors (OSC 104 without
+    /// parameters)
+    pub fn reset_palette(&mut self) {
+        self.palette = self.theme.clone();
+        self.damage.mark_all();
+    }
+
+    /// Get current formatting state
+    pub fn current_formatting(&self) -> (CellColor, CellColor, CellAttrs) {
+        (self.current_fg, self.current_bg, self.current_attrs)
+    }
+
+    /// Reset formatting to defaults
+    pub fn reset_formatting(&mut self) {
+        self.current_fg = CellColor::Default;
+        self.current_bg = CellColor::Default;
+        self.current_attrs = CellAttrs::empty();
+        self.current_underline_color = None;
+    }
+
+    // Text Selection Methods
+
+    /// Start a new text selection at the given position
+    pub fn start_selection(&mut self, position: Position) {
+        let clamped_position = self.clamp_position(position);
+        self.set_selection(Some(Selection::new(clamped_position, clamped_position)));
+        trace!(
+            subsystem = "blocks",
+            col = clamped_position.col,
+            row = clamped_position.row,
+            "Started text selection"
+        );
     }
 
     /// Extend the current selection to the given position
     pub fn extend_selection(&mut self, position: Position) {
         let clamped_position = self.clamp_position(position);
-        if let Some(selection) = &mut self.selection {
-            selection.end = clamped_position;
+        if let Some(selection) = &self.selection {
             // Normalize the selection
-            *selection = Selection::new(selection.start, selection.end);
+            let selection = Selection::new(selection.start, clamped_position);
             trace!(
                 subsystem = "blocks",
                 start_col = selection.start.col,
@@ -992,6 +2452,7 @@ impl TerminalGrid {
                 end_row = selection.end.row,
                 "Extended text selection"
             );
+            self.set_selection(Some(selection));
         } else {
             self.start_selection(position);
         }
@@ -1000,11 +2461,23 @@ impl TerminalGrid {
     /// Clear the current text selection
     pub fn clear_selection(&mut self) {
         if self.selection.is_some() {
-            self.selection = None;
+            self.set_selection(None);
             trace!(subsystem = "blocks", "Cleared text selection");
         }
     }
 
+    /// Replace the selection, damaging the rows it covered and now covers
+    fn set_selection(&mut self, selection: Option<Selection>) {
+        for covered in [self.selection.as_ref(), selection.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            self.damage
+                .mark_range(covered.start.row..covered.end.row.saturating_add(1));
+        }
+        self.selection = selection;
+    }
+
     /// Get the current text selection, if any
     pub fn get_selection(&self) -> Option<&Selection> {
         self.selection.as_ref()
@@ -1062,14 +2535,12 @@ impl TerminalGrid {
         if start.row == end.row {
             // Single line selection
             if let Some(line) = self.get_viewport_line(start.row) {
-                for col in start.col..=end.col.min(self.cols.saturating_sub(1)) {
+                let start_col = wide_char_start(line, start.col);
+                for col in start_col..=end.col.min(self.cols.saturating_sub(1)) {
                     if let Some(cell) = line.get(col as usize) {
-                        if cell.glyph_id != 0 && !cell.is_empty() {
-                            if let Some(ch) = char::from_u32(cell.glyph_id) {
-                                result.push(ch);
-                            }
-                        } else {
-                            result.push(' ');
+                        // A spacer's pair was copied with its first half
+                        if !is_placeholder(cell) {
+                            push_cell_text(&self.graphemes, cell, &mut result);
                         }
                     }
                 }
@@ -1078,7 +2549,11 @@ impl TerminalGrid {
             // Multi-line selection
             for row in start.row..=end.row {
                 if let Some(line) = self.get_viewport_line(row) {
-                    let start_col = if row == start.row { start.col } else { 0 };
+                    let start_col = if row == start.row {
+                        wide_char_start(line, start.col)
+                    } else {
+                        0
+                    };
                     let end_col = if row == end.row {
                         end.col.min(self.cols.saturating_sub(1))
                     } else {
@@ -1088,16 +2563,18 @@ impl TerminalGrid {
                     let mut line_text = String::new();
                     for col in start_col..=end_col {
                         if let Some(cell) = line.get(col as usize) {
-                            if cell.glyph_id != 0 && !cell.is_empty() {
-                                if let Some(ch) = char::from_u32(cell.glyph_id) {
-                                    line_text.push(ch);
-                                }
-                            } else {
-                                line_text.push(' ');
+                            if !is_placeholder(cell) {
+                                push_cell_text(&self.graphemes, cell, &mut line_text);
                             }
                         }
                     }
 
+                    if is_wrapped(line) && row < end.row {
+                        // A soft-wrapped row continues on the next one as-is
+                        result.push_str(&line_text);
+                        continue;
+                    }
+
                     // Trim trailing whitespace from this line
                     result.push_str(line_text.trim_end());
 
@@ -1118,15 +2595,17 @@ impl TerminalGrid {
             return None;
         }
 
-        let scrollback_row = self.viewport_row_to_scrollback_index(row)?;
-        self.scrollback.get(scrollback_row)
+        match (row as usize).checked_sub(self.viewport_offset) {
+            Some(screen_row) => self.screen.get(screen_row),
+            None => self.history_view.get(row as usize),
+        }
     }
 
     /// Select all text in the current viewport
     pub fn select_all(&mut self) {
         let start = Position::new(0, 0);
         let end = Position::new(self.cols.saturating_sub(1), self.rows.saturating_sub(1));
-        self.selection = Some(Selection::new(start, end));
+        self.set_selection(Some(Selection::new(start, end)));
         debug!(subsystem = "blocks", "Selected all text in viewport");
     }
 
@@ -1140,7 +2619,7 @@ impl TerminalGrid {
 
             let start = Position::new(start_col, clamped_pos.row);
             let end = Position::new(end_col, clamped_pos.row);
-            self.selection = Some(Selection::new(start, end));
+            self.set_selection(Some(Selection::new(start, end)));
 
             debug!(
                 subsystem = "blocks",
@@ -1160,7 +2639,7 @@ impl TerminalGrid {
         while col > 0 {
             if let Some(cell) = line.get(col.saturating_sub(1)) {
                 if let Some(ch) = char::from_u32(cell.glyph_id) {
-                    if ch.is_alphanumeric() || ch == '_' {
+                    if is_word_char(cell, ch) {
                         col = col.saturating_sub(1);
                     } else {
                         break;
@@ -1184,7 +2663,7 @@ impl TerminalGrid {
         while col < line.len() {
             if let Some(cell) = line.get(col) {
                 if let Some(ch) = char::from_u32(cell.glyph_id) {
-                    if ch.is_alphanumeric() || ch == '_' {
+                    if is_word_char(cell, ch) {
                         col += 1;
                     } else {
                         break;
@@ -1210,25 +2689,346 @@ impl TerminalGrid {
     pub fn get_selection_viewport_bounds(&self) -> Option<(Position, Position)> {
         self.selection.as_ref().map(|s| s.normalized())
     }
-}
 
-/// Placeholder module for blocks (maintaining backwards compatibility)
-pub struct Blocks;
+    /// Buffer line number of the first row in the active buffer
+    ///
+    /// The alternate screen keeps no history, so its rows are numbered from 0.
+    fn line_base(&self) -> usize {
+        if self.alt_screen_active {
+            0
+        } else {
+            self.history.dropped()
+        }
+    }
 
-impl Blocks {
-    /// Create a new instance
-    pub fn new() -> Self {
-        Self
+    /// Buffer line number of the top viewport row
+    fn viewport_top_line(&self) -> usize {
+        self.line_base() + self.scrollback_len() - self.viewport_offset
     }
-}
 
-impl Default for Blocks {
-    fn default() -> Self {
-        Self::new()
+    /// Rows of the active buffer, as searched
+    fn search_rows(&self) -> search::Rows<'_> {
+        search::Rows::new(
+            &self.history,
+            self.scrollback_len(),
+            &self.screen,
+            self.line_base(),
+        )
     }
-}
 
-#[cfg(test)]
+    /// Find every match of `query` in scrollback and on screen, oldest first
+    ///
+    /// Matches may continue across soft-wrapped rows. Fails with
+    /// [`SearchError::Cancelled`] once `cancel` is triggered.
+    #[instrument(name = "grid_search", skip_all)]
+    pub fn search(
+        &self,
+        query: &SearchQuery,
+        cancel: &SearchCancel,
+    ) -> Result<Vec<SearchMatch>, SearchError> {
+        let matches = search::find_all(&mut self.search_rows(), &self.graphemes, query, cancel)?;
+        debug!(
+            subsystem = "blocks",
+            matches = matches.len(),
+            lines = self.total_lines(),
+            "Searched buffer"
+        );
+        Ok(matches)
+    }
+
+    /// Find the match nearest `from` in `direction`, not counting one that
+    /// starts exactly there
+    ///
+    /// The search wraps around the buffer once, so a lone match is found
+    /// again. Fails with [`SearchError::Cancelled`] once `cancel` is triggered.
+    pub fn search_next(
+        &self,
+        query: &SearchQuery,
+        from: SearchPoint,
+        direction: SearchDirection,
+        cancel: &SearchCancel,
+    ) -> Result<Option<SearchMatch>, SearchError> {
+        search::find_next(
+            &mut self.search_rows(),
+            &self.graphemes,
+            query,
+            from,
+            direction,
+            cancel,
+        )
+    }
+
+    /// Buffer point shown at a viewport position, e.g. to start searching from
+    pub fn viewport_point(&self, position: Position) -> SearchPoint {
+        SearchPoint::new(
+            self.viewport_top_line() + position.row as usize,
+            position.col,
+        )
+    }
+
+    /// Scroll the viewport so that a match is in view
+    ///
+    /// A match already in view stays where it is; otherwise it is brought to
+    /// the middle of the viewport where possible.
+    pub fn scroll_to_match(&mut self, found: &SearchMatch) {
+        let top = self.viewport_top_line();
+        let Some(index) = found.start.line.checked_sub(self.line_base()) else {
+            return;
+        };
+        if (top..top + self.rows as usize).contains(&found.start.line) {
+            return;
+        }
+
+        let offset = (self.scrollback_len() + self.rows as usize / 2)
+            .saturating_sub(index)
+            .min(self.scrollback_len());
+        if offset > self.viewport_offset {
+            self.scroll_up(offset - self.viewport_offset);
+        } else {
+            self.scroll_down(self.viewport_offset - offset);
+        }
+    }
+
+    /// Highlight search matches, with `current` drawn distinctly
+    ///
+    /// `matches` must be in buffer order, as returned by [`search`](Self::search).
+    pub fn set_search_highlights(
+        &mut self,
+        matches: Vec<SearchMatch>,
+        current: Option<SearchMatch>,
+    ) {
+        self.search_matches = matches;
+        self.current_match = current;
+        self.damage.mark_all();
+    }
+
+    /// Remove search highlighting
+    pub fn clear_search_highlights(&mut self) {
+        if !self.search_matches.is_empty() || self.current_match.is_some() {
+            self.search_matches.clear();
+            self.current_match = None;
+            self.damage.mark_all();
+        }
+    }
+
+    /// Search highlight attributes for each column of viewport row `row`,
+    /// or nothing when no match is highlighted
+    fn search_attrs(&self, row: u16) -> Vec<quantaterm_renderer::RendererCellAttrs> {
+        use quantaterm_renderer::RendererCellAttrs;
+
+        if self.search_matches.is_empty() && self.current_match.is_none() {
+            return Vec::new();
+        }
+        let line = self.viewport_top_line() + row as usize;
+        let first = self
+            .search_matches
+            .partition_point(|found| found.end.line < line);
+        let matches = self.search_matches[first..]
+            .iter()
+            .take_while(|found| found.start.line <= line)
+            .map(|found| (found, RendererCellAttrs::SEARCH_MATCH));
+        let current = self
+            .current_match
+            .iter()
+            .map(|found| (found, RendererCellAttrs::SEARCH_CURRENT));
+
+        let mut attrs = vec![RendererCellAttrs::empty(); self.cols as usize];
+        for (found, flag) in matches.chain(current) {
+            if let Some(cols) = found.columns_on(line, self.cols) {
+                for attr in &mut attrs[cols.start as usize..cols.end as usize] {
+                    *attr |= flag;
+                }
+            }
+        }
+        attrs
+    }
+}
+
+/// Append the text a cell displays, with blank cells as a space
+fn push_cell_text(graphemes: &GraphemeTable, cell: &Cell, out: &mut String) {
+    match cell.grapheme {
+        Some(id) => out.push_str(graphemes.get(id)),
+        None if cell.glyph_id == 0 => out.push(' '),
+        None => out.push(char::from_u32(cell.glyph_id).unwrap_or(' ')),
+    }
+}
+
+/// Column where the character covering `col` starts, stepping back from a spacer
+fn wide_char_start(line: &CellRow, col: u16) -> u16 {
+    match line.get(col as usize) {
+        Some(cell) if cell.is_wide_spacer() && col > 0 => col - 1,
+        _ => col,
+    }
+}
+
+/// Whether a cell only fills space left by a double-width character
+fn is_placeholder(cell: &Cell) -> bool {
+    cell.flags
+        .intersects(CellFlags::WIDE_SPACER | CellFlags::WIDE_PADDING)
+}
+
+/// Whether a row continues on the next row
+fn is_wrapped(line: &[Cell]) -> bool {
+    line.last()
+        .is_some_and(|cell| cell.flags.contains(CellFlags::WRAPPED))
+}
+
+/// Mark or unmark a row as continuing on the next row
+fn set_wrapped(line: &mut [Cell], wrapped: bool) {
+    if let Some(last) = line.last_mut() {
+        last.flags.set(CellFlags::WRAPPED, wrapped);
+    }
+}
+
+/// Flags on the last cell that describe the whole row
+const ROW_FLAGS: CellFlags = CellFlags::WRAPPED
+    .union(CellFlags::DOUBLE_WIDTH)
+    .union(CellFlags::DOUBLE_HEIGHT_TOP)
+    .union(CellFlags::DOUBLE_HEIGHT_BOTTOM);
+
+/// Size a row is drawn at
+fn row_size(line: &[Cell]) -> LineSize {
+    let flags = line.last().map_or(CellFlags::empty(), |cell| cell.flags);
+    if flags.contains(CellFlags::DOUBLE_WIDTH) {
+        LineSize::DoubleWidth
+    } else if flags.contains(CellFlags::DOUBLE_HEIGHT_TOP) {
+        LineSize::DoubleHeightTop
+    } else if flags.contains(CellFlags::DOUBLE_HEIGHT_BOTTOM) {
+        LineSize::DoubleHeightBottom
+    } else {
+        LineSize::SingleWidth
+    }
+}
+
+/// Change the size a row is drawn at
+fn set_row_size(line: &mut [Cell], size: LineSize) {
+    if let Some(last) = line.last_mut() {
+        last.flags.remove(ROW_FLAGS.difference(CellFlags::WRAPPED));
+        last.flags |= match size {
+            LineSize::SingleWidth => CellFlags::empty(),
+            LineSize::DoubleWidth => CellFlags::DOUBLE_WIDTH,
+            LineSize::DoubleHeightTop => CellFlags::DOUBLE_HEIGHT_TOP,
+            LineSize::DoubleHeightBottom => CellFlags::DOUBLE_HEIGHT_BOTTOM,
+        };
+    }
+}
+
+/// Clear and return the row flags, before the last cell is moved or replaced
+fn take_row_flags(line: &mut [Cell]) -> CellFlags {
+    line.last_mut().map_or(CellFlags::empty(), |last| {
+        let flags = last.flags & ROW_FLAGS;
+        last.flags.remove(ROW_FLAGS);
+        flags
+    })
+}
+
+/// Put back row flags taken with [`take_row_flags`]
+fn restore_row_flags(line: &mut [Cell], flags: CellFlags) {
+    if let Some(last) = line.last_mut() {
+        last.flags |= flags;
+    }
+}
+
+/// Split the cells of a logical line into rows of `cols` cells
+///
+/// Trailing blank cells are dropped and double-width characters are kept
+/// whole. Returns the row and column `cursor`, an offset into `cells`, ends
+/// up at; a cursor past the content stays on the last row, at most one
+/// column past its end.
+fn wrap_line(
+    cells: &mut Vec<Cell>,
+    cols: usize,
+    cursor: Option<usize>,
+    out: &mut VecDeque<CellRow>,
+) -> Option<(usize, usize)> {
+    let len = cells
+        .iter()
+        .rposition(|cell| !cell.is_empty())
+        .map_or(0, |i| i + 1);
+    cells.truncate(len);
+
+    let mut moved = None;
+    let mut start = 0;
+    loop {
+        let mut take = cols.min(len - start);
+        // Keep double-width characters whole
+        let split_pair = take > 1 && cells.get(start + take).is_some_and(Cell::is_wide_spacer);
+        if split_pair {
+            take -= 1;
+        }
+
+        let mut row = cells[start..start + take].to_vec();
+        if split_pair {
+            let mut padding = Cell::empty();
+            padding.flags |= CellFlags::WIDE_PADDING;
+            row.push(padding);
+        }
+        row.resize(cols, Cell::empty());
+
+        let end = start + take;
+        let last = end >= len;
+        if let Some(offset) = cursor {
+            if (start..end).contains(&offset) || (last && moved.is_none()) {
+                moved = Some((out.len(), offset.saturating_sub(start).min(cols)));
+            }
+        }
+        if !last {
+            set_wrapped(&mut row, true);
+        }
+        out.push_back(row);
+        if last {
+            cells.clear();
+            return moved;
+        }
+        start = end;
+    }
+}
+
+/// Whether a cell continues a word for double-click selection
+fn is_word_char(cell: &Cell, ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_' || cell.is_wide_spacer()
+}
+
+/// Blank a double-width character straddling the boundary before `col`
+///
+/// Called before cells from `col` on are overwritten or moved, so that no
+/// half of a pair is left behind.
+fn split_wide_char(line: &mut CellRow, col: usize) {
+    if col == 0 || col > line.len() {
+        return;
+    }
+    let straddles = line.get(col).is_some_and(Cell::is_wide_spacer)
+        || (col == line.len() && line[col - 1].flags.contains(CellFlags::WIDE));
+    if straddles {
+        line[col - 1] = Cell::empty();
+        if let Some(spacer) = line.get_mut(col) {
+            *spacer = Cell::empty();
+        }
+    }
+}
+
+/// Placeholder module for blocks (maintaining backwards compatibility)
+pub struct Blocks;
+
+impl Blocks {
+    /// Create a new instance
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for Blocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Convert a resolved color for the renderer
+fn renderer_color(color: Color) -> quantaterm_renderer::RendererColor {
+    quantaterm_renderer::RendererColor::rgb(color.r, color.g, color.b)
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -1248,19 +3048,19 @@ mod tests {
     fn test_cell_creation_and_properties() {
         let cell = Cell::new(b'A' as u32);
         assert_eq!(cell.glyph_id, b'A' as u32);
-        assert_eq!(cell.fg_color, Color::DEFAULT_FG);
-        assert_eq!(cell.bg_color, Color::DEFAULT_BG);
+        assert_eq!(cell.fg_color, CellColor::Default);
+        assert_eq!(cell.bg_color, CellColor::Default);
         assert_eq!(cell.attrs, CellAttrs::empty());
 
         let styled_cell = Cell::with_style(
             b'B' as u32,
-            Color::rgb(255, 0, 0),
-            Color::rgb(0, 255, 0),
+            CellColor::Rgb(Color::rgb(255, 0, 0)),
+            CellColor::Rgb(Color::rgb(0, 255, 0)),
             CellAttrs::BOLD | CellAttrs::ITALIC,
         );
         assert_eq!(styled_cell.glyph_id, b'B' as u32);
-        assert_eq!(styled_cell.fg_color, Color::rgb(255, 0, 0));
-        assert_eq!(styled_cell.bg_color, Color::rgb(0, 255, 0));
+        assert_eq!(styled_cell.fg_color, CellColor::Rgb(Color::rgb(255, 0, 0)));
+        assert_eq!(styled_cell.bg_color, CellColor::Rgb(Color::rgb(0, 255, 0)));
         assert!(styled_cell.attrs.contains(CellAttrs::BOLD));
         assert!(styled_cell.attrs.contains(CellAttrs::ITALIC));
 
@@ -1292,7 +3092,7 @@ mod tests {
         assert_eq!(grid.rows, 24);
         assert_eq!(grid.viewport_offset, 0);
         assert_eq!(grid.cursor_position(), (0, 0));
-        assert_eq!(grid.scrollback.len(), 24);
+        assert_eq!(grid.total_lines(), 24);
     }
 
     #[test]
@@ -1328,7 +3128,7 @@ mod tests {
         grid.resize(15, 8);
         assert_eq!(grid.cols, 15);
         assert_eq!(grid.rows, 8);
-        assert_eq!(grid.scrollback.len(), 8);
+        assert_eq!(grid.total_lines(), 8);
 
         // Check that existing content is preserved
         let retrieved_cell = grid.get_cell(5, 2).unwrap();
@@ -1358,17 +3158,23 @@ mod tests {
         assert_eq!(grid.cols, 10);
         assert_eq!(grid.rows, 5);
 
-        // After rewrapping, 'T' should be at (5,1) since row got shifted due to rewrapping
-        let retrieved_cell = grid.get_cell(5, 1).unwrap();
+        // The cursor stays on the top row, so 'T' keeps its row
+        let retrieved_cell = grid.get_cell(5, 2).unwrap();
         assert_eq!(retrieved_cell.glyph_id, b'T' as u32);
+        assert_eq!(grid.cursor_position(), (0, 0));
 
-        // 'X' should have been wrapped to the next line at position (2,2)
-        let wrapped_cell = grid.get_cell(2, 2).unwrap();
+        // 'X' should have been wrapped to the next line at position (2,3)
+        let wrapped_cell = grid.get_cell(2, 3).unwrap();
         assert_eq!(wrapped_cell.glyph_id, b'X' as u32);
+        assert!(grid.is_line_wrapped(2));
 
         // Check that content outside new bounds is inaccessible
         assert!(grid.get_cell(12, 2).is_none()); // Column out of bounds
         assert!(grid.get_cell(5, 7).is_none()); // Row out of bounds (was removed)
+        assert!(grid
+            .get_viewport_text()
+            .iter()
+            .all(|line| !line.contains('Y')));
     }
 
     #[test]
@@ -1385,7 +3191,8 @@ mod tests {
             grid.set_cell(i, 1, Cell::new((b'F' + i as u8) as u32));
         }
 
-        // Shrink width to force rewrapping
+        // Shrink width to force rewrapping, with the cursor below the output
+        grid.set_cursor_position(0, 2);
         grid.resize(3, 3);
 
         // Check that content was rewrapped correctly
@@ -1463,7 +3270,7 @@ mod tests {
         assert!(grid.get_cell(2, 1).unwrap().is_empty());
         assert_eq!(grid.cursor_position(), (0, 0));
         assert_eq!(grid.viewport_offset, 0);
-        assert_eq!(grid.scrollback.len(), 3);
+        assert_eq!(grid.total_lines(), 3);
     }
 
     #[test]
@@ -1489,29 +3296,33 @@ mod tests {
 
         // Test default formatting
         let (fg, bg, attrs) = grid.current_formatting();
-        assert_eq!(fg, Color::DEFAULT_FG);
-        assert_eq!(bg, Color::DEFAULT_BG);
+        assert_eq!(fg, CellColor::Default);
+        assert_eq!(bg, CellColor::Default);
         assert!(attrs.is_empty());
 
         // Apply bold red formatting
-        grid.apply_sgr(Color::rgb(255, 0, 0), Color::DEFAULT_BG, CellAttrs::BOLD);
+        grid.apply_sgr(
+            CellColor::Rgb(Color::rgb(255, 0, 0)),
+            CellColor::Default,
+            CellAttrs::BOLD,
+        );
         let (fg, bg, attrs) = grid.current_formatting();
-        assert_eq!(fg, Color::rgb(255, 0, 0));
-        assert_eq!(bg, Color::DEFAULT_BG);
+        assert_eq!(fg, CellColor::Rgb(Color::rgb(255, 0, 0)));
+        assert_eq!(bg, CellColor::Default);
         assert!(attrs.contains(CellAttrs::BOLD));
 
         // Print a character with formatting
         grid.print_char('A');
         let cell = grid.get_cell(0, 0).unwrap();
         assert_eq!(cell.glyph_id, b'A' as u32);
-        assert_eq!(cell.fg_color, Color::rgb(255, 0, 0));
+        assert_eq!(cell.fg_color, CellColor::Rgb(Color::rgb(255, 0, 0)));
         assert!(cell.attrs.contains(CellAttrs::BOLD));
 
         // Reset formatting
         grid.reset_formatting();
         let (fg, bg, attrs) = grid.current_formatting();
-        assert_eq!(fg, Color::DEFAULT_FG);
-        assert_eq!(bg, Color::DEFAULT_BG);
+        assert_eq!(fg, CellColor::Default);
+        assert_eq!(bg, CellColor::Default);
         assert!(attrs.is_empty());
     }
 
@@ -1546,7 +3357,11 @@ mod tests {
         let mut grid = TerminalGrid::new(3, 3);
 
         // Apply formatting
-        grid.apply_sgr(Color::rgb(0, 255, 0), Color::DEFAULT_BG, CellAttrs::ITALIC);
+        grid.apply_sgr(
+            CellColor::Rgb(Color::rgb(0, 255, 0)),
+            CellColor::Default,
+            CellAttrs::ITALIC,
+        );
 
         // Print characters that will wrap
         grid.print_char('A');
@@ -1559,8 +3374,8 @@ mod tests {
         // Check that formatting is preserved across wrapping
         let cell_a = grid.get_cell(0, 0).unwrap();
         let cell_d = grid.get_cell(0, 1).unwrap();
-        assert_eq!(cell_a.fg_color, Color::rgb(0, 255, 0));
-        assert_eq!(cell_d.fg_color, Color::rgb(0, 255, 0));
+        assert_eq!(cell_a.fg_color, CellColor::Rgb(Color::rgb(0, 255, 0)));
+        assert_eq!(cell_d.fg_color, CellColor::Rgb(Color::rgb(0, 255, 0)));
         assert!(cell_a.attrs.contains(CellAttrs::ITALIC));
         assert!(cell_d.attrs.contains(CellAttrs::ITALIC));
     }
@@ -1600,7 +3415,7 @@ mod tests {
         }
 
         // Should be limited to max_scrollback + rows
-        assert!(grid.scrollback.len() <= 7); // 5 scrollback + 2 viewport rows
+        assert!(grid.total_lines() <= 7); // 5 scrollback + 2 viewport rows
     }
 
     #[test]
@@ -1642,7 +3457,7 @@ mod tests {
         }
 
         // Should be limited to max_scrollback + viewport rows (10 + 3 = 13)
-        assert!(grid.scrollback.len() <= 13);
+        assert!(grid.total_lines() <= 13);
 
         // Test scrolling through the entire buffer
         let max_scroll = grid.scrollback_len();
@@ -1725,7 +3540,7 @@ mod tests {
         );
 
         // Buffer should respect size limits
-        assert!(grid.scrollback.len() <= 10024); // 10000 + 24 viewport rows
+        assert!(grid.total_lines() <= 10024); // 10000 + 24 viewport rows
     }
 
     #[test]
@@ -1763,7 +3578,7 @@ mod tests {
 
         // Test scrolling beyond limits
         grid.scroll_up(100);
-        let max_offset = grid.scrollback.len().saturating_sub(grid.rows as usize);
+        let max_offset = grid.scrollback_len();
         assert_eq!(grid.viewport_offset, max_offset);
 
         // Test scrolling down beyond limits
@@ -1808,14 +3623,14 @@ mod tests {
             grid.add_line(line);
         }
 
-        assert_eq!(grid.scrollback.len(), 103);
+        assert_eq!(grid.total_lines(), 103);
 
         // Add one more line to trigger limit
         let line: CellRow = vec![Cell::new(b'Z' as u32); 10];
         grid.add_line(line);
 
         // Should maintain exactly the target size
-        assert_eq!(grid.scrollback.len(), 103);
+        assert_eq!(grid.total_lines(), 103);
 
         // Verify the oldest content was removed (no longer see first line)
         grid.scroll_up(grid.scrollback_len());
@@ -2382,6 +4197,144 @@ mod tests {
         assert_eq!(grid.cursor_position(), (9, 4)); // Should be clamped
     }
 
+    /// Fill each screen row with the given text, starting at column 0
+    fn fill_rows(grid: &mut TerminalGrid, lines: &[&str]) {
+        for (row, text) in lines.iter().enumerate() {
+            for (col, ch) in text.chars().enumerate() {
+                grid.set_cell(col as u16, row as u16, Cell::new(ch as u32));
+            }
+        }
+    }
+
+    #[test]
+    fn test_erase_in_display_and_line() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(5, 3);
+        fill_rows(&mut grid, &["AAAAA", "BBBBB", "CCCCC"]);
+
+        grid.set_cursor_position(2, 1);
+        grid.handle_csi_action(&CsiAction::EraseInDisplay(DisplayErase::Below));
+        assert_eq!(grid.get_viewport_text(), vec!["AAAAA", "BB   ", "     "]);
+
+        fill_rows(&mut grid, &["AAAAA", "BBBBB", "CCCCC"]);
+        grid.handle_csi_action(&CsiAction::EraseInDisplay(DisplayErase::Above));
+        assert_eq!(grid.get_viewport_text(), vec!["     ", "   BB", "CCCCC"]);
+
+        fill_rows(&mut grid, &["AAAAA", "BBBBB", "CCCCC"]);
+        grid.handle_csi_action(&CsiAction::EraseInLine(LineErase::Right));
+        assert_eq!(grid.get_viewport_text()[1], "BB   ");
+
+        fill_rows(&mut grid, &["AAAAA", "BBBBB", "CCCCC"]);
+        grid.handle_csi_action(&CsiAction::EraseInLine(LineErase::Left));
+        assert_eq!(grid.get_viewport_text()[1], "   BB");
+
+        grid.handle_csi_action(&CsiAction::EraseInLine(LineErase::All));
+        assert_eq!(grid.get_viewport_text()[1], "     ");
+
+        grid.handle_csi_action(&CsiAction::EraseInDisplay(DisplayErase::All));
+        assert!(grid.get_viewport_text().iter().all(|l| l == "     "));
+        assert_eq!(grid.cursor_position(), (2, 1)); // ED does not move the cursor
+    }
+
+    #[test]
+    fn test_erase_scrollback_keeps_screen() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(5, 3);
+        for _ in 0..10 {
+            grid.newline();
+        }
+        grid.print_char('X');
+        assert!(grid.scrollback_len() > 0);
+
+        grid.handle_csi_action(&CsiAction::EraseInDisplay(DisplayErase::Scrollback));
+        assert_eq!(grid.scrollback_len(), 0);
+        assert_eq!(grid.get_cell(0, 2).unwrap().glyph_id, b'X' as u32);
+    }
+
+    #[test]
+    fn test_insert_delete_erase_chars() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(6, 2);
+        fill_rows(&mut grid, &["ABCDEF"]);
+
+        grid.set_cursor_position(1, 0);
+        grid.handle_csi_action(&CsiAction::InsertChars(2));
+        assert_eq!(grid.get_viewport_text()[0], "A  BCD");
+
+        grid.handle_csi_action(&CsiAction::DeleteChars(3));
+        assert_eq!(grid.get_viewport_text()[0], "ACD   ");
+
+        grid.handle_csi_action(&CsiAction::EraseChars(1));
+        assert_eq!(grid.get_viewport_text()[0], "A D   ");
+        assert_eq!(grid.cursor_position(), (1, 0));
+
+        // Counts larger than the rest of the line are clamped
+        grid.handle_csi_action(&CsiAction::InsertChars(100));
+        assert_eq!(grid.get_viewport_text()[0], "A     ");
+        assert_eq!(grid.get_viewport()[0].len(), 6);
+    }
+
+    #[test]
+    fn test_insert_and_delete_lines() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(3, 4);
+        fill_rows(&mut grid, &["AAA", "BBB", "CCC", "DDD"]);
+
+        grid.set_cursor_position(2, 1);
+        grid.handle_csi_action(&CsiAction::InsertLines(2));
+        assert_eq!(grid.get_viewport_text(), vec!["AAA", "   ", "   ", "BBB"]);
+        assert_eq!(grid.cursor_position(), (0, 1));
+
+        grid.handle_csi_action(&CsiAction::DeleteLines(2));
+        assert_eq!(grid.get_viewport_text(), vec!["AAA", "BBB", "   ", "   "]);
+
+        // IL/DL never touch scrollback
+        assert_eq!(grid.scrollback_len(), 0);
+    }
+
+    #[test]
+    fn test_scroll_up_and_down() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(3, 3);
+        fill_rows(&mut grid, &["AAA", "BBB", "CCC"]);
+
+        grid.handle_csi_action(&CsiAction::ScrollUp(1));
+        assert_eq!(grid.get_viewport_text(), vec!["BBB", "CCC", "   "]);
+        assert_eq!(grid.scrollback_len(), 1);
+
+        grid.handle_csi_action(&CsiAction::ScrollDown(2));
+        assert_eq!(grid.get_viewport_text(), vec!["   ", "   ", "BBB"]);
+        assert_eq!(grid.scrollback_len(), 1);
+    }
+
+    #[test]
+    fn test_vpa_tabs_and_repeat() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(30, 5);
+
+        grid.set_cursor_position(4, 0);
+        grid.handle_csi_action(&CsiAction::LinePositionAbsolute(3));
+        assert_eq!(grid.cursor_position(), (4, 3));
+
+        grid.handle_csi_action(&CsiAction::CursorForwardTab(2));
+        assert_eq!(grid.cursor_position(), (16, 3));
+
+        grid.handle_csi_action(&CsiAction::CursorBackwardTab(1));
+        assert_eq!(grid.cursor_position(), (8, 3));
+
+        grid.handle_csi_action(&CsiAction::CursorBackwardTab(5));
+        assert_eq!(grid.cursor_position(), (0, 3));
+
+        grid.handle_csi_action(&CsiAction::CursorForwardTab(10));
+        assert_eq!(grid.cursor_position(), (29, 3));
+
+        grid.set_cursor_position(0, 4);
+        grid.print_char('=');
+        grid.handle_csi_action(&CsiAction::RepeatChar(4));
+        assert_eq!(grid.get_viewport_text()[4].trim_end(), "=====");
+        assert_eq!(grid.cursor_position(), (5, 4));
+    }
+
     #[test]
     fn test_complete_selection_workflow() {
         use quantaterm_core::CsiAction;
@@ -2482,4 +4435,1211 @@ mod tests {
 
         println!("\n✨ Complete workflow test passed!");
     }
+
+    #[test]
+    fn test_mode_actions() {
+        use quantaterm_core::{AnsiMode, CsiAction};
+        let mut grid = TerminalGrid::new(5, 3);
+
+        let paste = Mode::Dec(DecMode::BracketedPaste);
+        grid.handle_csi_action(&CsiAction::Mode(ModeAction::Set(paste)));
+        assert!(grid.modes().bracketed_paste);
+
+        // XTSAVE / XTRESTORE round trip
+        grid.handle_mode_action(&ModeAction::Save(paste));
+        grid.handle_mode_action(&ModeAction::Reset(paste));
+        assert!(!grid.modes().bracketed_paste);
+        grid.handle_mode_action(&ModeAction::Restore(paste));
+        assert!(grid.modes().bracketed_paste);
+
+        grid.set_mode(Mode::Dec(DecMode::ShowCursor), false);
+        grid.set_mode(Mode::Dec(DecMode::CursorKeys), true);
+        grid.set_mode(Mode::Dec(DecMode::FocusReporting), true);
+        assert!(!grid.modes().cursor_visible);
+        assert!(grid.modes().application_cursor_keys);
+        assert!(grid.modes().focus_reporting);
+
+        // Setting origin mode homes the cursor
+        grid.set_cursor_position(3, 2);
+        grid.set_mode(Mode::Dec(DecMode::Origin), true);
+        assert_eq!(grid.cursor_position(), (0, 0));
+
+        // Unknown modes leave the state untouched
+        let before = grid.modes().clone();
+        grid.set_mode(Mode::Ansi(AnsiMode::Unknown(2)), true);
+        assert_eq!(grid.modes(), &before);
+    }
+
+    #[test]
+    fn test_autowrap_and_insert_modes() {
+        use quantaterm_core::AnsiMode;
+        let mut grid = TerminalGrid::new(3, 2);
+
+        grid.set_mode(Mode::Dec(DecMode::Autowrap), false);
+        for c in "abcd".chars() {
+            grid.print_char(c);
+        }
+        assert_eq!(grid.get_viewport_text()[0], "abd");
+        assert_eq!(grid.cursor_position(), (2, 0));
+
+        grid.set_mode(Mode::Ansi(AnsiMode::Insert), true);
+        grid.set_cursor_position(0, 0);
+        grid.print_char('X');
+        assert_eq!(grid.get_viewport_text()[0], "Xab");
+    }
+
+    #[test]
+    fn test_linefeed_newline_mode() {
+        use quantaterm_core::AnsiMode;
+        let mut grid = TerminalGrid::new(5, 3);
+
+        grid.set_cursor_position(2, 0);
+        grid.execute_control(b'\n');
+        assert_eq!(grid.cursor_position(), (2, 1));
+
+        grid.set_mode(Mode::Ansi(AnsiMode::LineFeedNewLine), true);
+        grid.execute_control(b'\n');
+        assert_eq!(grid.cursor_position(), (0, 2));
+    }
+
+    #[test]
+    fn test_alt_screen_preserves_primary() {
+        let mut grid = TerminalGrid::with_scrollback(4, 2, 100);
+        for line in ["one", "two", "three"] {
+            for c in line.chars() {
+                grid.print_char(c);
+            }
+            grid.execute_control(b'\r');
+            grid.execute_control(b'\n');
+        }
+        grid.print_char('$');
+        let primary_text = grid.get_viewport_text();
+        let primary_history = grid.scrollback_len();
+        let primary_cursor = grid.cursor_position();
+
+        grid.set_mode(Mode::Dec(DecMode::AltScreenSaveCursor), true);
+        assert!(grid.is_alt_screen_active());
+        assert_eq!(grid.get_viewport_text(), vec!["    ", "    "]);
+        assert_eq!(grid.scrollback_len(), 0);
+
+        // TUI output scrolls without touching any history
+        grid.set_cursor_position(0, 0);
+        for _ in 0..10 {
+            grid.print_char('x');
+            grid.execute_control(b'\r');
+            grid.execute_control(b'\n');
+        }
+        assert_eq!(grid.scrollback_len(), 0);
+        grid.scroll_up(5);
+        assert_eq!(grid.viewport_offset(), 0);
+
+        grid.set_mode(Mode::Dec(DecMode::AltScreenSaveCursor), false);
+        assert!(!grid.is_alt_screen_active());
+        assert_eq!(grid.get_viewport_text(), primary_text);
+        assert_eq!(grid.scrollback_len(), primary_history);
+        assert_eq!(grid.cursor_position(), primary_cursor);
+    }
+
+    #[test]
+    fn test_alt_screen_modes_47_and_1047() {
+        let mut grid = TerminalGrid::new(3, 2);
+        grid.print_char('p');
+
+        // Mode 47 keeps alternate contents between switches
+        grid.set_mode(Mode::Dec(DecMode::AltScreen), true);
+        grid.set_cursor_position(0, 1);
+        grid.print_char('a');
+        grid.set_mode(Mode::Dec(DecMode::AltScreen), false);
+        assert_eq!(grid.get_viewport_text()[0], "p  ");
+        grid.set_mode(Mode::Dec(DecMode::AltScreen), true);
+        assert_eq!(grid.get_viewport_text()[1], "a  ");
+
+        // Mode 1047 clears the alternate screen on the way out
+        grid.set_mode(Mode::Dec(DecMode::AltScreenClear), false);
+        assert_eq!(grid.get_viewport_text()[0], "p  ");
+        grid.set_mode(Mode::Dec(DecMode::AltScreen), true);
+        assert_eq!(grid.get_viewport_text(), vec!["   ", "   "]);
+    }
+
+    #[test]
+    fn test_saved_cursor_per_buffer() {
+        let mut grid = TerminalGrid::new(10, 5);
+        grid.apply_sgr(
+            CellColor::Rgb(Color::rgb(255, 0, 0)),
+            CellColor::Default,
+            CellAttrs::BOLD,
+        );
+        grid.set_cursor_position(4, 3);
+        grid.set_mode(Mode::Dec(DecMode::SaveCursor), true);
+
+        grid.set_mode(Mode::Dec(DecMode::AltScreen), true);
+        assert_eq!(grid.saved_cursor(), None);
+        grid.set_cursor_position(1, 1);
+        grid.save_cursor();
+        grid.set_mode(Mode::Dec(DecMode::AltScreen), false);
+
+        grid.reset_formatting();
+        grid.set_mode(Mode::Dec(DecMode::SaveCursor), false);
+        assert_eq!(grid.cursor_position(), (4, 3));
+        assert_eq!(
+            grid.current_formatting(),
+            (
+                CellColor::Rgb(Color::rgb(255, 0, 0)),
+                CellColor::Default,
+                CellAttrs::BOLD
+            )
+        );
+    }
+
+    #[test]
+    fn test_resize_on_alt_screen_keeps_primary() {
+        let mut grid = TerminalGrid::new(5, 3);
+        for c in "hello".chars() {
+            grid.print_char(c);
+        }
+        grid.set_mode(Mode::Dec(DecMode::AltScreenSaveCursor), true);
+        grid.resize(8, 4);
+        assert_eq!(grid.get_viewport().len(), 4);
+        assert_eq!(grid.get_viewport()[0].len(), 8);
+
+        grid.set_mode(Mode::Dec(DecMode::AltScreenSaveCursor), false);
+        assert_eq!(grid.get_viewport_text()[0], "hello   ");
+        assert_eq!(grid.get_viewport().len(), 4);
+        assert!(grid.get_viewport().iter().all(|row| row.len() == 8));
+    }
+
+    #[test]
+    fn test_scroll_region_keeps_scrollback_clean() {
+        let mut grid = TerminalGrid::with_scrollback(3, 4, 100);
+        fill_rows(&mut grid, &["AAA", "BBB", "CCC", "DDD"]);
+
+        // Region rows 1..=2, like a status line above and below
+        grid.set_scroll_region(1, Some(2));
+        assert_eq!(grid.cursor_position(), (0, 0));
+        grid.set_cursor_position(0, 2);
+        grid.execute_control(b'\n');
+        grid.index();
+
+        let text = grid.get_viewport_text();
+        assert_eq!(text, vec!["AAA", "   ", "   ", "DDD"]);
+        assert_eq!(grid.cursor_position(), (0, 2));
+        assert_eq!(grid.scrollback_len(), 0);
+
+        // Full-screen scrolling still feeds scrollback
+        grid.set_scroll_region(0, None);
+        grid.set_cursor_position(0, 3);
+        grid.execute_control(b'\n');
+        assert_eq!(grid.scrollback_len(), 1);
+    }
+
+    #[test]
+    fn test_reverse_index_and_next_line() {
+        let mut grid = TerminalGrid::new(3, 4);
+        fill_rows(&mut grid, &["AAA", "BBB", "CCC", "DDD"]);
+
+        grid.set_scroll_region(1, Some(3));
+        grid.set_cursor_position(2, 1);
+        grid.reverse_index();
+        assert_eq!(grid.get_viewport_text(), vec!["AAA", "   ", "BBB", "CCC"]);
+        assert_eq!(grid.cursor_position(), (2, 1));
+
+        grid.set_cursor_position(2, 3);
+        grid.next_line();
+        assert_eq!(grid.get_viewport_text(), vec!["AAA", "BBB", "CCC", "   "]);
+        assert_eq!(grid.cursor_position(), (0, 3));
+
+        // Outside the region RI just moves up and stops at the top row
+        grid.set_cursor_position(0, 0);
+        grid.reverse_index();
+        assert_eq!(grid.cursor_position(), (0, 0));
+        assert_eq!(grid.get_viewport_text()[0], "AAA");
+    }
+
+    #[test]
+    fn test_region_bounded_line_operations() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(3, 5);
+        fill_rows(&mut grid, &["AAA", "BBB", "CCC", "DDD", "EEE"]);
+        grid.handle_csi_action(&CsiAction::SetScrollRegion {
+            top: 1,
+            bottom: Some(3),
+        });
+
+        grid.set_cursor_position(0, 2);
+        grid.insert_lines(1);
+        assert_eq!(
+            grid.get_viewport_text(),
+            vec!["AAA", "BBB", "   ", "CCC", "EEE"]
+        );
+
+        grid.delete_lines(1);
+        assert_eq!(
+            grid.get_viewport_text(),
+            vec!["AAA", "BBB", "CCC", "   ", "EEE"]
+        );
+
+        grid.scroll_screen_down(1);
+        assert_eq!(
+            grid.get_viewport_text(),
+            vec!["AAA", "   ", "BBB", "CCC", "EEE"]
+        );
+        grid.scroll_screen_up(2);
+        assert_eq!(
+            grid.get_viewport_text(),
+            vec!["AAA", "CCC", "   ", "   ", "EEE"]
+        );
+
+        // IL outside the region is ignored
+        grid.set_cursor_position(0, 4);
+        grid.insert_lines(1);
+        assert_eq!(grid.get_viewport_text()[4], "EEE");
+    }
+
+    #[test]
+    fn test_origin_mode_addressing() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(10, 10);
+        grid.set_scroll_region(2, Some(5));
+
+        grid.set_mode(Mode::Dec(DecMode::Origin), true);
+        assert_eq!(grid.cursor_position(), (0, 2));
+
+        grid.handle_csi_action(&CsiAction::CursorPosition(1, 4));
+        assert_eq!(grid.cursor_position(), (4, 3));
+
+        // Addressing is clamped to the region
+        grid.handle_csi_action(&CsiAction::CursorPosition(9, 0));
+        assert_eq!(grid.cursor_position(), (0, 5));
+        grid.handle_csi_action(&CsiAction::LinePositionAbsolute(0));
+        assert_eq!(grid.cursor_position(), (0, 2));
+
+        // Relative movement stops at the margins
+        grid.move_cursor_up(5);
+        assert_eq!(grid.cursor_position(), (0, 2));
+        grid.move_cursor_down(10);
+        assert_eq!(grid.cursor_position(), (0, 5));
+
+        grid.set_mode(Mode::Dec(DecMode::Origin), false);
+        assert_eq!(grid.cursor_position(), (0, 0));
+        grid.handle_csi_action(&CsiAction::CursorPosition(8, 0));
+        assert_eq!(grid.cursor_position(), (0, 8));
+    }
+
+    #[test]
+    fn test_invalid_scroll_region_is_ignored() {
+        let mut grid = TerminalGrid::new(5, 5);
+        grid.set_scroll_region(3, Some(3));
+        assert_eq!(grid.scroll_region(), (0, 4));
+        grid.set_scroll_region(1, Some(20));
+        assert_eq!(grid.scroll_region(), (1, 4));
+
+        grid.resize(5, 8);
+        assert_eq!(grid.scroll_region(), (0, 7));
+    }
+
+    #[test]
+    fn test_extended_attributes_and_underline_color() {
+        let mut grid = TerminalGrid::new(5, 2);
+        let curly = CellAttrs::CURLY_UNDERLINE | CellAttrs::DIM | CellAttrs::OVERLINE;
+        grid.apply_sgr(CellColor::Default, CellColor::Default, curly);
+        grid.set_underline_color(Some(CellColor::Rgb(Color::rgb(255, 0, 0))));
+        grid.print_char('e');
+
+        let cell = grid.get_cell(0, 0).unwrap();
+        assert_eq!(cell.attrs, curly);
+        assert_eq!(
+            cell.underline_color,
+            Some(CellColor::Rgb(Color::rgb(255, 0, 0)))
+        );
+        assert!(!cell.is_empty());
+
+        grid.reset_formatting();
+        assert_eq!(grid.current_underline_color(), None);
+        grid.print_char('x');
+        assert_eq!(grid.get_cell(1, 0).unwrap().underline_color, None);
+    }
+
+    #[test]
+    fn test_esc_charsets_and_cursor_save() {
+        use quantaterm_core::Charset;
+        let mut grid = TerminalGrid::new(6, 3);
+
+        grid.handle_esc_action(&EscAction::DesignateCharset(
+            CharsetSlot::G0,
+            Charset::DecSpecialGraphics,
+        ));
+        grid.set_cursor_position(1, 1);
+        grid.handle_esc_action(&EscAction::SaveCursor);
+        for c in "lqk".chars() {
+            grid.print_char(c);
+        }
+        assert_eq!(grid.get_viewport_text()[1], " ┌─┐  ");
+
+        // SI/SO switch between G0 and G1, which is still ASCII
+        grid.execute_control(0x0e);
+        grid.print_char('q');
+        grid.execute_control(0x0f);
+        grid.print_char('q');
+        assert_eq!(grid.get_viewport_text()[1], " ┌─┐q─");
+
+        // DECRC brings back the cursor and the charsets saved with it
+        grid.handle_esc_action(&EscAction::DesignateCharset(
+            CharsetSlot::G0,
+            Charset::Ascii,
+        ));
+        grid.handle_esc_action(&EscAction::RestoreCursor);
+        assert_eq!(grid.cursor_position(), (1, 1));
+        assert_eq!(
+            grid.charsets().charset(CharsetSlot::G0),
+            Charset::DecSpecialGraphics
+        );
+    }
+
+    #[test]
+    fn test_tab_clear_and_reset() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(20, 2);
+        grid.handle_csi_action(&CsiAction::TabClear(TabClear::All));
+        grid.tab();
+        assert_eq!(grid.cursor_position(), (19, 0));
+
+        for col in [5, 12] {
+            grid.set_cursor_position(col, 0);
+            grid.set_tab_stop();
+        }
+        grid.set_cursor_position(0, 0);
+        grid.tab();
+        assert_eq!(grid.cursor_position(), (5, 0));
+        grid.tab();
+        assert_eq!(grid.cursor_position(), (12, 0));
+        grid.tab_backward(1);
+        assert_eq!(grid.cursor_position(), (5, 0));
+
+        grid.handle_csi_action(&CsiAction::TabClear(TabClear::Current));
+        grid.set_cursor_position(0, 0);
+        grid.tab();
+        assert_eq!(grid.cursor_position(), (12, 0));
+
+        // Resizing and RIS restore the stops every 8 columns
+        grid.resize(30, 2);
+        assert!(!grid.tab_stops().is_set(12));
+        assert!(grid.tab_stops().is_set(24));
+        grid.set_cursor_position(3, 0);
+        grid.set_tab_stop();
+        grid.full_reset();
+        assert!(!grid.tab_stops().is_set(3));
+        assert!(grid.tab_stops().is_set(8));
+    }
+
+    #[test]
+    fn test_esc_tab_set_and_keypad() {
+        let mut grid = TerminalGrid::new(20, 2);
+        grid.set_cursor_position(3, 0);
+        grid.handle_esc_action(&EscAction::HorizontalTabSet);
+
+        grid.set_cursor_position(0, 0);
+        grid.tab();
+        assert_eq!(grid.cursor_position(), (3, 0));
+        grid.tab_forward(2);
+        assert_eq!(grid.cursor_position(), (16, 0));
+        grid.tab_backward(2);
+        assert_eq!(grid.cursor_position(), (3, 0));
+
+        grid.handle_esc_action(&EscAction::ApplicationKeypad);
+        assert!(grid.modes().application_keypad);
+        grid.handle_esc_action(&EscAction::NumericKeypad);
+        assert!(!grid.modes().application_keypad);
+    }
+
+    #[test]
+    fn test_full_reset() {
+        use quantaterm_core::Charset;
+        let mut grid = TerminalGrid::with_scrollback(4, 2, 10);
+        fill_rows(&mut grid, &["AAAA", "BBBB"]);
+        grid.set_cursor_position(0, 1);
+        grid.execute_control(b'\n');
+        grid.apply_sgr(
+            CellColor::Rgb(Color::rgb(255, 0, 0)),
+            CellColor::Default,
+            CellAttrs::BOLD,
+        );
+        grid.set_mode(Mode::Dec(DecMode::Autowrap), false);
+        grid.set_mode(Mode::Dec(DecMode::BracketedPaste), true);
+        grid.handle_esc_action(&EscAction::DesignateCharset(
+            CharsetSlot::G0,
+            Charset::DecSpecialGraphics,
+        ));
+        grid.save_cursor();
+        grid.set_mode(Mode::Dec(DecMode::AltScreenSaveCursor), true);
+        grid.set_scroll_region(0, Some(0));
+        grid.print_char('x');
+
+        grid.handle_esc_action(&EscAction::Reset);
+
+        assert!(!grid.is_alt_screen_active());
+        assert_eq!(grid.get_viewport_text(), vec!["    ", "    "]);
+        assert_eq!(grid.scrollback_len(), 1);
+        assert_eq!(grid.cursor_position(), (0, 0));
+        assert_eq!(grid.modes(), &TerminalModes::default());
+        assert_eq!(
+            grid.current_formatting(),
+            (CellColor::Default, CellColor::Default, CellAttrs::empty())
+        );
+        assert_eq!(grid.charsets(), &CharsetState::new());
+        assert_eq!(grid.saved_cursor(), None);
+        assert_eq!(grid.scroll_region(), (0, 1));
+    }
+
+    #[test]
+    fn test_soft_reset() {
+        use quantaterm_core::{AnsiMode, CsiAction};
+        let mut grid = TerminalGrid::new(10, 5);
+        grid.print_char('k');
+        grid.set_scroll_region(1, Some(3));
+        grid.set_mode(Mode::Dec(DecMode::Origin), true);
+        grid.set_mode(Mode::Dec(DecMode::ShowCursor), false);
+        grid.set_mode(Mode::Ansi(AnsiMode::Insert), true);
+        grid.set_mode(Mode::Dec(DecMode::BracketedPaste), true);
+        grid.handle_esc_action(&EscAction::ApplicationKeypad);
+        grid.apply_sgr(
+            CellColor::Default,
+            CellColor::Rgb(Color::rgb(0, 0, 255)),
+            CellAttrs::REVERSE,
+        );
+        grid.save_cursor();
+        grid.set_cursor_position(5, 2);
+
+        grid.handle_csi_action(&CsiAction::SoftReset);
+
+        let modes = grid.modes();
+        assert!(modes.cursor_visible && !modes.origin && !modes.insert);
+        assert!(!modes.application_keypad);
+        // Modes outside DECSTR's list are kept
+        assert!(modes.bracketed_paste);
+        assert_eq!(grid.scroll_region(), (0, 4));
+        assert_eq!(grid.saved_cursor(), None);
+        assert_eq!(
+            grid.current_formatting(),
+            (CellColor::Default, CellColor::Default, CellAttrs::empty())
+        );
+
+        // Neither the cursor nor the screen contents move
+        assert_eq!(grid.cursor_position(), (5, 2));
+        assert_eq!(grid.get_viewport_text()[0], "k         ");
+    }
+
+    #[test]
+    fn test_wide_characters() {
+        let mut grid = TerminalGrid::new(6, 3);
+        for c in "a中b".chars() {
+            grid.print_char(c);
+        }
+        assert_eq!(grid.cursor_position(), (4, 0));
+        assert_eq!(grid.get_viewport_text()[0], "a中b  ");
+        assert!(grid.get_cell(1, 0).unwrap().flags.contains(CellFlags::WIDE));
+        assert!(grid.get_cell(2, 0).unwrap().is_wide_spacer());
+
+        // Overwriting either half blanks the whole pair
+        grid.set_cursor_position(2, 0);
+        grid.print_char('x');
+        assert_eq!(grid.get_viewport_text()[0], "a xb  ");
+        assert!(grid.get_cell(1, 0).unwrap().is_empty());
+
+        // A wide character that does not fit in the last column wraps whole
+        grid.set_cursor_position(5, 1);
+        grid.print_char('🦀');
+        assert_eq!(grid.get_viewport_text()[1], "      ");
+        assert_eq!(grid.get_viewport_text()[2], "🦀    ");
+        assert_eq!(grid.cursor_position(), (2, 2));
+
+        // Without autowrap it lands on the last two columns instead
+        grid.set_mode(Mode::Dec(DecMode::Autowrap), false);
+        grid.set_cursor_position(5, 2);
+        grid.print_char('中');
+        assert_eq!(grid.get_viewport_text()[2], "🦀  中");
+        assert_eq!(grid.cursor_position(), (5, 2));
+    }
+
+    #[test]
+    fn test_wide_characters_in_line_edits() {
+        let mut grid = TerminalGrid::new(5, 2);
+        for c in "abc中".chars() {
+            grid.print_char(c);
+        }
+
+        // Inserting pushes the pair past the edge, dropping it entirely
+        grid.set_cursor_position(0, 0);
+        grid.insert_chars(1);
+        assert_eq!(grid.get_viewport_text()[0], " abc ");
+        assert!(grid.get_cell(4, 0).unwrap().is_empty());
+
+        grid.set_cursor_position(3, 0);
+        grid.print_char('中');
+        grid.set_cursor_position(4, 0);
+        grid.delete_chars(1);
+        assert_eq!(grid.get_viewport_text()[0], " ab  ");
+
+        grid.set_cursor_position(3, 0);
+        grid.print_char('中');
+        grid.set_cursor_position(4, 0);
+        grid.erase_in_line(LineErase::Left);
+        assert_eq!(grid.get_viewport_text()[0], "     ");
+    }
+
+    #[test]
+    fn test_wide_character_selection() {
+        let mut grid = TerminalGrid::new(10, 2);
+        for c in "ab 中文 x".chars() {
+            grid.print_char(c);
+        }
+
+        // Starting on a spacer still copies the whole character
+        grid.start_selection(Position::new(4, 0));
+        grid.extend_selection(Position::new(6, 0));
+        assert_eq!(grid.get_selected_text(), Some("中文".to_string()));
+
+        grid.select_word_at(Position::new(5, 0));
+        assert_eq!(grid.get_selected_text(), Some("中文".to_string()));
+    }
+
+    #[test]
+    fn test_ambiguous_width_switch() {
+        let mut grid = TerminalGrid::new(6, 1);
+        grid.print_char('±');
+        assert_eq!(grid.cursor_position(), (1, 0));
+
+        grid.set_ambiguous_width(AmbiguousWidth::Wide);
+        assert_eq!(grid.ambiguous_width(), AmbiguousWidth::Wide);
+        grid.print_char('±');
+        grid.print_char('a');
+        assert_eq!(grid.cursor_position(), (4, 0));
+        assert_eq!(grid.get_viewport_text()[0], "±±a  ");
+    }
+
+    #[test]
+    fn test_resize_keeps_wide_characters_whole() {
+        let mut grid = TerminalGrid::new(6, 1);
+        for c in "ab中c".chars() {
+            grid.print_char(c);
+        }
+        grid.resize(3, 1);
+        assert_eq!(grid.get_viewport_text(), vec!["中c"]);
+        grid.scroll_up(1);
+        assert_eq!(grid.get_viewport_text(), vec!["ab "]);
+    }
+
+    #[test]
+    fn test_zero_size_grid_clamps_to_one_cell() {
+        let mut grid = TerminalGrid::new(0, 0);
+        assert_eq!((grid.cols, grid.rows), (1, 1));
+        grid.print_char('a');
+        grid.print_char('b');
+        assert_eq!(grid.get_viewport_text(), vec!["b"]);
+    }
+
+    #[test]
+    fn test_overwriting_last_column_keeps_soft_wrap() {
+        let mut grid = TerminalGrid::new(5, 3);
+        for c in "ABCDEFGH".chars() {
+            grid.print_char(c);
+        }
+        assert!(grid.is_line_wrapped(0));
+
+        // Readline redraws the line in place
+        grid.set_cursor_position(4, 0);
+        grid.print_char('Z');
+        grid.set_cursor_position(0, 2);
+        assert!(grid.is_line_wrapped(0));
+
+        grid.start_selection(Position::new(0, 0));
+        grid.extend_selection(Position::new(2, 1));
+        assert_eq!(grid.get_selected_text(), Some("ABCDZFGH".to_string()));
+    }
+
+    #[test]
+    fn test_resize_to_zero_clamps_to_one_cell() {
+        let mut grid = TerminalGrid::new(5, 2);
+        grid.print_char('a');
+        grid.resize(0, 0);
+        assert_eq!((grid.cols, grid.rows), (1, 1));
+        assert_eq!(grid.get_viewport_text(), vec!["a"]);
+
+        grid.resize(5, 2);
+        grid.print_char('b');
+        assert_eq!(grid.get_viewport_text()[0], "ab   ");
+    }
+
+    #[test]
+    fn test_soft_wrap_reflow() {
+        let mut grid = TerminalGrid::new(10, 3);
+        for c in "hello world foo".chars() {
+            grid.print_char(c);
+        }
+        assert!(grid.is_line_wrapped(0));
+        assert!(!grid.is_line_wrapped(1));
+        assert_eq!(grid.cursor_position(), (5, 1));
+
+        // Copying the wrapped line gives back one line
+        grid.start_selection(Position::new(0, 0));
+        grid.extend_selection(Position::new(9, 1));
+        assert_eq!(
+            grid.get_selected_text(),
+            Some("hello world foo".to_string())
+        );
+
+        // Widening joins the rows again
+        grid.resize(20, 3);
+        assert_eq!(grid.get_viewport_text()[0], "hello world foo     ");
+        assert!(!grid.is_line_wrapped(0));
+        assert_eq!(grid.cursor_position(), (15, 0));
+
+        // Narrowing splits it, pushing the start into history
+        grid.resize(6, 3);
+        assert_eq!(grid.get_viewport_text(), vec!["foo   ", "      ", "      "]);
+        assert_eq!(grid.cursor_position(), (3, 0));
+        grid.scroll_up(2);
+        assert_eq!(grid.get_viewport_text()[..2], ["hello ", "world "]);
+        grid.start_selection(Position::new(0, 0));
+        grid.extend_selection(Position::new(5, 2));
+        assert_eq!(
+            grid.get_selected_text(),
+            Some("hello world foo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_wrapped_wide_character_reflow() {
+        let mut grid = TerminalGrid::new(5, 2);
+        for c in "abcd中".chars() {
+            grid.print_char(c);
+        }
+        assert!(grid.is_line_wrapped(0));
+        grid.start_selection(Position::new(0, 0));
+        grid.extend_selection(Position::new(1, 1));
+        assert_eq!(grid.get_selected_text(), Some("abcd中".to_string()));
+
+        // The padding left in the last column disappears when the row widens
+        grid.resize(6, 2);
+        assert_eq!(grid.get_viewport_text()[0], "abcd中");
+        assert!(!grid.is_line_wrapped(0));
+    }
+
+    #[test]
+    fn test_pending_wrap() {
+        let mut grid = TerminalGrid::new(5, 3);
+        for c in "abcde".chars() {
+            grid.print_char(c);
+        }
+        assert_eq!(grid.cursor_position(), (4, 0));
+        assert!(grid.wrap_pending());
+        assert!(!grid.is_line_wrapped(0));
+
+        // A combining mark still joins the character in the last column
+        grid.print_char('\u{301}');
+        assert_eq!(grid.cell_text(grid.get_cell(4, 0).unwrap()), "e\u{301}");
+
+        // Cursor motion cancels the wrap
+        grid.carriage_return();
+        grid.linefeed();
+        assert!(!grid.wrap_pending());
+        grid.print_char('x');
+        assert_eq!(grid.get_viewport_text()[1], "x    ");
+
+        for c in "1234z".chars() {
+            grid.print_char(c);
+        }
+        assert_eq!(grid.get_viewport_text()[1..], ["x1234", "z    "]);
+        assert!(grid.is_line_wrapped(1));
+
+        // Resizing keeps the cursor just past the last character
+        grid.set_cursor_position(0, 2);
+        for c in "vwxyz".chars() {
+            grid.print_char(c);
+        }
+        grid.resize(7, 3);
+        assert_eq!(grid.get_viewport_text()[1..], ["x1234vw", "xyz    "]);
+        assert_eq!(grid.cursor_position(), (3, 2));
+        assert!(!grid.wrap_pending());
+        grid.resize(5, 3);
+        assert_eq!(grid.get_viewport_text()[1..], ["x1234", "vwxyz"]);
+        assert_eq!(grid.cursor_position(), (4, 2));
+        assert!(grid.wrap_pending());
+    }
+
+    #[test]
+    fn test_background_color_erase() {
+        let blue = CellColor::Indexed(4);
+        let mut grid = TerminalGrid::new(5, 3);
+        grid.apply_sgr(
+            CellColor::Indexed(1),
+            blue,
+            CellAttrs::BOLD | CellAttrs::UNDERLINE,
+        );
+        let is_bce_blank = |cell: &Cell| {
+            cell.glyph_id == b' ' as u32
+                && cell.bg_color == blue
+                && cell.fg_color == CellColor::Default
+                && cell.attrs.is_empty()
+        };
+
+        grid.erase_in_display(DisplayErase::All);
+        assert!(grid.get_viewport().iter().flatten().all(is_bce_blank));
+
+        // Scrolled-in, inserted and shifted-in cells take the background too
+        grid.set_cursor_position(0, 2);
+        grid.linefeed();
+        assert!(grid.get_viewport()[2].iter().all(is_bce_blank));
+        grid.set_cursor_position(0, 0);
+        grid.apply_sgr(CellColor::Default, CellColor::Default, CellAttrs::empty());
+        grid.print_char('a');
+        grid.print_char('b');
+        grid.apply_sgr(CellColor::Default, blue, CellAttrs::empty());
+        grid.set_cursor_position(0, 0);
+        grid.delete_chars(1);
+        assert!(is_bce_blank(grid.get_cell(4, 0).unwrap()));
+        grid.insert_lines(1);
+        assert!(grid.get_viewport()[0].iter().all(is_bce_blank));
+        assert_eq!(grid.get_viewport_text()[1], "b    ");
+    }
+
+    #[test]
+    fn test_selective_erase() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(8, 2);
+        let print =
+            |grid: &mut TerminalGrid, text: &str| text.chars().for_each(|c| grid.print_char(c));
+
+        print(&mut grid, "ab");
+        grid.handle_csi_action(&CsiAction::SetCharProtection(true));
+        print(&mut grid, "c中");
+        grid.handle_csi_action(&CsiAction::SetCharProtection(false));
+        print(&mut grid, "de");
+
+        grid.handle_csi_action(&CsiAction::SelectiveEraseInLine(LineErase::All));
+        assert_eq!(grid.get_viewport_text()[0], "  c中   ");
+        assert!(grid
+            .get_cell(2, 0)
+            .unwrap()
+            .flags
+            .contains(CellFlags::PROTECTED));
+
+        // Plain erase ignores protection
+        grid.handle_csi_action(&CsiAction::EraseInDisplay(DisplayErase::All));
+        assert_eq!(grid.get_viewport_text()[0], "        ");
+
+        // DECSC saves the protection attribute
+        grid.set_protected(true);
+        grid.save_cursor();
+        grid.set_protected(false);
+        grid.restore_cursor();
+        grid.set_cursor_position(0, 1);
+        print(&mut grid, "xy");
+        grid.handle_csi_action(&CsiAction::SelectiveEraseInDisplay(DisplayErase::Above));
+        assert_eq!(grid.get_viewport_text()[1], "xy      ");
+    }
+
+    #[test]
+    fn test_grapheme_clusters() {
+        let mut grid = TerminalGrid::new(12, 2);
+        let text = "e\u{301}👨\u{200D}👩\u{200D}👧❤\u{FE0F}🇺🇸e\u{301}";
+        for c in text.chars() {
+            grid.print_char(c);
+        }
+
+        // The combining accent joins the previous cell
+        let e = grid.get_cell(0, 0).unwrap();
+        assert_eq!(e.glyph_id, 'e' as u32);
+        assert_eq!(grid.cell_text(e), "e\u{301}");
+        assert!(grid.get_cell(1, 0).unwrap().flags.contains(CellFlags::WIDE));
+        // The emoji presentation selector widens the heart
+        assert!(grid.get_cell(3, 0).unwrap().flags.contains(CellFlags::WIDE));
+        assert!(grid.get_cell(4, 0).unwrap().is_wide_spacer());
+        assert_eq!(grid.cursor_position(), (8, 0));
+        // Identical clusters share one table entry
+        assert_eq!(grid.get_cell(7, 0).unwrap().grapheme, e.grapheme);
+        let interned = grid.graphemes().len();
+        grid.execute_control(b'\r');
+        grid.execute_control(b'\n');
+        for c in text.chars() {
+            grid.print_char(c);
+        }
+        assert_eq!(grid.graphemes().len(), interned);
+
+        assert_eq!(grid.get_viewport_text()[1], format!("{text}    "));
+        grid.start_selection(Position::new(0, 0));
+        grid.extend_selection(Position::new(7, 0));
+        assert_eq!(grid.get_selected_text(), Some(text.to_string()));
+    }
+
+    #[test]
+    fn test_grapheme_clusters_freed() {
+        let mut grid = TerminalGrid::new(4, 2);
+        // Overwritten clusters are freed once the table has grown
+        for i in 0..5000 {
+            let base = char::from_u32(0x4E00 + i).unwrap();
+            grid.set_cursor_position(0, 0);
+            grid.print_char(base);
+            grid.print_char('\u{301}');
+        }
+        assert!(grid.graphemes().len() <= 1024, "{}", grid.graphemes().len());
+        let last = char::from_u32(0x4E00 + 4999).unwrap();
+        let cell = grid.get_cell(0, 0).unwrap();
+        assert_eq!(grid.cell_text(cell), format!("{last}\u{301}"));
+
+        // Clusters in history outlive a reset, but not clearing scrollback
+        grid.linefeed();
+        grid.linefeed();
+        grid.full_reset();
+        assert_eq!(grid.graphemes().len(), 1);
+        grid.erase_in_display(DisplayErase::Scrollback);
+        assert!(grid.graphemes().is_empty());
+    }
+
+    #[test]
+    fn test_combining_mark_without_base() {
+        let mut grid = TerminalGrid::new(4, 1);
+        grid.print_char('\u{301}');
+        assert_eq!(grid.cursor_position(), (1, 0));
+        assert!(grid.get_cell(0, 0).unwrap().grapheme.is_none());
+    }
+
+    #[test]
+    fn test_palette_changes_recolor_existing_cells() {
+        let mut grid = TerminalGrid::new(4, 2);
+        grid.apply_sgr(
+            CellColor::Indexed(1),
+            CellColor::Default,
+            CellAttrs::empty(),
+        );
+        grid.print_char('a');
+        let cell = grid.get_cell(0, 0).unwrap().clone();
+        assert_eq!(cell.fg_color, CellColor::Indexed(1));
+        assert_eq!(
+            grid.palette().resolve_fg(cell.fg_color),
+            Color::rgb(128, 0, 0)
+        );
+
+        grid.set_palette_color(1, Color::rgb(255, 85, 85));
+        assert_eq!(
+            grid.palette().resolve_fg(cell.fg_color),
+            Color::rgb(255, 85, 85)
+        );
+        grid.reset_palette_color(1);
+        assert_eq!(
+            grid.palette().resolve_fg(cell.fg_color),
+            Color::rgb(128, 0, 0)
+        );
+
+        // A theme recolors default and indexed cells; OSC 104 and RIS return to it
+        let mut theme = Palette::new();
+        theme.set(1, Color::rgb(204, 36, 29));
+        theme.set_background(Color::rgb(40, 40, 40));
+        grid.set_theme(theme);
+        grid.set_palette_color(1, Color::WHITE);
+        grid.reset_palette();
+        assert_eq!(
+            grid.palette().resolve_fg(cell.fg_color),
+            Color::rgb(204, 36, 29)
+        );
+        assert_eq!(
+            grid.palette().resolve_bg(cell.bg_color),
+            Color::rgb(40, 40, 40)
+        );
+        grid.set_palette_color(1, Color::WHITE);
+        grid.full_reset();
+        assert_eq!(grid.palette().get(1), Color::rgb(204, 36, 29));
+    }
+
+    #[test]
+    fn test_cursor_style() {
+        use quantaterm_core::CsiAction;
+        let mut grid = TerminalGrid::new(6, 2);
+        assert_eq!(grid.cursor_style(), CursorStyle::default());
+
+        let bar = CursorStyle {
+            shape: CursorShape::Bar,
+            blinking: true,
+        };
+        grid.handle_csi_action(&CsiAction::SetCursorStyle(Some(bar)));
+        assert_eq!(grid.cursor_style(), bar);
+        assert!(grid.modes().cursor_blink);
+        grid.set_mode(Mode::Dec(DecMode::CursorBlink), false);
+        assert!(!grid.cursor_style().blinking);
+
+        // DECSCUSR 0 and RIS go back to the configured style
+        let underline = CursorStyle {
+            shape: CursorShape::Underline,
+            blinking: false,
+        };
+        grid.set_default_cursor_style(underline);
+        assert_eq!(grid.cursor_style(), underline);
+        grid.handle_csi_action(&CsiAction::SetCursorStyle(Some(bar)));
+        grid.handle_csi_action(&CsiAction::SetCursorStyle(None));
+        assert_eq!(grid.cursor_style(), underline);
+        grid.handle_csi_action(&CsiAction::SetCursorStyle(Some(bar)));
+        grid.full_reset();
+        assert_eq!(grid.cursor_style(), underline);
+    }
+
+    #[test]
+    fn test_renderer_cursor() {
+        let mut grid = TerminalGrid::new(6, 2);
+        grid.print_char('a');
+        grid.print_char('中');
+        grid.set_cursor_position(1, 0);
+        let cursor = grid.renderer_cursor().unwrap();
+        assert_eq!((cursor.col, cursor.row, cursor.width), (1, 0, 2));
+
+        grid.set_mode(Mode::Dec(DecMode::ShowCursor), false);
+        assert!(grid.renderer_cursor().is_none());
+        grid.set_mode(Mode::Dec(DecMode::ShowCursor), true);
+
+        // Scrolling back moves the cursor down and then out of view
+        for _ in 0..3 {
+            grid.linefeed();
+        }
+        grid.set_cursor_position(0, 0);
+        grid.scroll_up(1);
+        assert_eq!(grid.renderer_cursor().unwrap().row, 1);
+        grid.scroll_up(1);
+        assert!(grid.renderer_cursor().is_none());
+    }
+
+    #[test]
+    fn test_damage_tracking() {
+        let mut grid = TerminalGrid::new(10, 4);
+        assert!(grid.take_damage().is_full());
+        assert!(grid.take_damage().is_empty());
+
+        // Writes damage the rows they touch
+        grid.set_cursor_position(0, 1);
+        grid.print_char('a');
+        grid.set_cursor_position(0, 3);
+        grid.erase_in_line(LineErase::All);
+        assert_eq!(grid.take_damage().rows().collect::<Vec<_>>(), [1, 3]);
+
+        // Scrolling a partial region damages the region only
+        grid.set_scroll_region(2, Some(3));
+        grid.set_cursor_position(0, 3);
+        grid.linefeed();
+        assert_eq!(grid.take_damage().rows().collect::<Vec<_>>(), [2, 3]);
+        grid.set_scroll_region(0, None);
+
+        // Selection changes damage the rows selected before and after
+        grid.start_selection(Position::new(0, 0));
+        grid.extend_selection(Position::new(3, 1));
+        assert_eq!(grid.take_damage().rows().collect::<Vec<_>>(), [0, 1]);
+        grid.clear_selection();
+        assert_eq!(grid.take_damage().rows().collect::<Vec<_>>(), [0, 1]);
+
+        // Scrolling the screen into history moves the rows up, so only the
+        // row moving in at the bottom needs sending
+        grid.set_cursor_position(0, 3);
+        grid.linefeed();
+        let damage = grid.take_damage();
+        assert_eq!(damage.scrolled(), 1);
+        assert_eq!(damage.rows().collect::<Vec<_>>(), [3]);
+
+        // With the view scrolled back, screen rows appear further down
+        grid.scroll_up(1);
+        assert!(grid.take_damage().is_full());
+        grid.set_cursor_position(0, 1);
+        grid.print_char('b');
+        grid.set_cursor_position(0, 3);
+        grid.print_char('c');
+        assert_eq!(grid.take_damage().rows().collect::<Vec<_>>(), [2]);
+
+        // Output scrolling the screen leaves a scrolled-back view as it is
+        let view = grid.get_viewport_text();
+        grid.set_cursor_position(0, 3);
+        grid.linefeed();
+        assert!(grid.take_damage().is_empty());
+        assert_eq!(grid.get_viewport_text(), view);
+
+        grid.set_palette_color(1, Color::rgb(1, 2, 3));
+        assert!(grid.take_damage().is_full());
+    }
+
+    #[test]
+    fn test_line_size() {
+        let mut grid = TerminalGrid::new(10, 3);
+        grid.set_cursor_position(0, 1);
+        grid.handle_esc_action(&EscAction::LineSize(LineSize::DoubleWidth));
+        assert_eq!(grid.line_size(1), LineSize::DoubleWidth);
+
+        // Only half the columns are addressable, and printing wraps at the half
+        grid.move_cursor_to_column(9);
+        assert_eq!(grid.cursor_position(), (4, 1));
+        grid.move_cursor_to_line_start();
+        for c in "abcdef".chars() {
+            grid.print_char(c);
+        }
+        assert_eq!(grid.get_viewport_text()[1], "abcde     ");
+        assert_eq!(grid.get_viewport_text()[2].trim_end(), "f");
+        assert!(grid.is_line_wrapped(1));
+
+        // Editing the row keeps its size; moving onto it clamps the cursor
+        grid.set_cursor_position(0, 1);
+        grid.insert_chars(2);
+        grid.erase_in_line(LineErase::All);
+        assert_eq!(grid.line_size(1), LineSize::DoubleWidth);
+        grid.set_cursor_position(8, 2);
+        grid.move_cursor_up(1);
+        assert_eq!(grid.renderer_cursor().unwrap().col, 4);
+        grid.print_char('z');
+        assert_eq!(grid.get_viewport_text()[1], "    z     ");
+
+        // Resizing cuts a double-size row instead of rewrapping it
+        grid.resize(6, 3);
+        assert_eq!(grid.line_size(1), LineSize::DoubleWidth);
+        assert_eq!(grid.get_viewport_text()[1], "    z ");
+        grid.erase_in_display(DisplayErase::All);
+        assert_eq!(grid.line_size(1), LineSize::SingleWidth);
+    }
+
+    #[test]
+    fn test_compact_scrollback() {
+        let mut grid = TerminalGrid::with_scrollback(300, 2, 10_000);
+        grid.apply_sgr(CellColor::Indexed(2), CellColor::Default, CellAttrs::BOLD);
+        let print_line = |grid: &mut TerminalGrid, text: &str| {
+            for c in text.chars() {
+                grid.print_char(c);
+            }
+            grid.carriage_return();
+            grid.linefeed();
+        };
+        for i in 0..1000 {
+            print_line(&mut grid, &format!("line {i}"));
+        }
+        assert_eq!(grid.scrollback_len(), 999);
+        let stats = grid.scrollback_stats();
+        assert_eq!(stats.lines, 999);
+        assert_eq!(stats.styles, 1);
+        assert!(stats.bytes_per_line() < 200.0, "{stats:?}");
+
+        grid.scroll_up(3);
+        assert_eq!(grid.get_viewport_text()[0].trim_end(), "line 996");
+        assert_eq!(grid.get_cell(0, 1).unwrap().fg_color, CellColor::Indexed(2));
+        assert_eq!(grid.get_cell(299, 1), Some(&Cell::empty()));
+
+        // The scrolled-back view stays put while output arrives
+        print_line(&mut grid, "more");
+        assert_eq!(grid.viewport_offset(), 4);
+        assert_eq!(grid.get_viewport_text()[0].trim_end(), "line 996");
+
+        // Cells edited in view are written back to history
+        grid.set_cell(0, 0, Cell::new('X' as u32));
+        grid.reset_viewport();
+        grid.scroll_up(4);
+        assert_eq!(grid.get_viewport_text()[0].trim_end(), "Xine 996");
+
+        grid.set_scrollback_compression(true);
+        assert_eq!(grid.scrollback_stats().compressed_pages, 1);
+        grid.scroll_to_top();
+        assert_eq!(grid.get_viewport_text()[0].trim_end(), "line 0");
+
+        // Over budget, whole pages are dropped oldest first
+        grid.set_scrollback_limits(10_000, 1);
+        assert_eq!(grid.scrollback_len(), 1000 % history::PAGE_LINES);
+        assert_eq!(grid.viewport_offset(), grid.scrollback_len());
+        assert_eq!(grid.get_viewport_text()[0].trim_end(), "line 768");
+    }
+
+    #[test]
+    fn test_resize_leaves_compressed_history() {
+        let mut grid = TerminalGrid::with_scrollback(10, 2, 10_000);
+        grid.set_scrollback_compression(true);
+        for i in 0..1000 {
+            for c in format!("line {i}").chars() {
+                grid.print_char(c);
+            }
+            grid.carriage_return();
+            grid.linefeed();
+        }
+        assert_eq!(grid.scrollback_stats().compressed_pages, 1);
+
+        // Only lines past the compressed page are rewrapped, two rows each;
+        // the second row of "line 999" stays on screen
+        grid.resize(5, 2);
+        let kept = history::PAGE_LINES;
+        assert_eq!(grid.scrollback_len(), kept + (1000 - kept) * 2 - 1);
+        grid.scroll_to_top();
+        assert_eq!(grid.get_viewport_text(), ["line ", "line "]);
+        grid.scroll_down(kept - 1);
+        assert_eq!(grid.get_viewport_text(), ["line ", "line "]);
+        grid.scroll_down(1);
+        assert_eq!(grid.get_viewport_text(), ["line ", "256  "]);
+    }
+
+    #[test]
+    fn test_search_scrollback_and_highlights() {
+        use quantaterm_renderer::RendererCellAttrs;
+
+        let mut grid = TerminalGrid::with_scrollback(10, 3, 50);
+        let print_line = |grid: &mut TerminalGrid, text: &str| {
+            for c in text.chars() {
+                grid.print_char(c);
+            }
+            grid.carriage_return();
+            grid.linefeed();
+        };
+        print_line(&mut grid, "error one");
+        for i in 0..30 {
+            print_line(&mut grid, &format!("filler {i}"));
+        }
+        // Wraps onto a second row in the middle of the word
+        print_line(&mut grid, "xxxxxxxxerror1");
+        print_line(&mut grid, "done");
+
+        let cancel = SearchCancel::new();
+        let query = SearchQuery::literal("ERROR", false).unwrap();
+        let matches = grid.search(&query, &cancel).unwrap();
+        let span = |start: (usize, u16), end: (usize, u16)| SearchMatch {
+            start: SearchPoint::new(start.0, start.1),
+            end: SearchPoint::new(end.0, end.1),
+        };
+        assert_eq!(matches, [span((0, 0), (0, 4)), span((31, 8), (32, 2))]);
+        let exact = SearchQuery::literal("ERROR", true).unwrap();
+        assert!(grid.search(&exact, &cancel).unwrap().is_empty());
+
+        let top = grid.viewport_point(Position::new(0, 0));
+        assert_eq!(top, SearchPoint::new(32, 0));
+        let previous = grid
+            .search_next(&query, top, SearchDirection::Backward, &cancel)
+            .unwrap();
+        assert_eq!(previous, Some(matches[1]));
+        let next = |from| {
+            grid.search_next(&query, from, SearchDirection::Forward, &cancel)
+                .unwrap()
+        };
+        assert_eq!(next(matches[1].start), Some(matches[0]));
+        assert_eq!(next(matches[0].start), Some(matches[1]));
+
+        // The current match is scrolled into view and drawn distinctly
+        grid.scroll_to_match(&matches[0]);
+        assert_eq!(grid.get_viewport_text()[0].trim_end(), "error one");
+        grid.set_search_highlights(matches.clone(), Some(matches[0]));
+        let row = grid.renderer_row(0);
+        assert!(row[0].attrs.contains(RendererCellAttrs::SEARCH_CURRENT));
+        assert!(row[4].attrs.contains(RendererCellAttrs::SEARCH_MATCH));
+        assert!(!row[5].attrs.intersects(RendererCellAttrs::SEARCH_MATCH));
+
+        // Line numbers hold while old lines are dropped from history
+        for i in 0..40 {
+            print_line(&mut grid, &format!("more {i}"));
+        }
+        assert_eq!(grid.scrollback_len(), 50);
+        grid.scroll_to_match(&matches[1]);
+        let row = (31 - grid.viewport_point(Position::new(0, 0)).line) as u16;
+        assert_eq!(grid.get_viewport_text()[row as usize], "xxxxxxxxer");
+        assert!(grid.renderer_row(row)[8]
+            .attrs
+            .contains(RendererCellAttrs::SEARCH_MATCH));
+        let next_row = grid.renderer_row(row + 1);
+        assert!(next_row[2].attrs.contains(RendererCellAttrs::SEARCH_MATCH));
+        assert!(!next_row[3].attrs.contains(RendererCellAttrs::SEARCH_MATCH));
+
+        grid.clear_search_highlights();
+        assert!(grid.renderer_row(row)[8].attrs.is_empty());
+    }
 }
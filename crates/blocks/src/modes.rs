@@ -0,0 +1,144 @@
+//! Terminal mode state
+//!
+//! Tracks the ANSI and DEC private modes toggled by SM/RM and DECSET/DECRST so
+//! the grid, the input encoder and the renderer can consult a single source of
+//! truth.
+
+use quantaterm_core::{AnsiMode, DecMode, Mode};
+use std::collections::HashMap;
+
+/// Current values of the terminal modes that affect grid behaviour and input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalModes {
+    /// Wrap to the next line when printing past the last column (DECAWM)
+    pub autowrap: bool,
+    /// Cursor addressing is relative to the scroll region (DECOM)
+    pub origin: bool,
+    /// Cursor is visible (DECTCEM)
+    pub cursor_visible: bool,
+    /// Cursor blinks
+    pub cursor_blink: bool,
+    /// Cursor keys send application sequences (DECCKM)
+    pub application_cursor_keys: bool,
+    /// Keypad sends application sequences (DECKPAM/DECKPNM)
+    pub application_keypad: bool,
+    /// Pasted text is wrapped in `ESC [200~` / `ESC [201~`
+    pub bracketed_paste: bool,
+    /// Focus changes are reported as `ESC [I` / `ESC [O`
+    pub focus_reporting: bool,
+    /// An application is mid-update and rendering should be held back
+    pub synchronized_output: bool,
+    /// Printed characters shift the rest of the line right (IRM)
+    pub insert: bool,
+    /// Line feed also returns the cursor to the first column (LNM)
+    pub linefeed_newline: bool,
+    /// Values remembered by XTSAVE
+    saved: HashMap<Mode, bool>,
+}
+
+impl Default for TerminalModes {
+    fn default() -> Self {
+        Self {
+            autowrap: true,
+            origin: false,
+            cursor_visible: true,
+            cursor_blink: false,
+            application_cursor_keys: false,
+            application_keypad: false,
+            bracketed_paste: false,
+            focus_reporting: false,
+            synchronized_output: false,
+            insert: false,
+            linefeed_newline: false,
+            saved: HashMap::new(),
+        }
+    }
+}
+
+impl TerminalModes {
+    /// Create the power-on mode state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current value of a mode, or `None` if it is not tracked here
+    pub fn get(&self, mode: Mode) -> Option<bool> {
+        match mode {
+            Mode::Ansi(AnsiMode::Insert) => Some(self.insert),
+            Mode::Ansi(AnsiMode::LineFeedNewLine) => Some(self.linefeed_newline),
+            Mode::Dec(DecMode::CursorKeys) => Some(self.application_cursor_keys),
+            Mode::Dec(DecMode::Origin) => Some(self.origin),
+            Mode::Dec(DecMode::Autowrap) => Some(self.autowrap),
+            Mode::Dec(DecMode::CursorBlink) => Some(self.cursor_blink),
+            Mode::Dec(DecMode::ShowCursor) => Some(self.cursor_visible),
+            Mode::Dec(DecMode::FocusReporting) => Some(self.focus_reporting),
+            Mode::Dec(DecMode::BracketedPaste) => Some(self.bracketed_paste),
+            Mode::Dec(DecMode::SynchronizedOutput) => Some(self.synchronized_output),
+            _ => None,
+        }
+    }
+
+    /// Set a mode, returning `false` if it is not tracked here
+    pub fn set(&mut self, mode: Mode, enabled: bool) -> bool {
+        let flag = match mode {
+            Mode::Ansi(AnsiMode::Insert) => &mut self.insert,
+            Mode::Ansi(AnsiMode::LineFeedNewLine) => &mut self.linefeed_newline,
+            Mode::Dec(DecMode::CursorKeys) => &mut self.application_cursor_keys,
+            Mode::Dec(DecMode::Origin) => &mut self.origin,
+            Mode::Dec(DecMode::Autowrap) => &mut self.autowrap,
+            Mode::Dec(DecMode::CursorBlink) => &mut self.cursor_blink,
+            Mode::Dec(DecMode::ShowCursor) => &mut self.cursor_visible,
+            Mode::Dec(DecMode::FocusReporting) => &mut self.focus_reporting,
+            Mode::Dec(DecMode::BracketedPaste) => &mut self.bracketed_paste,
+            Mode::Dec(DecMode::SynchronizedOutput) => &mut self.synchronized_output,
+            _ => return false,
+        };
+        *flag = enabled;
+        true
+    }
+
+    /// Remember the current value of a mode (XTSAVE)
+    pub fn save(&mut self, mode: Mode) {
+        if let Some(value) = self.get(mode) {
+            self.saved.insert(mode, value);
+        }
+    }
+
+    /// Value remembered for a mode by `save`, if any (XTRESTORE)
+    pub fn saved(&self, mode: Mode) -> Option<bool> {
+        self.saved.get(&mode).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let modes = TerminalModes::new();
+        assert!(modes.autowrap);
+        assert!(modes.cursor_visible);
+        assert!(!modes.origin);
+        assert!(!modes.bracketed_paste);
+        assert_eq!(modes.get(Mode::Dec(DecMode::Unknown(9999))), None);
+    }
+
+    #[test]
+    fn test_set_and_save() {
+        let mut modes = TerminalModes::new();
+        let paste = Mode::Dec(DecMode::BracketedPaste);
+
+        assert!(modes.set(paste, true));
+        assert_eq!(modes.get(paste), Some(true));
+
+        modes.save(paste);
+        modes.set(paste, false);
+        assert_eq!(modes.saved(paste), Some(true));
+
+        // Untracked modes are ignored
+        assert!(!modes.set(Mode::Ansi(AnsiMode::Unknown(2)), true));
+        modes.save(Mode::Ansi(AnsiMode::Unknown(2)));
+        assert_eq!(modes.saved(Mode::Ansi(AnsiMode::Unknown(2))), None);
+    }
+}
@@ -0,0 +1,173 @@
+//! Indexed colors and the palette they resolve through
+//!
+//! SGR 30–37, 90–97 and `38;5;n` select palette entries rather than fixed
+//! values, so cells store the index as a [`CellColor`] and the grid resolves
+//! it through its live [`Palette`] when rendering. Changing an entry (OSC 4)
+//! or switching theme therefore recolors output that is already on screen,
+//! scrollback included.
+
+use crate::Color;
+
+/// Color stored in a cell, resolved through the palette at render time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CellColor {
+    /// The palette's default foreground or background
+    #[default]
+    Default,
+    /// One of the 256 palette entries
+    Indexed(u8),
+    /// A direct color (SGR `38;2;r;g;b`), unaffected by the palette
+    Rgb(Color),
+}
+
+impl From<Color> for CellColor {
+    fn from(color: Color) -> Self {
+        CellColor::Rgb(color)
+    }
+}
+
+/// The 256 indexed colors plus the default foreground and background
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    /// Entries 0–255: 16 named colors, the 6x6x6 cube and the gray ramp
+    colors: [Color; 256],
+    /// Color of `CellColor::Default` text
+    foreground: Color,
+    /// Color of `CellColor::Default` backgrounds
+    background: Color,
+}
+
+impl Palette {
+    /// Create the built-in palette
+    pub fn new() -> Self {
+        Self {
+            colors: std::array::from_fn(|index| Self::builtin(index as u8)),
+            foreground: Color::DEFAULT_FG,
+            background: Color::DEFAULT_BG,
+        }
+    }
+
+    /// Built-in value of palette entry `index`
+    pub fn builtin(index: u8) -> Color {
+        match index {
+            0 => Color::rgb(0, 0, 0),
+            1 => Color::rgb(128, 0, 0),
+            2 => Color::rgb(0, 128, 0),
+            3 => Color::rgb(128, 128, 0),
+            4 => Color::rgb(0, 0, 128),
+            5 => Color::rgb(128, 0, 128),
+            6 => Color::rgb(0, 128, 128),
+            7 => Color::rgb(192, 192, 192),
+            8 => Color::rgb(128, 128, 128),
+            9 => Color::rgb(255, 0, 0),
+            10 => Color::rgb(0, 255, 0),
+            11 => Color::rgb(255, 255, 0),
+            12 => Color::rgb(0, 0, 255),
+            13 => Color::rgb(255, 0, 255),
+            14 => Color::rgb(0, 255, 255),
+            15 => Color::rgb(255, 255, 255),
+
+            // 6x6x6 color cube
+            16..=231 => {
+                let index = index - 16;
+                let level = |step: u8| step * 51;
+                Color::rgb(level(index / 36), level(index % 36 / 6), level(index % 6))
+            }
+
+            // Grayscale ramp
+            232..=255 => {
+                let gray = 8 + (index - 232) * 10;
+                Color::rgb(gray, gray, gray)
+            }
+        }
+    }
+
+    /// Current value of entry `index`
+    pub fn get(&self, index: u8) -> Color {
+        self.colors[index as usize]
+    }
+
+    /// Change entry `index`
+    pub fn set(&mut self, index: u8, color: Color) {
+        self.colors[index as usize] = color;
+    }
+
+    /// Default foreground color
+    pub fn foreground(&self) -> Color {
+        self.foreground
+    }
+
+    /// Change the default foreground color
+    pub fn set_foreground(&mut self, color: Color) {
+        self.foreground = color;
+    }
+
+    /// Default background color
+    pub fn background(&self) -> Color {
+        self.background
+    }
+
+    /// Change the default background color
+    pub fn set_background(&mut self, color: Color) {
+        self.background = color;
+    }
+
+    /// Resolve a foreground color
+    pub fn resolve_fg(&self, color: CellColor) -> Color {
+        self.resolve(color, self.foreground)
+    }
+
+    /// Resolve a background color
+    pub fn resolve_bg(&self, color: CellColor) -> Color {
+        self.resolve(color, self.background)
+    }
+
+    fn resolve(&self, color: CellColor, default: Color) -> Color {
+        match color {
+            CellColor::Default => default,
+            CellColor::Indexed(index) => self.get(index),
+            CellColor::Rgb(color) => color,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_palette() {
+        let palette = Palette::new();
+        assert_eq!(palette.get(1), Color::rgb(128, 0, 0));
+        assert_eq!(palette.get(9), Color::rgb(255, 0, 0));
+        assert_eq!(palette.get(16), Color::rgb(0, 0, 0));
+        assert_eq!(palette.get(196), Color::rgb(255, 0, 0));
+        assert_eq!(palette.get(231), Color::rgb(255, 255, 255));
+        assert_eq!(palette.get(232), Color::rgb(8, 8, 8));
+        assert_eq!(palette.get(255), Color::rgb(238, 238, 238));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut palette = Palette::new();
+        palette.set(4, Color::rgb(1, 2, 3));
+        palette.set_background(Color::rgb(9, 9, 9));
+
+        assert_eq!(palette.resolve_fg(CellColor::Default), Color::DEFAULT_FG);
+        assert_eq!(palette.resolve_bg(CellColor::Default), Color::rgb(9, 9, 9));
+        assert_eq!(
+            palette.resolve_fg(CellColor::Indexed(4)),
+            Color::rgb(1, 2, 3)
+        );
+        assert_eq!(
+            palette.resolve_bg(CellColor::Rgb(Color::rgb(7, 7, 7))),
+            Color::rgb(7, 7, 7)
+        );
+    }
+}
@@ -0,0 +1,505 @@
+//! Searching scrollback and the screen
+//!
+//! Rows joined by soft wraps are searched as one logical line, so a match
+//! may start on one row and end on the next. Matches never span a hard line
+//! break. Positions are [`SearchPoint`]s: a buffer line number and a column.
+//! Line numbers count from the first line the primary screen ever scrolled
+//! into history, so they stay valid while output arrives and history is
+//! trimmed; lines dropped from history simply cannot be shown any more.
+//!
+//! History is unpacked a page at a time while searching, and a search can
+//! be cancelled from another thread through a [`SearchCancel`] token.
+
+use crate::history::{History, PAGE_LINES};
+use crate::{is_placeholder, is_wrapped, push_cell_text, Cell, CellFlags, CellRow, GraphemeTable};
+use regex::{Regex, RegexBuilder};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Errors from building or running a search
+#[derive(Debug, Error)]
+pub enum SearchError {
+    /// The regular expression does not parse or is too large
+    #[error("invalid search pattern: {0}")]
+    InvalidPattern(#[from] regex::Error),
+    /// The search was cancelled before it finished
+    #[error("search cancelled")]
+    Cancelled,
+}
+
+/// What to search for
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    regex: Regex,
+}
+
+impl SearchQuery {
+    /// Search for `text` as typed
+    pub fn literal(text: &str, case_sensitive: bool) -> Result<Self, SearchError> {
+        Self::regex(&regex::escape(text), case_sensitive)
+    }
+
+    /// Search for a regular expression
+    pub fn regex(pattern: &str, case_sensitive: bool) -> Result<Self, SearchError> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(!case_sensitive)
+            .build()?;
+        Ok(Self { regex })
+    }
+}
+
+/// Flag that stops a search in progress
+///
+/// Clones share the flag, so one can be handed to whatever may cancel the
+/// search while another is passed to it.
+#[derive(Debug, Clone, Default)]
+pub struct SearchCancel(Arc<AtomicBool>);
+
+impl SearchCancel {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop searches using this token
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`cancel`](Self::cancel) was called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn check(&self) -> Result<(), SearchError> {
+        if self.is_cancelled() {
+            Err(SearchError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Direction to look for the next match in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchDirection {
+    /// Towards the bottom of the buffer
+    Forward,
+    /// Towards the top of history
+    Backward,
+}
+
+/// A cell in the buffer searched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SearchPoint {
+    /// Buffer line number; see the module documentation
+    pub line: usize,
+    /// Column
+    pub col: u16,
+}
+
+impl SearchPoint {
+    /// Create a point
+    pub fn new(line: usize, col: u16) -> Self {
+        Self { line, col }
+    }
+}
+
+/// Cells covered by one match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SearchMatch {
+    /// First cell
+    pub start: SearchPoint,
+    /// Last cell, inclusive; the second half of a double-width character
+    /// when the match ends with one
+    pub end: SearchPoint,
+}
+
+impl SearchMatch {
+    /// Columns covered on buffer line `line` of a grid `cols` wide, if any
+    pub fn columns_on(&self, line: usize, cols: u16) -> Option<std::ops::Range<u16>> {
+        if line < self.start.line || line > self.end.line {
+            return None;
+        }
+        let start = if line == self.start.line {
+            self.start.col
+        } else {
+            0
+        };
+        let end = if line == self.end.line {
+            self.end.col.saturating_add(1)
+        } else {
+            cols
+        };
+        Some(start..end.min(cols))
+    }
+}
+
+/// Rows of a buffer, reading history a page at a time
+pub(crate) struct Rows<'a> {
+    history: &'a History,
+    /// History lines searched; none on the alternate screen
+    history_len: usize,
+    screen: &'a VecDeque<CellRow>,
+    /// Line number of the first row
+    base: usize,
+    /// Index of the first history line in `chunk`
+    chunk_start: usize,
+    /// Unpacked history lines
+    chunk: Vec<CellRow>,
+}
+
+impl<'a> Rows<'a> {
+    pub(crate) fn new(
+        history: &'a History,
+        history_len: usize,
+        screen: &'a VecDeque<CellRow>,
+        base: usize,
+    ) -> Self {
+        Self {
+            history,
+            history_len,
+            screen,
+            base,
+            chunk_start: 0,
+            chunk: Vec::new(),
+        }
+    }
+
+    /// Rows in the buffer
+    fn len(&self) -> usize {
+        self.history_len + self.screen.len()
+    }
+
+    /// Row `index`, 0 being the oldest
+    fn get(&mut self, index: usize) -> &CellRow {
+        if index >= self.history_len {
+            return &self.screen[index - self.history_len];
+        }
+        if !(self.chunk_start..self.chunk_start + self.chunk.len()).contains(&index) {
+            self.chunk_start = index - index % PAGE_LINES;
+            self.chunk = self
+                .history
+                .lines(self.chunk_start..(self.chunk_start + PAGE_LINES).min(self.history_len));
+        }
+        &self.chunk[index - self.chunk_start]
+    }
+
+    /// First row of the logical line holding row `index`
+    fn line_start(&mut self, index: usize) -> usize {
+        let mut start = index;
+        while start > 0 && is_wrapped(self.get(start - 1)) {
+            start -= 1;
+        }
+        start
+    }
+
+    /// Index of the row showing buffer line `line`, clamped to the buffer
+    fn index_of(&self, line: usize) -> usize {
+        line.saturating_sub(self.base)
+            .min(self.len().saturating_sub(1))
+    }
+}
+
+/// A cell's place in the text of a logical line
+#[derive(Debug, Clone, Copy)]
+struct TextCell {
+    /// Byte offset of the cell's text
+    offset: usize,
+    /// Row index
+    row: usize,
+    col: u16,
+    /// Columns covered
+    width: u16,
+}
+
+/// Soft-wrapped rows joined into one line of text
+struct LogicalLine {
+    text: String,
+    cells: Vec<TextCell>,
+    /// Row after the last one in the line
+    end: usize,
+}
+
+impl LogicalLine {
+    /// Read the logical line starting at row `start`
+    fn read(rows: &mut Rows<'_>, graphemes: &GraphemeTable, start: usize) -> Self {
+        let mut line = Self {
+            text: String::new(),
+            cells: Vec::new(),
+            end: start,
+        };
+        while line.end < rows.len() {
+            let index = line.end;
+            let row = rows.get(index);
+            let wrapped = is_wrapped(row);
+            // Trailing blanks end the line, they are not part of it
+            let len = if wrapped {
+                row.len()
+            } else {
+                row.iter()
+                    .rposition(|cell| !is_blank(cell))
+                    .map_or(0, |i| i + 1)
+            };
+            for (col, cell) in row[..len].iter().enumerate() {
+                if is_placeholder(cell) {
+                    continue;
+                }
+                line.cells.push(TextCell {
+                    offset: line.text.len(),
+                    row: index,
+                    col: col as u16,
+                    width: if cell.flags.contains(CellFlags::WIDE) {
+                        2
+                    } else {
+                        1
+                    },
+                });
+                push_cell_text(graphemes, cell, &mut line.text);
+            }
+            line.end += 1;
+            if !wrapped {
+                break;
+            }
+        }
+        line
+    }
+
+    /// Matches of `query` in the line, in order
+    fn matches<'a>(
+        &'a self,
+        query: &'a SearchQuery,
+        base: usize,
+    ) -> impl DoubleEndedIterator<Item = SearchMatch> + 'a {
+        let found: Vec<_> = query
+            .regex
+            .find_iter(&self.text)
+            .filter(|found| !found.is_empty())
+            .collect();
+        found.into_iter().map(move |found| {
+            let first = self.cell_at(found.start());
+            let last = self.cell_at(found.end() - 1);
+            SearchMatch {
+                start: SearchPoint::new(base + first.row, first.col),
+                end: SearchPoint::new(base + last.row, last.col + last.width - 1),
+            }
+        })
+    }
+
+    /// Cell whose text holds byte `offset`
+    fn cell_at(&self, offset: usize) -> TextCell {
+        let index = self.cells.partition_point(|cell| cell.offset <= offset);
+        self.cells[index - 1]
+    }
+}
+
+/// Whether a cell is a plain space
+fn is_blank(cell: &Cell) -> bool {
+    cell.glyph_id == b' ' as u32 && cell.grapheme.is_none()
+}
+
+/// Every match in `rows`, oldest first
+pub(crate) fn find_all(
+    rows: &mut Rows<'_>,
+    graphemes: &GraphemeTable,
+    query: &SearchQuery,
+    cancel: &SearchCancel,
+) -> Result<Vec<SearchMatch>, SearchError> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start < rows.len() {
+        cancel.check()?;
+        let line = LogicalLine::read(rows, graphemes, start);
+        matches.extend(line.matches(query, rows.base));
+        start = line.end;
+    }
+    Ok(matches)
+}
+
+/// The nearest match strictly after or before `from`, wrapping around the
+/// buffer once
+pub(crate) fn find_next(
+    rows: &mut Rows<'_>,
+    graphemes: &GraphemeTable,
+    query: &SearchQuery,
+    from: SearchPoint,
+    direction: SearchDirection,
+    cancel: &SearchCancel,
+) -> Result<Option<SearchMatch>, SearchError> {
+    if rows.len() == 0 {
+        return Ok(None);
+    }
+    let first = rows.line_start(rows.index_of(from.line));
+    let mut start = first;
+    let mut moved = false;
+    loop {
+        cancel.check()?;
+        let line = LogicalLine::read(rows, graphemes, start);
+        let mut matches = line.matches(query, rows.base);
+        // The line `from` is on is searched past `from` first, and up to it
+        // once the search has gone all the way round
+        let found = match (direction, start == first, moved) {
+            (SearchDirection::Forward, true, false) => matches.find(|m| m.start > from),
+            (SearchDirection::Forward, true, true) => matches.find(|m| m.start <= from),
+            (SearchDirection::Forward, false, _) => matches.next(),
+            (SearchDirection::Backward, true, false) => matches.rfind(|m| m.start < from),
+            (SearchDirection::Backward, true, true) => matches.rfind(|m| m.start >= from),
+            (SearchDirection::Backward, false, _) => matches.next_back(),
+        };
+        if found.is_some() || (moved && start == first) {
+            return Ok(found);
+        }
+
+        start = match direction {
+            SearchDirection::Forward if line.end < rows.len() => line.end,
+            SearchDirection::Forward => 0,
+            SearchDirection::Backward if start > 0 => rows.line_start(start - 1),
+            SearchDirection::Backward => rows.line_start(rows.len() - 1),
+        };
+        moved = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(text: &str, cols: usize, wrapped: bool) -> CellRow {
+        let mut row: CellRow = text.chars().map(|c| Cell::new(c as u32)).collect();
+        row.resize(cols, Cell::empty());
+        if wrapped {
+            row[cols - 1].flags |= CellFlags::WRAPPED;
+        }
+        row
+    }
+
+    fn find(rows: &mut Rows<'_>, query: &SearchQuery) -> Vec<SearchMatch> {
+        find_all(rows, &GraphemeTable::new(), query, &SearchCancel::new()).unwrap()
+    }
+
+    fn span(start: (usize, u16), end: (usize, u16)) -> SearchMatch {
+        SearchMatch {
+            start: SearchPoint::new(start.0, start.1),
+            end: SearchPoint::new(end.0, end.1),
+        }
+    }
+
+    #[test]
+    fn test_matches_across_wrapped_rows() {
+        let history = History::new(100, usize::MAX, false);
+        let screen: VecDeque<CellRow> = [
+            row("say hel", 7, true),
+            row("lo world", 10, false),
+            row("hello", 7, false),
+        ]
+        .into();
+        let mut rows = Rows::new(&history, 0, &screen, 10);
+
+        let query = SearchQuery::literal("HELLO", false).unwrap();
+        assert_eq!(
+            find(&mut rows, &query),
+            [span((10, 4), (11, 1)), span((12, 0), (12, 4))]
+        );
+        assert!(find(&mut rows, &SearchQuery::literal("HELLO", true).unwrap()).is_empty());
+        // Hard line breaks are not joined, and trailing blanks are not text
+        assert!(find(
+            &mut rows,
+            &SearchQuery::regex(r"world\s*hello", false).unwrap()
+        )
+        .is_empty());
+        assert!(find(&mut rows, &SearchQuery::regex(r"world\s", false).unwrap()).is_empty());
+        assert!(matches!(
+            SearchQuery::regex("(", false),
+            Err(SearchError::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn test_wide_characters_cover_both_cells() {
+        let history = History::new(100, usize::MAX, false);
+        let mut wide = row("a中b", 6, false);
+        wide[1].flags = CellFlags::WIDE;
+        wide.insert(
+            2,
+            Cell {
+                flags: CellFlags::WIDE_SPACER,
+                ..Cell::empty()
+            },
+        );
+        wide.truncate(6);
+        let screen: VecDeque<CellRow> = [wide].into();
+        let mut rows = Rows::new(&history, 0, &screen, 0);
+
+        let query = SearchQuery::literal("中", true).unwrap();
+        assert_eq!(find(&mut rows, &query), [span((0, 1), (0, 2))]);
+        let query = SearchQuery::literal("中b", true).unwrap();
+        assert_eq!(find(&mut rows, &query), [span((0, 1), (0, 3))]);
+    }
+
+    #[test]
+    fn test_next_and_previous_wrap_around() {
+        let mut history = History::new(1000, usize::MAX, false);
+        for i in 0..PAGE_LINES + 10 {
+            let text = if i % 100 == 0 { "needle" } else { "hay" };
+            history.push(&row(text, 10, false));
+        }
+        let screen: VecDeque<CellRow> = [row("needle", 10, false), row("", 10, false)].into();
+        let mut rows = Rows::new(&history, history.len(), &screen, 0);
+        let query = SearchQuery::literal("needle", true).unwrap();
+        let cancel = SearchCancel::new();
+        let graphemes = GraphemeTable::new();
+        let mut next = |from: SearchPoint, direction| {
+            find_next(&mut rows, &graphemes, &query, from, direction, &cancel)
+                .unwrap()
+                .map(|found| found.start)
+        };
+
+        let end = SearchPoint::new(PAGE_LINES + 11, 0);
+        assert_eq!(next(SearchPoint::new(0, 0), SearchDirection::Forward), {
+            Some(SearchPoint::new(100, 0))
+        });
+        assert_eq!(next(SearchPoint::new(200, 0), SearchDirection::Forward), {
+            Some(SearchPoint::new(PAGE_LINES + 10, 0))
+        });
+        assert_eq!(
+            next(end, SearchDirection::Forward),
+            Some(SearchPoint::new(0, 0))
+        );
+        assert_eq!(next(SearchPoint::new(100, 3), SearchDirection::Backward), {
+            Some(SearchPoint::new(100, 0))
+        });
+        assert_eq!(next(SearchPoint::new(0, 0), SearchDirection::Backward), {
+            Some(SearchPoint::new(PAGE_LINES + 10, 0))
+        });
+        // A lone match is found again after going all the way round
+        let mut rows = Rows::new(&history, 0, &screen, 0);
+        assert_eq!(
+            find_next(
+                &mut rows,
+                &graphemes,
+                &query,
+                SearchPoint::new(0, 0),
+                SearchDirection::Forward,
+                &cancel
+            )
+            .unwrap(),
+            Some(span((0, 0), (0, 5)))
+        );
+    }
+
+    #[test]
+    fn test_cancelled_search() {
+        let history = History::new(100, usize::MAX, false);
+        let screen: VecDeque<CellRow> = [row("text", 4, false)].into();
+        let mut rows = Rows::new(&history, 0, &screen, 0);
+        let cancel = SearchCancel::new();
+        cancel.clone().cancel();
+        let query = SearchQuery::literal("text", true).unwrap();
+        assert!(matches!(
+            find_all(&mut rows, &GraphemeTable::new(), &query, &cancel),
+            Err(SearchError::Cancelled)
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+//! Tab stop storage
+//!
+//! Tab stops start out every 8 columns. Applications move them with HTS
+//! (`ESC H`) and TBC (`CSI g`, `CSI 3 g`); tools such as `tabs` and `column`
+//! rely on that to line up their output. Stops are kept one bit per column.
+
+/// Columns with a tab stop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabStops {
+    /// One bit per column, least significant bit first
+    bits: Vec<u64>,
+    /// Number of columns covered
+    cols: u16,
+}
+
+impl TabStops {
+    /// Interval between the tab stops set at power-on
+    pub const DEFAULT_INTERVAL: u16 = 8;
+
+    /// Create the power-on stops for a screen `cols` wide: every 8 columns
+    pub fn new(cols: u16) -> Self {
+        let mut stops = Self {
+            bits: vec![0; (cols as usize).div_ceil(64)],
+            cols,
+        };
+        for col in (Self::DEFAULT_INTERVAL..cols).step_by(Self::DEFAULT_INTERVAL as usize) {
+            stops.set(col);
+        }
+        stops
+    }
+
+    /// Number of columns covered
+    pub fn cols(&self) -> u16 {
+        self.cols
+    }
+
+    /// Whether `col` has a tab stop
+    pub fn is_set(&self, col: u16) -> bool {
+        col < self.cols && self.bits[col as usize / 64] & (1 << (col % 64)) != 0
+    }
+
+    /// Set a tab stop at `col` (HTS)
+    pub fn set(&mut self, col: u16) {
+        if col < self.cols {
+            self.bits[col as usize / 64] |= 1 << (col % 64);
+        }
+    }
+
+    /// Remove the tab stop at `col` (`CSI 0 g`)
+    pub fn clear(&mut self, col: u16) {
+        if col < self.cols {
+            self.bits[col as usize / 64] &= !(1 << (col % 64));
+        }
+    }
+
+    /// Remove every tab stop (`CSI 3 g`)
+    pub fn clear_all(&mut self) {
+        self.bits.fill(0);
+    }
+
+    /// First tab stop after `col`
+    pub fn next(&self, col: u16) -> Option<u16> {
+        let start = col.checked_add(1)?;
+        if start >= self.cols {
+            return None;
+        }
+        let mut word = start as usize / 64;
+        let mut bits = self.bits[word] & (u64::MAX << (start % 64));
+        loop {
+            if bits != 0 {
+                return Some((word * 64) as u16 + bits.trailing_zeros() as u16);
+            }
+            word += 1;
+            bits = *self.bits.get(word)?;
+        }
+    }
+
+    /// Last tab stop before `col`
+    pub fn prev(&self, col: u16) -> Option<u16> {
+        let end = col.min(self.cols);
+        if end == 0 {
+            return None;
+        }
+        let mut word = (end as usize - 1) / 64;
+        let mut bits = self.bits[word] & (u64::MAX >> (63 - (end - 1) % 64));
+        loop {
+            if bits != 0 {
+                return Some((word * 64) as u16 + 63 - bits.leading_zeros() as u16);
+            }
+            word = word.checked_sub(1)?;
+            bits = self.bits[word];
+        }
+    }
+}
+
+impl Default for TabStops {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_stops() {
+        let stops = TabStops::new(30);
+        let set: Vec<u16> = (0..30).filter(|&col| stops.is_set(col)).collect();
+        assert_eq!(set, vec![8, 16, 24]);
+        assert_eq!(stops.next(0), Some(8));
+        assert_eq!(stops.next(8), Some(16));
+        assert_eq!(stops.next(24), None);
+        assert_eq!(stops.prev(16), Some(8));
+        assert_eq!(stops.prev(8), None);
+        assert_eq!(stops.prev(100), Some(24));
+    }
+
+    #[test]
+    fn test_set_and_clear_across_words() {
+        let mut stops = TabStops::new(200);
+        stops.clear_all();
+        assert_eq!(stops.next(0), None);
+        assert_eq!(stops.prev(200), None);
+
+        stops.set(3);
+        stops.set(130);
+        stops.set(199);
+        stops.set(200);
+        assert_eq!(stops.next(3), Some(130));
+        assert_eq!(stops.next(130), Some(199));
+        assert_eq!(stops.prev(199), Some(130));
+        assert_eq!(stops.prev(130), Some(3));
+
+        stops.clear(130);
+        assert!(!stops.is_set(130));
+        assert_eq!(stops.next(3), Some(199));
+        assert_eq!(stops.prev(199), Some(3));
+    }
+}
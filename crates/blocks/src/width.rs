@@ -0,0 +1,66 @@
+//! Character cell widths
+//!
+//! Terminals lay text out on a fixed grid, so every printed character needs a
+//! column count: one for most scripts, two for CJK ideographs, Hangul and
+//! emoji. Widths come from the Unicode East Asian Width tables, with the
+//! Ambiguous class resolved by [`AmbiguousWidth`].
+
+use quantaterm_core::AmbiguousWidth;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Number of columns a printed character occupies: 0, 1 or 2
+///
+/// Control characters never reach the grid, so they count as zero.
+pub fn char_width(c: char, ambiguous: AmbiguousWidth) -> usize {
+    let width = match ambiguous {
+        AmbiguousWidth::Narrow => c.width(),
+        AmbiguousWidth::Wide => c.width_cjk(),
+    };
+    width.unwrap_or(0).min(2)
+}
+
+/// Number of columns a grapheme cluster occupies: 1 or 2
+///
+/// Emoji presentation and ZWJ sequences are two columns wide even when
+/// their base character alone is narrow.
+pub fn cluster_width(cluster: &str, ambiguous: AmbiguousWidth) -> usize {
+    let width = match ambiguous {
+        AmbiguousWidth::Narrow => cluster.width(),
+        AmbiguousWidth::Wide => cluster.width_cjk(),
+    };
+    width.clamp(1, 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_widths() {
+        assert_eq!(char_width('a', AmbiguousWidth::Narrow), 1);
+        assert_eq!(char_width('中', AmbiguousWidth::Narrow), 2);
+        assert_eq!(char_width('한', AmbiguousWidth::Narrow), 2);
+        assert_eq!(char_width('🦀', AmbiguousWidth::Narrow), 2);
+        assert_eq!(char_width('\u{301}', AmbiguousWidth::Narrow), 0);
+    }
+
+    #[test]
+    fn test_cluster_widths() {
+        assert_eq!(cluster_width("e\u{301}", AmbiguousWidth::Narrow), 1);
+        assert_eq!(cluster_width("❤\u{FE0F}", AmbiguousWidth::Narrow), 2);
+        assert_eq!(
+            cluster_width("👨\u{200D}👩\u{200D}👧", AmbiguousWidth::Narrow),
+            2
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_width() {
+        assert_eq!(char_width('±', AmbiguousWidth::Narrow), 1);
+        assert_eq!(char_width('±', AmbiguousWidth::Wide), 2);
+        assert_eq!(char_width('§', AmbiguousWidth::Wide), 2);
+        // Unambiguous characters are unaffected
+        assert_eq!(char_width('a', AmbiguousWidth::Wide), 1);
+        assert_eq!(char_width('中', AmbiguousWidth::Wide), 2);
+    }
+}
@@ -3,40 +3,108 @@
 //! Main application logic for handling window creation, events, and rendering.
 
 use anyhow::{Context, Result};
-use quantaterm_pty::{Pty, PtyEvent};
+use quantaterm_config::Config;
+use quantaterm_pty::{Pty, PtyEvent, Terminal};
 use quantaterm_renderer::Renderer;
+use quantaterm_telemetry::Telemetry;
 use std::sync::Arc;
-use tracing::{debug, error, info, warn};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, trace, warn};
 use winit::{
     application::ApplicationHandler,
-    event::{ElementState, KeyEvent, WindowEvent},
-    event_loop::ActiveEventLoop,
-    keyboard::{KeyCode, PhysicalKey},
+    event::{ElementState, KeyEvent, StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     window::{Window, WindowAttributes, WindowId},
 };
 
+/// Window title shown until the shell sets one
+const WINDOW_TITLE: &str = "QuantaTerm";
+/// Terminal width used until the window reports its real size
+const DEFAULT_COLS: u16 = 80;
+/// Terminal height used until the window reports its real size
+const DEFAULT_ROWS: u16 = 24;
+/// How often accumulated PTY statistics are reported to telemetry
+const STATS_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Main QuantaTerm application
 pub struct QuantaTermApp {
     window: Option<Arc<Window>>,
     renderer: Option<Renderer>,
     pty: Option<Pty>,
+    /// Parser and grid that shell output is applied to
+    terminal: Terminal,
+    /// Collector for runtime statistics
+    telemetry: Telemetry,
+    /// When statistics were last reported
+    stats_reported: Instant,
+    /// Modifier keys currently held
+    modifiers: ModifiersState,
 }
 
 impl QuantaTermApp {
-    /// Create a new QuantaTerm application
+    /// Create a new QuantaTerm application with the user's configuration
     pub async fn new() -> Result<Self> {
+        Self::with_config(Config::load_or_default()).await
+    }
+
+    /// Create a new QuantaTerm application with the given configuration
+    pub async fn with_config(config: Config) -> Result<Self> {
         info!("Initializing QuantaTerm application");
 
         // Initialize PTY
         let pty = Pty::new();
 
+        let mut terminal = Terminal::new(DEFAULT_COLS, DEFAULT_ROWS);
+        let grid = terminal.grid_mut();
+        grid.set_scrollback_limits(
+            config.terminal.max_scrollback,
+            config.terminal.scrollback_bytes,
+        );
+        grid.set_scrollback_compression(config.terminal.compress_scrollback);
+        grid.set_default_cursor_style(config.renderer.cursor_style);
+
         Ok(Self {
             window: None,
             renderer: None,
             pty: Some(pty),
+            terminal,
+            telemetry: Telemetry::new(),
+            stats_reported: Instant::now(),
+            modifiers: ModifiersState::empty(),
         })
     }
 
+    /// Report statistics accumulated since the last report, at most once per
+    /// [`STATS_INTERVAL`]
+    fn report_stats(&mut self, now: Instant) {
+        if now.duration_since(self.stats_reported) < STATS_INTERVAL {
+            return;
+        }
+        self.stats_reported = now;
+
+        if let Some(ref pty) = self.pty {
+            pty.take_reader_stats().record(&mut self.telemetry);
+        }
+        self.terminal
+            .grid()
+            .scrollback_stats()
+            .record(&mut self.telemetry);
+    }
+
+    /// Write a line of text into the terminal as if the shell had printed it
+    fn show_message(&mut self, text: &str) {
+        let line = format!("{}\r\n", text);
+        self.terminal.process(line.as_bytes(), &mut Vec::new());
+    }
+
+    /// Push the visible grid contents to the renderer
+    fn update_renderer(&mut self) {
+        if let Some(ref mut renderer) = self.renderer {
+            self.terminal.grid_mut().update_renderer(renderer);
+        }
+    }
+
     /// Handle keyboard input events
     fn handle_keyboard_input(&mut self, event: KeyEvent, event_loop: &ActiveEventLoop) {
         debug!("Keyboard input: {:?}", event);
@@ -52,6 +120,11 @@ impl QuantaTermApp {
                     }
                     event_loop.exit();
                 }
+                PhysicalKey::Code(KeyCode::KeyV)
+                    if self.modifiers.control_key() && self.modifiers.shift_key() =>
+                {
+                    self.paste_from_clipboard();
+                }
                 PhysicalKey::Code(keycode) => {
                     debug!("Key pressed: {:?}", keycode);
 
@@ -69,17 +142,73 @@ impl QuantaTermApp {
         }
     }
 
+    /// Send the clipboard's text to the shell as a paste
+    fn paste_from_clipboard(&self) {
+        let text = match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("Failed to read clipboard: {}", e);
+                return;
+            }
+        };
+        if let Some(ref pty) = self.pty {
+            if let Err(e) = pty.write_data(&self.paste_to_bytes(&text)) {
+                warn!("Failed to write to PTY: {}", e);
+            }
+        }
+    }
+
+    /// Encode pasted text for the shell
+    ///
+    /// Line breaks are sent as carriage returns, like the Enter key. With
+    /// bracketed paste on, the text is wrapped in `ESC [200~` / `ESC [201~`
+    /// and any end marker inside it is removed, so a paste cannot end the
+    /// bracket early and have the rest run as typed input.
+    fn paste_to_bytes(&self, text: &str) -> Vec<u8> {
+        let text = text.replace("\r\n", "\r").replace('\n', "\r");
+        if !self.terminal.grid().modes().bracketed_paste {
+            return text.into_bytes();
+        }
+        let text = text.replace("\x1b[201~", "");
+        let mut data = Vec::with_capacity(text.len() + 12);
+        data.extend_from_slice(b"\x1b[200~");
+        data.extend_from_slice(text.as_bytes());
+        data.extend_from_slice(b"\x1b[201~");
+        data
+    }
+
+    /// Report a focus change to the shell, if the application asked for it
+    fn focus_to_bytes(&self, focused: bool) -> Option<&'static [u8]> {
+        if !self.terminal.grid().modes().focus_reporting {
+            return None;
+        }
+        Some(if focused { b"\x1b[I" } else { b"\x1b[O" })
+    }
+
     /// Convert a keycode to bytes to send to the shell
+    ///
+    /// Cursor keys send `ESC O` sequences instead of `ESC [` ones while the
+    /// application has DECCKM set.
     fn keycode_to_bytes(&self, keycode: KeyCode) -> Option<Vec<u8>> {
+        let cursor_key = |final_byte: u8| {
+            let prefix = if self.terminal.grid().modes().application_cursor_keys {
+                b'O'
+            } else {
+                b'['
+            };
+            Some(vec![0x1b, prefix, final_byte])
+        };
         match keycode {
             KeyCode::Enter | KeyCode::NumpadEnter => Some(b"\r".to_vec()),
             KeyCode::Backspace => Some(b"\x08".to_vec()),
             KeyCode::Tab => Some(b"\t".to_vec()),
             KeyCode::Space => Some(b" ".to_vec()),
-            KeyCode::ArrowUp => Some(b"\x1b[A".to_vec()),
-            KeyCode::ArrowDown => Some(b"\x1b[B".to_vec()),
-            KeyCode::ArrowRight => Some(b"\x1b[C".to_vec()),
-            KeyCode::ArrowLeft => Some(b"\x1b[D".to_vec()),
+            KeyCode::ArrowUp => cursor_key(b'A'),
+            KeyCode::ArrowDown => cursor_key(b'B'),
+            KeyCode::ArrowRight => cursor_key(b'C'),
+            KeyCode::ArrowLeft => cursor_key(b'D'),
+            KeyCode::Home => cursor_key(b'H'),
+            KeyCode::End => cursor_key(b'F'),
             // Letter keys (simplified - would need more complete mapping)
             KeyCode::KeyA => Some(b"a".to_vec()),
             KeyCode::KeyB => Some(b"b".to_vec()),
@@ -128,7 +257,7 @@ impl QuantaTermApp {
     /// Create the main window
     async fn create_window(&mut self, event_loop: &ActiveEventLoop) -> Result<()> {
         let window_attributes = WindowAttributes::default()
-            .with_title("QuantaTerm")
+            .with_title(WINDOW_TITLE)
             .with_inner_size(winit::dpi::LogicalSize::new(800, 600));
 
         let window = event_loop
@@ -144,21 +273,19 @@ impl QuantaTermApp {
         self.window = Some(window);
         self.renderer = Some(renderer);
 
-        // Start PTY with initial size (rough terminal size calculation)
+        // Start PTY with the grid's size
         if let Some(ref mut pty) = self.pty {
-            let cols = 80; // Default terminal width
-            let rows = 24; // Default terminal height
-
-            pty.start_shell(cols, rows)
+            let grid = self.terminal.grid();
+            pty.start_shell(grid.cols, grid.rows)
                 .await
                 .context("Failed to start shell")?;
 
-            // Add welcome message to renderer
-            if let Some(ref mut renderer) = self.renderer {
-                renderer.add_text("QuantaTerm v0.1.0 - Shell Started");
-                renderer.add_text("Type commands and see output appear!");
-                renderer.add_text("Press Escape to exit.");
-            }
+            self.show_message(&format!(
+                "QuantaTerm v{} - Shell Started",
+                quantaterm_core::VERSION
+            ));
+            self.show_message("Press Escape to exit.");
+            self.update_renderer();
         }
 
         info!("Window and renderer initialized successfully");
@@ -167,46 +294,52 @@ impl QuantaTermApp {
 
     /// Process PTY events
     fn process_pty_events(&mut self) {
-        if let Some(ref mut pty) = self.pty {
-            while let Some(event) = pty.try_recv_event() {
-                match event {
-                    PtyEvent::Data(data) => {
-                        // Convert bytes to string for display
-                        if let Ok(text) = String::from_utf8(data.clone()) {
-                            debug!("Shell output: {}", text.trim());
-
-                            // Send text to renderer for display
-                            if let Some(ref mut renderer) = self.renderer {
-                                renderer.add_text(&text);
-                            }
-                        } else {
-                            debug!("Shell output (binary): {} bytes", data.len());
-                        }
-                    }
-                    PtyEvent::ParsedActions(_actions) => {
-                        // TODO: Apply parsed actions to terminal grid
-                        // For now, we just use the raw data above
-                        debug!("Received parsed terminal actions");
+        let Some(ref mut pty) = self.pty else {
+            return;
+        };
+
+        let mut responder = pty.responder();
+        let mut messages = Vec::new();
+        let mut output = false;
+        while let Some(event) = pty.try_recv_event() {
+            match event {
+                PtyEvent::Data(data) => {
+                    trace!("Shell output: {} bytes", data.len());
+                    // Query replies go back to the shell once it is running
+                    match responder.as_mut() {
+                        Some(responder) => self.terminal.process(&data, responder),
+                        None => self.terminal.process(&data, &mut Vec::new()),
                     }
-                    PtyEvent::ProcessExit(code) => {
-                        info!("Shell process exited with code: {}", code);
+                    output = true;
+                }
+                PtyEvent::ProcessExit(code) => {
+                    info!("Shell process exited with code: {}", code);
+                    messages.push(format!("Shell exited with code: {}", code));
+                }
+                PtyEvent::Error(error) => {
+                    error!("PTY error: {}", error);
+                    messages.push(format!("PTY Error: {}", error));
+                }
+            }
+        }
 
-                        // Add exit message to display
-                        if let Some(ref mut renderer) = self.renderer {
-                            renderer.add_text(&format!("Shell exited with code: {}", code));
-                        }
-                    }
-                    PtyEvent::Error(error) => {
-                        error!("PTY error: {}", error);
+        for message in &messages {
+            self.show_message(message);
+        }
+        if !output && messages.is_empty() {
+            return;
+        }
 
-                        // Add error message to display
-                        if let Some(ref mut renderer) = self.renderer {
-                            renderer.add_text(&format!("PTY Error: {}", error));
-                        }
-                    }
-                }
+        if let Some(title) = self.terminal.take_title_change() {
+            if let Some(ref window) = self.window {
+                window.set_title(if title.is_empty() {
+                    WINDOW_TITLE
+                } else {
+                    title
+                });
             }
         }
+        self.update_renderer();
     }
 }
 
@@ -221,6 +354,28 @@ impl ApplicationHandler for QuantaTermApp {
         }
     }
 
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        // A blinking cursor is due to change phase
+        if let StartCause::ResumeTimeReached { .. } = cause {
+            if let Some(ref window) = self.window {
+                window.request_redraw();
+            }
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.report_stats(Instant::now());
+
+        let next_blink = self
+            .renderer
+            .as_ref()
+            .and_then(|renderer| renderer.next_cursor_blink(Instant::now()));
+        event_loop.set_control_flow(match next_blink {
+            Some(deadline) => ControlFlow::WaitUntil(deadline),
+            None => ControlFlow::Wait,
+        });
+    }
+
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
@@ -240,6 +395,23 @@ impl ApplicationHandler for QuantaTermApp {
             WindowEvent::KeyboardInput { event, .. } => {
                 self.handle_keyboard_input(event, event_loop);
             }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::Focused(focused) => {
+                debug!("Window focus changed: {}", focused);
+                if let (Some(data), Some(ref pty)) = (self.focus_to_bytes(focused), &self.pty) {
+                    if let Err(e) = pty.write_data(data) {
+                        warn!("Failed to write to PTY: {}", e);
+                    }
+                }
+                if let Some(renderer) = &mut self.renderer {
+                    renderer.set_focused(focused);
+                }
+                if let Some(ref window) = self.window {
+                    window.request_redraw();
+                }
+            }
             WindowEvent::RedrawRequested => {
                 // Process PTY events before rendering
                 self.process_pty_events();
@@ -256,16 +428,18 @@ impl ApplicationHandler for QuantaTermApp {
                     renderer.resize(physical_size);
                 }
 
-                // Resize PTY to match window
-                if let Some(ref pty) = self.pty {
-                    // Rough calculation: divide by character size (estimate)
-                    let cols = (physical_size.width / 8).max(1) as u16; // ~8px per char width
-                    let rows = (physical_size.height / 16).max(1) as u16; // ~16px per char height
+                // Rough calculation: divide by character size (estimate)
+                let cols = (physical_size.width / 8).max(1) as u16; // ~8px per char width
+                let rows = (physical_size.height / 16).max(1) as u16; // ~16px per char height
 
+                // Resize the grid and the PTY to match the window
+                self.terminal.resize(cols, rows);
+                if let Some(ref pty) = self.pty {
                     if let Err(e) = pty.resize(cols, rows) {
                         warn!("Failed to resize PTY: {}", e);
                     }
                 }
+                self.update_renderer();
             }
             _ => {}
         }
@@ -275,6 +449,8 @@ impl ApplicationHandler for QuantaTermApp {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use quantaterm_core::{CursorShape, CursorStyle};
+    use quantaterm_telemetry::TelemetryEvent;
 
     #[tokio::test]
     async fn test_app_creation() {
@@ -282,6 +458,80 @@ mod tests {
         assert!(app.window.is_none());
         assert!(app.renderer.is_none());
         assert!(app.pty.is_some());
+        assert_eq!(app.terminal.grid().cols, DEFAULT_COLS);
+    }
+
+    #[tokio::test]
+    async fn test_messages_go_through_terminal() {
+        let mut app = QuantaTermApp::new().await.unwrap();
+        app.show_message("first");
+        app.show_message("\x1b[1msecond");
+
+        let text = app.terminal.grid().get_viewport_text();
+        assert_eq!(text[0].trim_end(), "first");
+        assert_eq!(text[1].trim_end(), "second");
+        assert_eq!(app.terminal.grid().cursor_position(), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_stats_reported_once_per_interval() {
+        let mut app = QuantaTermApp::new().await.unwrap();
+        let start = app.stats_reported;
+
+        app.report_stats(start + STATS_INTERVAL / 2);
+        assert_eq!(app.stats_reported, start);
+        assert!(!app
+            .telemetry
+            .get_summary()
+            .counters
+            .contains_key("pty.bytes_read"));
+
+        app.report_stats(start + STATS_INTERVAL);
+        assert_eq!(app.stats_reported, start + STATS_INTERVAL);
+        assert!(app
+            .telemetry
+            .get_summary()
+            .counters
+            .contains_key("pty.bytes_read"));
+        assert!(matches!(
+            app.telemetry.recent_events().last(),
+            Some(TelemetryEvent::ScrollbackMemory { lines: 0, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_config_applied_to_terminal() {
+        let mut config = Config::default();
+        config.terminal.max_scrollback = 300;
+        config.terminal.scrollback_bytes = 1024 * 1024;
+        let mut app = QuantaTermApp::with_config(config).await.unwrap();
+        assert_eq!(app.terminal.grid().scrollback_stats().budget, 1024 * 1024);
+
+        for i in 0..400 {
+            app.show_message(&i.to_string());
+        }
+        assert_eq!(app.terminal.grid().scrollback_stats().lines, 300);
+    }
+
+    #[tokio::test]
+    async fn test_configured_cursor_style_is_default() {
+        let mut config = Config::default();
+        config.renderer.cursor_style = CursorStyle {
+            shape: CursorShape::Bar,
+            blinking: false,
+        };
+        let mut app = QuantaTermApp::with_config(config.clone()).await.unwrap();
+        assert_eq!(
+            app.terminal.grid().cursor_style(),
+            config.renderer.cursor_style
+        );
+
+        // DECSCUSR 0 goes back to the configured style
+        app.terminal.process(b"\x1b[2 q\x1b[0 q", &mut Vec::new());
+        assert_eq!(
+            app.terminal.grid().cursor_style(),
+            config.renderer.cursor_style
+        );
     }
 
     #[test]
@@ -297,6 +547,42 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_cursor_keys_follow_decckm() {
+        let mut app = QuantaTermApp::with_config(Config::default()).await.unwrap();
+        app.terminal.process(b"\x1b[?1h", &mut Vec::new());
+        assert_eq!(
+            app.keycode_to_bytes(KeyCode::ArrowUp),
+            Some(b"\x1bOA".to_vec())
+        );
+        assert_eq!(
+            app.keycode_to_bytes(KeyCode::Home),
+            Some(b"\x1bOH".to_vec())
+        );
+
+        app.terminal.process(b"\x1b[?1l", &mut Vec::new());
+        assert_eq!(
+            app.keycode_to_bytes(KeyCode::ArrowLeft),
+            Some(b"\x1b[D".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_paste_and_focus_follow_modes() {
+        let mut app = QuantaTermApp::with_config(Config::default()).await.unwrap();
+        assert_eq!(app.paste_to_bytes("ls\n-la\r\n"), b"ls\r-la\r");
+        assert_eq!(app.focus_to_bytes(true), None);
+
+        app.terminal
+            .process(b"\x1b[?2004h\x1b[?1004h", &mut Vec::new());
+        assert_eq!(
+            app.paste_to_bytes("echo\x1b[201~rm\n"),
+            b"\x1b[200~echorm\r\x1b[201~"
+        );
+        assert_eq!(app.focus_to_bytes(true), Some(&b"\x1b[I"[..]));
+        assert_eq!(app.focus_to_bytes(false), Some(&b"\x1b[O"[..]));
+    }
+
     #[test]
     fn test_pty_event_handling() {
         use quantaterm_pty::PtyEvent;
@@ -6,6 +6,7 @@
 #![deny(unsafe_code)]
 
 use quantaterm_core::logging::{LogLevel, LoggingConfig};
+use quantaterm_core::{AmbiguousWidth, CursorStyle};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tracing::{debug, error, info, instrument, warn};
@@ -30,8 +31,17 @@ pub struct TerminalConfig {
     pub default_rows: u16,
     /// Maximum scrollback lines
     pub max_scrollback: usize,
+    /// Most memory scrollback history may use, in bytes
+    #[serde(default = "default_scrollback_bytes")]
+    pub scrollback_bytes: usize,
+    /// Compress scrollback pages that are no longer near the bottom
+    #[serde(default)]
+    pub compress_scrollback: bool,
     /// Shell command override
     pub shell_command: Option<String>,
+    /// Width of East Asian Ambiguous characters
+    #[serde(default)]
+    pub ambiguous_width: AmbiguousWidth,
 }
 
 /// Renderer-specific configuration
@@ -45,6 +55,14 @@ pub struct RendererConfig {
     pub font_size: f32,
     /// Font family
     pub font_family: String,
+    /// Cursor shape and blinking until an application changes them
+    #[serde(default)]
+    pub cursor_style: CursorStyle,
+}
+
+/// Scrollback byte budget when the config file does not set one
+fn default_scrollback_bytes() -> usize {
+    64 * 1024 * 1024
 }
 
 impl Default for TerminalConfig {
@@ -53,7 +71,10 @@ impl Default for TerminalConfig {
             default_cols: 80,
             default_rows: 24,
             max_scrollback: 10000,
+            scrollback_bytes: default_scrollback_bytes(),
+            compress_scrollback: false,
             shell_command: None,
+            ambiguous_width: AmbiguousWidth::Narrow,
         }
     }
 }
@@ -65,6 +86,7 @@ impl Default for RendererConfig {
             target_fps: 60,
             font_size: 14.0,
             font_family: "monospace".to_string(),
+            cursor_style: CursorStyle::default(),
         }
     }
 }
@@ -299,6 +321,7 @@ mod tests {
         assert_eq!(config.terminal.default_rows, 24);
         assert_eq!(config.renderer.font_size, 14.0);
         assert!(config.renderer.vsync);
+        assert_eq!(config.terminal.ambiguous_width, AmbiguousWidth::Narrow);
     }
 
     #[test]
@@ -315,6 +338,71 @@ mod tests {
         assert!(invalid_config.validate().is_err());
     }
 
+    #[test]
+    fn test_ambiguous_width_setting() {
+        let content = toml::to_string_pretty(&Config::default()).unwrap();
+        assert!(content.contains("ambiguous_width = \"Narrow\""));
+
+        let wide: Config = toml::from_str(&content.replace("\"Narrow\"", "\"Wide\"")).unwrap();
+        assert_eq!(wide.terminal.ambiguous_width, AmbiguousWidth::Wide);
+
+        // Files written before the setting existed still load
+        let old: Config =
+            toml::from_str(&content.replace("ambiguous_width = \"Narrow\"", "")).unwrap();
+        assert_eq!(old.terminal.ambiguous_width, AmbiguousWidth::Narrow);
+    }
+
+    #[test]
+    fn test_cursor_style_setting() {
+        let content = toml::to_string_pretty(&Config::default()).unwrap();
+        assert!(content.contains("[renderer.cursor_style]"));
+
+        let bar: Config = toml::from_str(
+            &content
+                .replace("shape = \"Block\"", "shape = \"Bar\"")
+                .replace("blinking = false", "blinking = true"),
+        )
+        .unwrap();
+        assert_eq!(
+            bar.renderer.cursor_style,
+            CursorStyle {
+                shape: quantaterm_core::CursorShape::Bar,
+                blinking: true,
+            }
+        );
+
+        let old: Config = toml::from_str(
+            &content
+                .replace("[renderer.cursor_style]", "")
+                .replace("shape = \"Block\"", "")
+                .replace("blinking = false", ""),
+        )
+        .unwrap();
+        assert_eq!(old.renderer.cursor_style, CursorStyle::default());
+    }
+
+    #[test]
+    fn test_scrollback_settings() {
+        let content = toml::to_string_pretty(&Config::default()).unwrap();
+        assert!(content.contains("scrollback_bytes = 67108864"));
+
+        let compressed: Config = toml::from_str(&content.replace(
+            "compress_scrollback = false",
+            "compress_scrollback = true",
+        ))
+        .unwrap();
+        assert!(compressed.terminal.compress_scrollback);
+
+        let old: Config = toml::from_str(
+            &content
+                .replace("scrollback_bytes = 67108864", "")
+                .replace("compress_scrollback = false", ""),
+        )
+        .unwrap();
+        assert_eq!(old.terminal.scrollback_bytes, 64 * 1024 * 1024);
+        assert!(!old.terminal.compress_scrollback);
+    }
+
     #[test]
     fn test_log_level_update() {
         let mut config = Config::default();
@@ -10,6 +10,8 @@ pub mod logging;
 
 pub use error::QuantaTermError;
 
+use serde::{Deserialize, Serialize};
+
 /// Core result type for QuantaTerm operations
 pub type Result<T> = std::result::Result<T, QuantaTermError>;
 
@@ -17,10 +19,13 @@ pub type Result<T> = std::result::Result<T, QuantaTermError>;
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// CSI sequence actions for terminal control
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CsiAction {
     /// SGR (Select Graphic Rendition) - formatting attributes
-    Sgr(Vec<u16>),
+    ///
+    /// Each entry is a parameter followed by its colon-separated
+    /// subparameters, e.g. `4:3` is `[4, 3]`.
+    Sgr(Vec<Vec<u16>>),
     /// Cursor movement and positioning commands
     CursorUp(u16),
     /// Cursor down
@@ -37,6 +42,62 @@ pub enum CsiAction {
     CursorHorizontalAbsolute(u16),
     /// Cursor position (row, col)
     CursorPosition(u16, u16),
+    /// Vertical line position absolute (0-based row)
+    LinePositionAbsolute(u16),
+    /// Cursor forward by the given number of tab stops (CHT)
+    CursorForwardTab(u16),
+    /// Cursor backward by the given number of tab stops (CBT)
+    CursorBackwardTab(u16),
+    /// Clear tab stops (TBC)
+    TabClear(TabClear),
+    /// Erase in display (ED)
+    EraseInDisplay(DisplayErase),
+    /// Erase in line (EL)
+    EraseInLine(LineErase),
+    /// Erase unprotected characters in display (DECSED, `CSI ? J`)
+    SelectiveEraseInDisplay(DisplayErase),
+    /// Erase unprotected characters in line (DECSEL, `CSI ? K`)
+    SelectiveEraseInLine(LineErase),
+    /// Protect subsequently printed characters from selective erase (DECSCA, `CSI 1 " q`)
+    SetCharProtection(bool),
+    /// Set the cursor shape and blinking (DECSCUSR, `CSI Ps SP q`); `None`
+    /// restores the configured default
+    SetCursorStyle(Option<CursorStyle>),
+    /// Insert blank characters at the cursor (ICH)
+    InsertChars(u16),
+    /// Delete characters at the cursor (DCH)
+    DeleteChars(u16),
+    /// Erase characters at the cursor without moving it (ECH)
+    EraseChars(u16),
+    /// Insert blank lines at the cursor row (IL)
+    InsertLines(u16),
+    /// Delete lines at the cursor row (DL)
+    DeleteLines(u16),
+    /// Scroll content up, adding blank lines at the bottom (SU)
+    ScrollUp(u16),
+    /// Scroll content down, adding blank lines at the top (SD)
+    ScrollDown(u16),
+    /// Repeat the preceding graphic character (REP)
+    RepeatChar(u16),
+    /// Set the top and bottom scrolling margins (DECSTBM), 0-based
+    ///
+    /// A missing bottom margin means the last row of the screen.
+    SetScrollRegion {
+        /// Top margin row
+        top: u16,
+        /// Bottom margin row, inclusive
+        bottom: Option<u16>,
+    },
+    /// Set, reset, save or restore a terminal mode (SM/RM, DECSET/DECRST, XTSAVE/XTRESTORE)
+    Mode(ModeAction),
+    /// Request for terminal state that expects a reply
+    Query(TerminalQuery),
+    /// Soft terminal reset (DECSTR, `CSI ! p`)
+    SoftReset,
+    /// Save the window title on the title stack (XTPUSHTITLE, `CSI 22 t`)
+    PushTitle,
+    /// Restore the window title from the title stack (XTPOPTITLE, `CSI 23 t`)
+    PopTitle,
     /// Other CSI commands
     Other {
         /// The final byte of the CSI sequence
@@ -46,6 +107,402 @@ pub enum CsiAction {
     },
 }
 
+/// ESC sequence actions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscAction {
+    /// Full terminal reset (RIS, `ESC c`)
+    Reset,
+    /// Save the cursor, formatting, origin mode and charsets (DECSC, `ESC 7`)
+    SaveCursor,
+    /// Restore the state saved by `SaveCursor` (DECRC, `ESC 8`)
+    RestoreCursor,
+    /// Move down one line, scrolling at the bottom margin (IND)
+    Index,
+    /// Move to the start of the next line, scrolling at the bottom margin (NEL)
+    NextLine,
+    /// Move up one line, scrolling at the top margin (RI)
+    ReverseIndex,
+    /// Set a tab stop at the cursor column (HTS, `ESC H`)
+    HorizontalTabSet,
+    /// Keypad sends application sequences (DECKPAM, `ESC =`)
+    ApplicationKeypad,
+    /// Keypad sends numeric characters (DECKPNM, `ESC >`)
+    NumericKeypad,
+    /// Designate a character set into G0-G3 (`ESC ( B`, `ESC ) 0`, ...)
+    DesignateCharset(CharsetSlot, Charset),
+    /// Invoke G2 or G3 into GL until the next shift (LS2 `ESC n`, LS3 `ESC o`)
+    LockingShift(CharsetSlot),
+    /// Set the size of the cursor line (DECDHL `ESC # 3`/`4`, DECSWL `ESC # 5`, DECDWL `ESC # 6`)
+    LineSize(LineSize),
+    /// Other escape sequences
+    Other(char),
+}
+
+/// One of the four character set slots G0-G3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CharsetSlot {
+    /// G0, designated with `ESC (` and invoked by SI
+    #[default]
+    G0,
+    /// G1, designated with `ESC )` and invoked by SO
+    G1,
+    /// G2, designated with `ESC *` and invoked by LS2
+    G2,
+    /// G3, designated with `ESC +` and invoked by LS3
+    G3,
+}
+
+impl CharsetSlot {
+    /// Slot selected by the intermediate byte of a designation sequence
+    pub fn from_intermediate(byte: u8) -> Option<Self> {
+        match byte {
+            b'(' => Some(Self::G0),
+            b')' => Some(Self::G1),
+            b'*' => Some(Self::G2),
+            b'+' => Some(Self::G3),
+            _ => None,
+        }
+    }
+}
+
+/// 94-character sets that can be designated into a slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    /// US ASCII (`B`)
+    #[default]
+    Ascii,
+    /// DEC Special Graphics, used for line drawing (`0`)
+    DecSpecialGraphics,
+    /// United Kingdom, with `#` replaced by `£` (`A`)
+    Uk,
+}
+
+impl Charset {
+    /// Charset selected by the final byte of a designation sequence
+    pub fn from_final(byte: u8) -> Option<Self> {
+        match byte {
+            b'B' => Some(Self::Ascii),
+            b'0' => Some(Self::DecSpecialGraphics),
+            b'A' => Some(Self::Uk),
+            _ => None,
+        }
+    }
+}
+
+/// Region selected by an erase in display (ED) sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayErase {
+    /// From the cursor to the end of the screen (`CSI 0 J`)
+    Below,
+    /// From the start of the screen to the cursor (`CSI 1 J`)
+    Above,
+    /// The whole screen (`CSI 2 J`)
+    All,
+    /// Scrollback history only (`CSI 3 J`)
+    Scrollback,
+}
+
+impl DisplayErase {
+    /// Decode the ED parameter, returning `None` for unknown values
+    pub fn from_param(param: u16) -> Option<Self> {
+        match param {
+            0 => Some(Self::Below),
+            1 => Some(Self::Above),
+            2 => Some(Self::All),
+            3 => Some(Self::Scrollback),
+            _ => None,
+        }
+    }
+}
+
+/// Region selected by an erase in line (EL) sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineErase {
+    /// From the cursor to the end of the line (`CSI 0 K`)
+    Right,
+    /// From the start of the line to the cursor (`CSI 1 K`)
+    Left,
+    /// The whole line (`CSI 2 K`)
+    All,
+}
+
+impl LineErase {
+    /// Decode the EL parameter, returning `None` for unknown values
+    pub fn from_param(param: u16) -> Option<Self> {
+        match param {
+            0 => Some(Self::Right),
+            1 => Some(Self::Left),
+            2 => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+/// Tab stops removed by a tab clear (TBC) sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabClear {
+    /// The stop at the cursor column (`CSI 0 g`)
+    Current,
+    /// Every stop (`CSI 3 g`)
+    All,
+}
+
+impl TabClear {
+    /// Decode the TBC parameter, returning `None` for unknown values
+    pub fn from_param(param: u16) -> Option<Self> {
+        match param {
+            0 => Some(Self::Current),
+            3 => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+/// Terminal state queries answered by writing a reply to the PTY
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalQuery {
+    /// Primary device attributes (DA1, `CSI c`)
+    PrimaryDeviceAttributes,
+    /// Secondary device attributes (DA2, `CSI > c`)
+    SecondaryDeviceAttributes,
+    /// Operating status report (DSR, `CSI 5 n`)
+    OperatingStatus,
+    /// Cursor position report (CPR `CSI 6 n`, or DECXCPR `CSI ? 6 n` when `private`)
+    CursorPosition {
+        /// Reply in the DEC private form
+        private: bool,
+    },
+    /// Report whether a mode is set (DECRQM, `CSI Ps $ p` / `CSI ? Ps $ p`)
+    Mode(Mode),
+    /// Report the terminal name and version (XTVERSION, `CSI > q`)
+    Version,
+}
+
+/// Operation on a terminal mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeAction {
+    /// Enable a mode (`CSI Pm h` / `CSI ? Pm h`)
+    Set(Mode),
+    /// Disable a mode (`CSI Pm l` / `CSI ? Pm l`)
+    Reset(Mode),
+    /// Remember the current value of a DEC mode (`CSI ? Pm s`)
+    Save(Mode),
+    /// Restore a value remembered with `Save` (`CSI ? Pm r`)
+    Restore(Mode),
+}
+
+/// A terminal mode, in either the ANSI or the DEC private namespace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// ANSI mode, set without a private marker
+    Ansi(AnsiMode),
+    /// DEC private mode, set with the `?` marker
+    Dec(DecMode),
+}
+
+/// ANSI modes (SM/RM)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnsiMode {
+    /// Insert/replace mode (IRM, 4)
+    Insert,
+    /// Line feed also performs a carriage return (LNM, 20)
+    LineFeedNewLine,
+    /// Any other ANSI mode number
+    Unknown(u16),
+}
+
+impl AnsiMode {
+    /// Decode an ANSI mode number
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            4 => Self::Insert,
+            20 => Self::LineFeedNewLine,
+            n => Self::Unknown(n),
+        }
+    }
+
+    /// The numeric mode parameter
+    pub fn code(self) -> u16 {
+        match self {
+            Self::Insert => 4,
+            Self::LineFeedNewLine => 20,
+            Self::Unknown(n) => n,
+        }
+    }
+}
+
+/// DEC private modes (DECSET/DECRST)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DecMode {
+    /// Application cursor keys (DECCKM, 1)
+    CursorKeys,
+    /// Origin mode (DECOM, 6)
+    Origin,
+    /// Auto-wrap mode (DECAWM, 7)
+    Autowrap,
+    /// Blinking cursor (12)
+    CursorBlink,
+    /// Cursor visible (DECTCEM, 25)
+    ShowCursor,
+    /// Alternate screen buffer (47)
+    AltScreen,
+    /// Focus in/out reporting (1004)
+    FocusReporting,
+    /// Alternate screen buffer, cleared when leaving it (1047)
+    AltScreenClear,
+    /// Save the cursor as in DECSC (1048)
+    SaveCursor,
+    /// Save the cursor and switch to a cleared alternate screen (1049)
+    AltScreenSaveCursor,
+    /// Bracketed paste (2004)
+    BracketedPaste,
+    /// Synchronized output: hold rendering until the update ends (2026)
+    SynchronizedOutput,
+    /// Any other DEC private mode number
+    Unknown(u16),
+}
+
+impl DecMode {
+    /// Decode a DEC private mode number
+    pub fn from_code(code: u16) -> Self {
+        match code {
+            1 => Self::CursorKeys,
+            6 => Self::Origin,
+            7 => Self::Autowrap,
+            12 => Self::CursorBlink,
+            25 => Self::ShowCursor,
+            47 => Self::AltScreen,
+            1004 => Self::FocusReporting,
+            1047 => Self::AltScreenClear,
+            1048 => Self::SaveCursor,
+            1049 => Self::AltScreenSaveCursor,
+            2004 => Self::BracketedPaste,
+            2026 => Self::SynchronizedOutput,
+            n => Self::Unknown(n),
+        }
+    }
+
+    /// The numeric mode parameter
+    pub fn code(self) -> u16 {
+        match self {
+            Self::CursorKeys => 1,
+            Self::Origin => 6,
+            Self::Autowrap => 7,
+            Self::CursorBlink => 12,
+            Self::ShowCursor => 25,
+            Self::AltScreen => 47,
+            Self::FocusReporting => 1004,
+            Self::AltScreenClear => 1047,
+            Self::SaveCursor => 1048,
+            Self::AltScreenSaveCursor => 1049,
+            Self::BracketedPaste => 2004,
+            Self::SynchronizedOutput => 2026,
+            Self::Unknown(n) => n,
+        }
+    }
+}
+
+/// Column width of East Asian Ambiguous characters such as `±`, `§` or `Ω`
+///
+/// Western fonts draw them narrow, while CJK locales traditionally give them
+/// two columns; the setting has to match what applications assume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AmbiguousWidth {
+    /// One column, as in most Western locales
+    #[default]
+    Narrow,
+    /// Two columns, as in CJK locales
+    Wide,
+}
+
+/// Shape the cursor is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CursorShape {
+    /// Filled cell
+    #[default]
+    Block,
+    /// Line under the cell
+    Underline,
+    /// Vertical line at the left edge of the cell
+    Bar,
+}
+
+/// Cursor shape and whether it blinks
+///
+/// Editors such as Vim and fish switch between a block and a bar per mode
+/// with DECSCUSR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CursorStyle {
+    /// Cursor shape
+    pub shape: CursorShape,
+    /// Whether the cursor blinks
+    pub blinking: bool,
+}
+
+impl CursorStyle {
+    /// Decode a DECSCUSR parameter from 1 to 6, returning `None` for others
+    ///
+    /// Odd values blink; 1-2 select a block, 3-4 an underline, 5-6 a bar.
+    pub fn from_param(param: u16) -> Option<Self> {
+        let shape = match param {
+            1 | 2 => CursorShape::Block,
+            3 | 4 => CursorShape::Underline,
+            5 | 6 => CursorShape::Bar,
+            _ => return None,
+        };
+        Some(Self {
+            shape,
+            blinking: param % 2 == 1,
+        })
+    }
+
+    /// The DECSCUSR parameter selecting this style
+    pub fn param(self) -> u16 {
+        let steady = match self.shape {
+            CursorShape::Block => 2,
+            CursorShape::Underline => 4,
+            CursorShape::Bar => 6,
+        };
+        steady - self.blinking as u16
+    }
+}
+
+/// Size a row is drawn at (DECSWL, DECDWL, DECDHL)
+///
+/// Double-size rows show half as many columns, each two cells wide. A
+/// double-height line is sent twice, once marked as its top half and once
+/// as its bottom half.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LineSize {
+    /// Normal row
+    #[default]
+    SingleWidth,
+    /// Double-width row (DECDWL)
+    DoubleWidth,
+    /// Top half of a double-height, double-width row (DECDHL)
+    DoubleHeightTop,
+    /// Bottom half of a double-height, double-width row (DECDHL)
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    /// Line size selected by the final byte of an `ESC #` sequence
+    pub fn from_final(byte: u8) -> Option<Self> {
+        match byte {
+            b'3' => Some(Self::DoubleHeightTop),
+            b'4' => Some(Self::DoubleHeightBottom),
+            b'5' => Some(Self::SingleWidth),
+            b'6' => Some(Self::DoubleWidth),
+            _ => None,
+        }
+    }
+
+    /// Whether each column takes two cells
+    pub fn is_double(self) -> bool {
+        self != Self::SingleWidth
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -54,4 +511,56 @@ mod tests {
     fn test_version_info() {
         assert_eq!(VERSION, "0.1.0");
     }
+
+    #[test]
+    fn test_cursor_style_params() {
+        for param in 1..=6 {
+            assert_eq!(CursorStyle::from_param(param).unwrap().param(), param);
+        }
+        assert_eq!(
+            CursorStyle::from_param(5),
+            Some(CursorStyle {
+                shape: CursorShape::Bar,
+                blinking: true,
+            })
+        );
+        assert_eq!(CursorStyle::from_param(0), None);
+        assert_eq!(CursorStyle::from_param(7), None);
+    }
+
+    #[test]
+    fn test_erase_param_decoding() {
+        assert_eq!(DisplayErase::from_param(0), Some(DisplayErase::Below));
+        assert_eq!(DisplayErase::from_param(3), Some(DisplayErase::Scrollback));
+        assert_eq!(DisplayErase::from_param(4), None);
+
+        assert_eq!(LineErase::from_param(1), Some(LineErase::Left));
+        assert_eq!(LineErase::from_param(2), Some(LineErase::All));
+        assert_eq!(LineErase::from_param(3), None);
+    }
+
+    #[test]
+    fn test_mode_code_round_trip() {
+        for code in [
+            1, 6, 7, 12, 25, 47, 1004, 1047, 1048, 1049, 2004, 2026, 9999,
+        ] {
+            assert_eq!(DecMode::from_code(code).code(), code);
+        }
+        for code in [4, 20, 2] {
+            assert_eq!(AnsiMode::from_code(code).code(), code);
+        }
+        assert_eq!(DecMode::from_code(1049), DecMode::AltScreenSaveCursor);
+        assert_eq!(AnsiMode::from_code(3), AnsiMode::Unknown(3));
+    }
+
+    #[test]
+    fn test_charset_designation_decoding() {
+        assert_eq!(CharsetSlot::from_intermediate(b'('), Some(CharsetSlot::G0));
+        assert_eq!(CharsetSlot::from_intermediate(b'+'), Some(CharsetSlot::G3));
+        assert_eq!(CharsetSlot::from_intermediate(b'#'), None);
+
+        assert_eq!(Charset::from_final(b'0'), Some(Charset::DecSpecialGraphics));
+        assert_eq!(Charset::from_final(b'B'), Some(Charset::Ascii));
+        assert_eq!(Charset::from_final(b'<'), None);
+    }
 }
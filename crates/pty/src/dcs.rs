@@ -0,0 +1,237 @@
+//! DCS (Device Control String) buffering and decoding
+//!
+//! DCS payloads arrive byte by byte through the VTE `hook`/`put`/`unhook`
+//! callbacks and may span several reads, so they are collected in a
+//! [`DcsBuffer`] that lives on the parser between calls. Payloads larger than
+//! [`MAX_DCS_BYTES`] are discarded rather than buffered without bound.
+
+use tracing::debug;
+
+/// Largest DCS payload buffered, in bytes
+pub const MAX_DCS_BYTES: usize = 1024 * 1024;
+
+/// Decoded DCS sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DcsAction {
+    /// Request a setting as a control string (DECRQSS, `DCS $ q Pt ST`)
+    RequestStatus(StatusRequest),
+    /// Query terminfo capabilities by name (XTGETTCAP, `DCS + q Pt ST`)
+    RequestTermcap(Vec<String>),
+    /// Begin (`true`, `DCS = 1 s`) or end (`false`, `DCS = 2 s`) a synchronized update
+    SynchronizedUpdate(bool),
+    /// Any other DCS, such as Sixel graphics, with its raw contents
+    Other {
+        /// Numeric parameters
+        params: Vec<u16>,
+        /// Intermediate bytes
+        intermediates: Vec<u8>,
+        /// Final character
+        command: char,
+        /// Payload between the final character and ST
+        data: Vec<u8>,
+    },
+}
+
+/// Settings that can be requested with DECRQSS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusRequest {
+    /// Current graphic rendition (`m`)
+    Sgr,
+    /// Top and bottom margins (`r`)
+    ScrollRegion,
+    /// Cursor style as set by DECSCUSR (`SP q`)
+    CursorStyle,
+    /// A setting we do not report, with its raw selector
+    Unknown(Vec<u8>),
+}
+
+/// A DCS sequence being collected
+#[derive(Debug, Clone)]
+pub(crate) struct DcsBuffer {
+    params: Vec<u16>,
+    intermediates: Vec<u8>,
+    command: char,
+    data: Vec<u8>,
+    overflowed: bool,
+}
+
+impl DcsBuffer {
+    /// Start collecting a DCS sequence
+    pub(crate) fn new(params: Vec<u16>, intermediates: &[u8], command: char) -> Self {
+        Self {
+            params,
+            intermediates: intermediates.to_vec(),
+            command,
+            data: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    /// Append a payload byte, dropping the payload once it exceeds the cap
+    pub(crate) fn push(&mut self, byte: u8) {
+        if self.overflowed {
+            return;
+        }
+        if self.data.len() >= MAX_DCS_BYTES {
+            debug!(
+                subsystem = "pty",
+                command = ?self.command,
+                limit = MAX_DCS_BYTES,
+                "DCS payload too large, discarding"
+            );
+            self.overflowed = true;
+            self.data = Vec::new();
+            return;
+        }
+        self.data.push(byte);
+    }
+
+    /// Decode the finished sequence, or `None` if it overflowed
+    pub(crate) fn finish(self) -> Option<DcsAction> {
+        if self.overflowed {
+            return None;
+        }
+
+        let action = match (self.intermediates.as_slice(), self.command) {
+            (b"$", 'q') => DcsAction::RequestStatus(match self.data.as_slice() {
+                b"m" => StatusRequest::Sgr,
+                b"r" => StatusRequest::ScrollRegion,
+                b" q" => StatusRequest::CursorStyle,
+                _ => StatusRequest::Unknown(self.data),
+            }),
+            (b"+", 'q') => match decode_termcap_names(&self.data) {
+                Some(names) => DcsAction::RequestTermcap(names),
+                None => self.into_other(),
+            },
+            (b"=", 's') if self.data.is_empty() => match self.params.as_slice() {
+                [1] => DcsAction::SynchronizedUpdate(true),
+                [2] => DcsAction::SynchronizedUpdate(false),
+                _ => self.into_other(),
+            },
+            _ => self.into_other(),
+        };
+        Some(action)
+    }
+
+    fn into_other(self) -> DcsAction {
+        DcsAction::Other {
+            params: self.params,
+            intermediates: self.intermediates,
+            command: self.command,
+            data: self.data,
+        }
+    }
+}
+
+/// Decode the `;`-separated, hex-encoded capability names of XTGETTCAP
+fn decode_termcap_names(data: &[u8]) -> Option<Vec<String>> {
+    data.split(|&b| b == b';')
+        .map(|name| String::from_utf8(hex_decode(name)?).ok())
+        .collect()
+}
+
+/// Decode an even-length hex string
+pub(crate) fn hex_decode(hex: &[u8]) -> Option<Vec<u8>> {
+    if hex.is_empty() || hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Encode bytes as upper-case hex, as XTGETTCAP replies do
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finish(
+        params: &[u16],
+        intermediates: &[u8],
+        command: char,
+        data: &[u8],
+    ) -> Option<DcsAction> {
+        let mut buffer = DcsBuffer::new(params.to_vec(), intermediates, command);
+        for &byte in data {
+            buffer.push(byte);
+        }
+        buffer.finish()
+    }
+
+    #[test]
+    fn test_decrqss() {
+        assert_eq!(
+            finish(&[], b"$", 'q', b"m"),
+            Some(DcsAction::RequestStatus(StatusRequest::Sgr))
+        );
+        assert_eq!(
+            finish(&[], b"$", 'q', b"r"),
+            Some(DcsAction::RequestStatus(StatusRequest::ScrollRegion))
+        );
+        assert_eq!(
+            finish(&[], b"$", 'q', b"\"p"),
+            Some(DcsAction::RequestStatus(StatusRequest::Unknown(
+                b"\"p".to_vec()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_xtgettcap() {
+        // "TN" and "colors"
+        assert_eq!(
+            finish(&[], b"+", 'q', b"544E;636F6C6F7273"),
+            Some(DcsAction::RequestTermcap(vec![
+                "TN".to_string(),
+                "colors".to_string()
+            ]))
+        );
+
+        // Malformed hex falls back to the raw form
+        for bad in [&b"54E"[..], b"ZZ", b"", b"544E;"] {
+            assert!(
+                matches!(finish(&[], b"+", 'q', bad), Some(DcsAction::Other { .. })),
+                "payload {:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_synchronized_update() {
+        assert_eq!(
+            finish(&[1], b"=", 's', b""),
+            Some(DcsAction::SynchronizedUpdate(true))
+        );
+        assert_eq!(
+            finish(&[2], b"=", 's', b""),
+            Some(DcsAction::SynchronizedUpdate(false))
+        );
+        assert!(matches!(
+            finish(&[3], b"=", 's', b""),
+            Some(DcsAction::Other { .. })
+        ));
+    }
+
+    #[test]
+    fn test_oversized_payload_is_dropped() {
+        let mut buffer = DcsBuffer::new(vec![], b"", 'q');
+        for _ in 0..=MAX_DCS_BYTES {
+            buffer.push(b'#');
+        }
+        assert!(buffer.overflowed);
+        assert!(buffer.data.is_empty());
+        assert_eq!(buffer.finish(), None);
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        assert_eq!(hex_encode(b"Co"), "436F");
+        assert_eq!(hex_decode(b"436f"), Some(b"Co".to_vec()));
+        assert_eq!(hex_decode(b"4"), None);
+    }
+}
@@ -0,0 +1,173 @@
+//! Streaming parser output
+//!
+//! [`TerminalParser::advance`](crate::TerminalParser::advance) hands every
+//! decoded event straight to a [`TerminalHandler`] instead of collecting
+//! [`ParseAction`](crate::ParseAction)s first, so printing text allocates
+//! nothing per character. [`TerminalGrid`] implements the trait directly;
+//! `TerminalParser::parse` keeps the collected form for tests and tools.
+
+use crate::dcs::DcsAction;
+use crate::osc::{ColorRequest, OscAction, OscTerminator};
+use crate::parser::ParserState;
+use quantaterm_blocks::TerminalGrid;
+use quantaterm_core::{CsiAction, DecMode, EscAction, Mode};
+use tracing::{debug, trace};
+
+/// Receiver for events decoded by the parser, in stream order
+pub trait TerminalHandler {
+    /// Print a character
+    fn print(&mut self, c: char);
+
+    /// Execute a C0 or C1 control byte
+    fn execute(&mut self, byte: u8);
+
+    /// SGR changed the graphic rendition
+    ///
+    /// `params` are the raw parameter groups, each one parameter followed by
+    /// its colon-separated subparameters. `state` is the formatting in effect
+    /// after applying them.
+    fn sgr(&mut self, params: &[&[u16]], state: &ParserState);
+
+    /// Any other CSI sequence
+    fn csi_dispatch(&mut self, action: CsiAction);
+
+    /// An escape sequence
+    fn esc_dispatch(&mut self, action: EscAction);
+
+    /// Formatting in effect for new cells, if the handler tracks it
+    ///
+    /// Read after the cursor is restored, so SGR sequences that follow build
+    /// on the restored formatting. `None` leaves the parser's own unchanged.
+    fn formatting(&self) -> Option<ParserState> {
+        None
+    }
+
+    /// An OSC sequence and how it was terminated
+    fn osc_dispatch(&mut self, action: OscAction, terminator: OscTerminator) {
+        trace!(subsystem = "pty", action = ?action, terminator = ?terminator, "Ignored OSC action");
+    }
+
+    /// A terminated DCS sequence
+    fn dcs_dispatch(&mut self, action: DcsAction) {
+        trace!(subsystem = "pty", action = ?action, "Ignored DCS action");
+    }
+}
+
+impl TerminalHandler for TerminalGrid {
+    fn print(&mut self, c: char) {
+        self.print_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.execute_control(byte);
+    }
+
+    fn sgr(&mut self, _params: &[&[u16]], state: &ParserState) {
+        self.apply_sgr(state.fg_color, state.bg_color, state.attrs);
+        self.set_underline_color(state.underline_color);
+    }
+
+    fn csi_dispatch(&mut self, action: CsiAction) {
+        self.handle_csi_action(&action);
+    }
+
+    fn esc_dispatch(&mut self, action: EscAction) {
+        self.handle_esc_action(&action);
+    }
+
+    fn formatting(&self) -> Option<ParserState> {
+        let (fg_color, bg_color, attrs) = self.current_formatting();
+        Some(ParserState {
+            fg_color,
+            bg_color,
+            attrs,
+            underline_color: self.current_underline_color(),
+        })
+    }
+
+    fn osc_dispatch(&mut self, action: OscAction, _terminator: OscTerminator) {
+        apply_osc(self, &action);
+    }
+
+    fn dcs_dispatch(&mut self, action: DcsAction) {
+        match action {
+            DcsAction::SynchronizedUpdate(active) => {
+                self.set_mode(Mode::Dec(DecMode::SynchronizedOutput), active)
+            }
+            // Status and termcap requests only need a reply
+            DcsAction::RequestStatus(_) | DcsAction::RequestTermcap(_) => {}
+            DcsAction::Other { command, .. } => {
+                debug!(subsystem = "pty", command = ?command, "Unhandled DCS sequence");
+            }
+        }
+    }
+}
+
+/// Apply the grid state an OSC sequence changes: palette entries (OSC 4/104)
+pub(crate) fn apply_osc(grid: &mut TerminalGrid, action: &OscAction) {
+    match action {
+        OscAction::Palette(entries) => {
+            for (index, request) in entries {
+                if let ColorRequest::Set(color) = request {
+                    grid.set_palette_color(*index, *color);
+                }
+            }
+        }
+        OscAction::ResetPalette(indices) if indices.is_empty() => grid.reset_palette(),
+        OscAction::ResetPalette(indices) => {
+            for index in indices {
+                grid.reset_palette_color(*index);
+            }
+        }
+        other => trace!(subsystem = "pty", action = ?other, "Ignored OSC action"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::TerminalParser;
+    use quantaterm_blocks::{CellAttrs, CellColor};
+
+    #[test]
+    fn test_parser_drives_grid_directly() {
+        let mut parser = TerminalParser::new();
+        let mut grid = TerminalGrid::new(10, 3);
+        parser.advance(
+            b"ab\r\n\x1b[1;31mc\x1b[0md\x1b[2;5H!\x1bP=1s\x1b\\",
+            &mut grid,
+        );
+
+        assert_eq!(
+            grid.get_viewport_text(),
+            vec!["ab        ", "cd  !     ", "          "]
+        );
+        let c = grid.get_cell(0, 1).unwrap();
+        assert_eq!(c.attrs, CellAttrs::BOLD);
+        assert_eq!(c.fg_color, CellColor::Indexed(1));
+        assert_eq!(grid.get_cell(1, 1).unwrap().attrs, CellAttrs::empty());
+        assert!(grid.modes().synchronized_output);
+    }
+
+    #[test]
+    fn test_restored_formatting_comes_from_active_buffer() {
+        let mut parser = TerminalParser::new();
+        let mut grid = TerminalGrid::new(10, 3);
+
+        // Saved on the primary screen, restored on the alternate one, which
+        // has no saved cursor of its own
+        parser.advance(b"\x1b[1;31m\x1b7\x1b[?1049h\x1b[0;44m\x1b8", &mut grid);
+        assert_eq!(grid.current_formatting().2, CellAttrs::empty());
+        parser.advance(b"\x1b[4mx", &mut grid);
+        let x = grid.get_cell(0, 0).unwrap();
+        assert_eq!(x.attrs, CellAttrs::UNDERLINE);
+        assert_eq!(x.fg_color, CellColor::Default);
+        assert_eq!(x.bg_color, CellColor::Default);
+
+        // Leaving the alternate screen restores what DECSC saved before it
+        parser.advance(b"\x1b[?1049l\x1b[3my", &mut grid);
+        let y = grid.get_cell(0, 0).unwrap();
+        assert_eq!(y.attrs, CellAttrs::BOLD | CellAttrs::ITALIC);
+        assert_eq!(y.fg_color, CellColor::Indexed(1));
+    }
+}
@@ -7,21 +7,41 @@
 
 use anyhow::{Context, Result};
 use portable_pty::{CommandBuilder, PtySize};
-use std::io::{BufRead, BufReader, Write};
+use std::io::Write;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, instrument, trace, warn};
 
+pub mod dcs;
+pub mod handler;
+pub mod osc;
 pub mod parser;
-
-pub use parser::{CsiAction, EscAction, ParseAction, ParserState, TerminalParser};
+pub mod reader;
+pub mod responder;
+pub mod terminal;
+
+pub use dcs::{DcsAction, StatusRequest};
+pub use handler::TerminalHandler;
+pub use osc::{OscAction, OscTerminator};
+pub use parser::{
+    CsiAction, EscAction, Mode, ModeAction, ParseAction, ParserState, TerminalParser,
+};
+pub use reader::{ChunkRing, ReaderStats, RingConfig};
+pub use responder::{respond, PtyResponder, ResponseSink};
+pub use terminal::Terminal;
+
+/// Maximum number of undelivered events queued for the application
+///
+/// When the application stops draining events, output accumulates in the
+/// reader ring instead, where it is coalesced and eventually throttles the
+/// shell.
+pub const EVENT_QUEUE_CAPACITY: usize = 32;
 
 /// Events from the PTY that need to be handled by the application
 #[derive(Debug, Clone)]
 pub enum PtyEvent {
-    /// Raw data received from the shell (stdout/stderr)
+    /// Raw data received from the shell (stdout/stderr), to be fed to a [`Terminal`]
     Data(Vec<u8>),
-    /// Parsed actions from terminal escape sequences
-    ParsedActions(Vec<ParseAction>),
     /// Shell process has exited
     ProcessExit(i32),
     /// Error occurred in PTY operations
@@ -49,7 +69,9 @@ pub struct Pty {
     /// Channel for sending commands to the PTY
     command_tx: Option<mpsc::UnboundedSender<PtyCommand>>,
     /// Channel for receiving events from the PTY
-    event_rx: Option<mpsc::UnboundedReceiver<PtyEvent>>,
+    event_rx: Option<mpsc::Receiver<PtyEvent>>,
+    /// Ring buffer fed by the reader thread
+    ring: Option<Arc<ChunkRing>>,
 }
 
 impl Pty {
@@ -58,6 +80,7 @@ impl Pty {
         Self {
             command_tx: None,
             event_rx: None,
+            ring: None,
         }
     }
 
@@ -73,14 +96,16 @@ impl Pty {
 
         // Create channels for communication
         let (command_tx, command_rx) = mpsc::unbounded_channel();
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let (event_tx, event_rx) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+        let ring = Arc::new(ChunkRing::new(RingConfig::default()));
 
         self.command_tx = Some(command_tx);
         self.event_rx = Some(event_rx);
+        self.ring = Some(ring.clone());
 
         // Spawn the PTY task
         tokio::spawn(async move {
-            if let Err(e) = Self::pty_task(command_rx, event_tx, width, height).await {
+            if let Err(e) = Self::pty_task(command_rx, event_tx, ring, width, height).await {
                 error!(
                     subsystem = "pty",
                     error = %e,
@@ -103,6 +128,13 @@ impl Pty {
         }
     }
 
+    /// Get a handle that writes query replies back to the shell
+    ///
+    /// Returns `None` until the shell has been started.
+    pub fn responder(&self) -> Option<PtyResponder> {
+        self.command_tx.clone().map(PtyResponder::new)
+    }
+
     /// Try to receive a PTY event (non-blocking)
     pub fn try_recv_event(&mut self) -> Option<PtyEvent> {
         if let Some(ref mut rx) = self.event_rx {
@@ -121,6 +153,14 @@ impl Pty {
         }
     }
 
+    /// Take reader throughput and backpressure counters accumulated since the last call
+    pub fn take_reader_stats(&self) -> ReaderStats {
+        self.ring
+            .as_ref()
+            .map(|ring| ring.take_stats())
+            .unwrap_or_default()
+    }
+
     /// Write data to the shell's stdin
     #[instrument(name = "pty_write_data", skip(self, data))]
     pub fn write_data(&self, data: &[u8]) -> Result<()> {
@@ -169,10 +209,11 @@ impl Pty {
     }
 
     /// Main PTY task that handles shell communication
-    #[instrument(name = "pty_task", skip(command_rx, event_tx))]
+    #[instrument(name = "pty_task", skip(command_rx, event_tx, ring))]
     async fn pty_task(
         mut command_rx: mpsc::UnboundedReceiver<PtyCommand>,
-        event_tx: mpsc::UnboundedSender<PtyEvent>,
+        event_tx: mpsc::Sender<PtyEvent>,
+        ring: Arc<ChunkRing>,
         width: u16,
         height: u16,
     ) -> Result<()> {
@@ -214,67 +255,40 @@ impl Pty {
             .take_writer()
             .context("Failed to take writer")?;
 
-        // Spawn task to read shell output
-        let read_event_tx = event_tx.clone();
-        let read_task = tokio::spawn(async move {
-            let mut buf_reader = BufReader::new(reader);
-            let mut buffer = Vec::new();
-            let mut parser = TerminalParser::new();
-
-            loop {
-                buffer.clear();
-                match buf_reader.read_until(b'\n', &mut buffer) {
-                    Ok(0) => {
-                        // EOF - shell has closed
-                        debug!(subsystem = "pty", "Shell output stream closed");
-                        break;
-                    }
-                    Ok(bytes_read) => {
-                        trace!(
-                            subsystem = "pty",
-                            bytes_read = bytes_read,
-                            "Read data from shell"
-                        );
+        // Read shell output on a dedicated thread so blocking reads never
+        // stall the async runtime
+        reader::spawn_reader(reader, ring.clone()).context("Failed to spawn reader thread")?;
+        let mut output_pending = false;
 
-                        // Send raw data event
-                        if let Err(e) = read_event_tx.send(PtyEvent::Data(buffer.clone())) {
-                            warn!(
-                                subsystem = "pty",
-                                error = %e,
-                                "Failed to send data event"
-                            );
-                            break;
-                        }
+        // Main command processing loop
+        loop {
+            tokio::select! {
+                // Note that the reader has queued output or stopped with an error
+                _ = ring.readable(), if !output_pending => {
+                    output_pending = ring.has_data() || ring.has_error();
+                }
 
-                        // Parse the data and send parsed actions
-                        let actions = parser.parse(&buffer);
-                        if !actions.is_empty() {
-                            if let Err(e) = read_event_tx.send(PtyEvent::ParsedActions(actions)) {
-                                warn!(
-                                    subsystem = "pty",
-                                    error = %e,
-                                    "Failed to send parsed actions event"
-                                );
-                                break;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        error!(
+                // Forward queued output once the application has room for it
+                permit = event_tx.reserve(), if output_pending => {
+                    output_pending = false;
+                    let Ok(permit) = permit else {
+                        debug!(subsystem = "pty", "Event channel closed");
+                        break;
+                    };
+                    if let Some(data) = ring.drain() {
+                        trace!(
                             subsystem = "pty",
-                            error = %e,
-                            "Failed to read from shell"
+                            byte_count = data.len(),
+                            "Forwarding shell output"
                         );
-                        let _ = read_event_tx.send(PtyEvent::Error(e.to_string()));
-                        break;
+                        permit.send(PtyEvent::Data(data));
+                        // Report a read error once the output before it is delivered
+                        output_pending = ring.has_error();
+                    } else if let Some(error) = ring.take_error() {
+                        permit.send(PtyEvent::Error(error));
                     }
                 }
-            }
-        });
 
-        // Main command processing loop
-        loop {
-            tokio::select! {
                 // Handle incoming commands
                 cmd = command_rx.recv() => {
                     match cmd {
@@ -290,14 +304,14 @@ impl Pty {
                                     error = %e,
                                     "Failed to write to shell"
                                 );
-                                let _ = event_tx.send(PtyEvent::Error(e.to_string()));
+                                let _ = event_tx.try_send(PtyEvent::Error(e.to_string()));
                             } else if let Err(e) = writer.flush() {
                                 error!(
                                     subsystem = "pty",
                                     error = %e,
                                     "Failed to flush shell writer"
                                 );
-                                let _ = event_tx.send(PtyEvent::Error(e.to_string()));
+                                let _ = event_tx.try_send(PtyEvent::Error(e.to_string()));
                             }
                         }
                         Some(PtyCommand::Resize { width, height }) => {
@@ -319,7 +333,7 @@ impl Pty {
                                     error = %e,
                                     "Failed to resize PTY"
                                 );
-                                let _ = event_tx.send(PtyEvent::Error(e.to_string()));
+                                let _ = event_tx.try_send(PtyEvent::Error(e.to_string()));
                             } else {
                                 debug!(
                                     subsystem = "pty",
@@ -349,15 +363,16 @@ impl Pty {
                             exit_code = exit_code,
                             "Shell process exited"
                         );
-                        let _ = event_tx.send(PtyEvent::ProcessExit(exit_code));
+                        let _ = event_tx.send(PtyEvent::ProcessExit(exit_code)).await;
                         break;
                     }
                 }
             }
         }
 
-        // Cleanup
-        read_task.abort();
+        // Cleanup: closing the ring unblocks the reader thread, which exits
+        // once the master side is dropped
+        ring.close();
         let _ = child.kill();
         info!(subsystem = "pty", "PTY session ended");
 
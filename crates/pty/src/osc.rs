@@ -0,0 +1,785 @@
+//! Typed OSC (Operating System Command) decoding
+//!
+//! The VTE parser splits an OSC string on `;` and hands us raw byte slices.
+//! This module turns them into [`OscAction`]s, validating every field. Anything
+//! that is unrecognised or malformed is returned as [`OscAction::Unknown`] with
+//! the raw parameters preserved, so callers never act on partial data.
+
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use quantaterm_blocks::Color;
+use tracing::debug;
+
+/// Longest title, notification, path or URI accepted, in bytes
+pub const MAX_OSC_TEXT_BYTES: usize = 4096;
+
+/// Longest base64 clipboard payload accepted, in bytes
+pub const MAX_CLIPBOARD_BYTES: usize = 1024 * 1024;
+
+/// Base64 engine for OSC 52 that tolerates missing padding, as xterm does
+const CLIPBOARD_BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// How an OSC string was terminated
+///
+/// Replies to queries use the same terminator as the request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OscTerminator {
+    /// Terminated by BEL (0x07)
+    Bel,
+    /// Terminated by ST (`ESC \`)
+    St,
+}
+
+impl OscTerminator {
+    /// The bytes that end a reply using this terminator
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Self::Bel => b"\x07",
+            Self::St => b"\x1b\\",
+        }
+    }
+}
+
+/// Decoded OSC command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OscAction {
+    /// Set the window title and/or icon name (OSC 0/1/2)
+    SetTitle {
+        /// Which title is being set
+        target: TitleTarget,
+        /// The new title
+        title: String,
+    },
+    /// Set or query palette entries (OSC 4)
+    Palette(Vec<(u8, ColorRequest)>),
+    /// Reset palette entries to their defaults; empty means all (OSC 104)
+    ResetPalette(Vec<u8>),
+    /// Set or query dynamic colors (OSC 10/11/12/17/19)
+    DynamicColors(Vec<(DynamicColor, ColorRequest)>),
+    /// Reset a dynamic color to its default (OSC 110/111/112/117/119)
+    ResetDynamicColor(DynamicColor),
+    /// Report the shell's working directory (OSC 7)
+    WorkingDirectory {
+        /// Host name from the URI, if any
+        host: Option<String>,
+        /// Decoded absolute path
+        path: String,
+    },
+    /// Start a hyperlink, or end the current one with `None` (OSC 8)
+    Hyperlink(Option<Hyperlink>),
+    /// Set or query the clipboard (OSC 52)
+    Clipboard {
+        /// Selection buffers named by the request, e.g. `c` or `p`
+        selections: String,
+        /// What to do with them
+        request: ClipboardRequest,
+    },
+    /// Shell integration prompt mark (OSC 133)
+    SemanticPrompt(PromptMark),
+    /// Desktop notification (OSC 9 / OSC 777)
+    Notify {
+        /// Notification title, if the protocol carries one
+        title: Option<String>,
+        /// Notification body
+        body: String,
+    },
+    /// Unrecognised or malformed command, with the raw parameters
+    Unknown(Vec<Vec<u8>>),
+}
+
+/// Target of a title change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleTarget {
+    /// Icon name and window title (OSC 0)
+    Both,
+    /// Icon name only (OSC 1)
+    Icon,
+    /// Window title only (OSC 2)
+    Window,
+}
+
+/// Either a new color value or a query for the current one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorRequest {
+    /// Set the color
+    Set(Color),
+    /// Report the current color (`?`)
+    Query,
+}
+
+/// Colors addressed by OSC 10 and following
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DynamicColor {
+    /// Default foreground (10)
+    Foreground,
+    /// Default background (11)
+    Background,
+    /// Cursor color (12)
+    Cursor,
+    /// Selection background (17)
+    HighlightBackground,
+    /// Selection foreground (19)
+    HighlightForeground,
+}
+
+impl DynamicColor {
+    /// Decode an OSC dynamic color number
+    pub fn from_code(code: u16) -> Option<Self> {
+        match code {
+            10 => Some(Self::Foreground),
+            11 => Some(Self::Background),
+            12 => Some(Self::Cursor),
+            17 => Some(Self::HighlightBackground),
+            19 => Some(Self::HighlightForeground),
+            _ => None,
+        }
+    }
+
+    /// The OSC number used to set or query this color
+    pub fn code(self) -> u16 {
+        match self {
+            Self::Foreground => 10,
+            Self::Background => 11,
+            Self::Cursor => 12,
+            Self::HighlightBackground => 17,
+            Self::HighlightForeground => 19,
+        }
+    }
+}
+
+/// An OSC 8 hyperlink target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hyperlink {
+    /// Explicit link id, used to join cells of the same link
+    pub id: Option<String>,
+    /// Link target
+    pub uri: String,
+}
+
+/// OSC 52 clipboard operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardRequest {
+    /// Replace the clipboard contents
+    Set(Vec<u8>),
+    /// Report the clipboard contents
+    Query,
+}
+
+/// OSC 133 semantic prompt marks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptMark {
+    /// Start of the prompt (`A`)
+    PromptStart,
+    /// End of the prompt, start of user input (`B`)
+    CommandStart,
+    /// Command submitted, output follows (`C`)
+    CommandExecuted,
+    /// Command finished, with its exit status if reported (`D`)
+    CommandFinished {
+        /// Exit status of the command
+        exit_code: Option<i32>,
+    },
+}
+
+/// Decode the parameters of an OSC string
+pub fn parse_osc(params: &[&[u8]]) -> OscAction {
+    match decode(params) {
+        Some(action) => action,
+        None => {
+            debug!(
+                subsystem = "pty",
+                command = ?params.first().map(|p| String::from_utf8_lossy(p)),
+                "Unknown or malformed OSC command"
+            );
+            OscAction::Unknown(params.iter().map(|p| p.to_vec()).collect())
+        }
+    }
+}
+
+fn decode(params: &[&[u8]]) -> Option<OscAction> {
+    let command = parse_number(params.first()?)?;
+    let args = &params[1..];
+
+    match command {
+        0..=2 => {
+            let target = match command {
+                0 => TitleTarget::Both,
+                1 => TitleTarget::Icon,
+                _ => TitleTarget::Window,
+            };
+            // Titles may themselves contain ';'
+            let title = text(&join(args))?;
+            Some(OscAction::SetTitle { target, title })
+        }
+        4 => decode_palette(args),
+        104 => args
+            .iter()
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| parse_index(arg))
+            .collect::<Option<Vec<_>>>()
+            .map(OscAction::ResetPalette),
+        10..=19 => decode_dynamic_colors(command, args),
+        110 | 111 | 112 | 117 | 119 => {
+            DynamicColor::from_code(command - 100).map(OscAction::ResetDynamicColor)
+        }
+        7 => decode_working_directory(&join(args)),
+        8 => decode_hyperlink(args),
+        52 => decode_clipboard(args),
+        133 => decode_prompt_mark(args),
+        9 => {
+            // ConEmu uses numeric subcommands (e.g. `9;4;...` progress)
+            if args.len() > 1 && parse_number(args[0]).is_some() {
+                return None;
+            }
+            let body = text(&join(args))?;
+            (!body.is_empty()).then_some(OscAction::Notify { title: None, body })
+        }
+        777 => {
+            if args.first().copied() != Some(b"notify".as_slice()) || args.len() < 3 {
+                return None;
+            }
+            let title = text(args[1])?;
+            let body = text(&join(&args[2..]))?;
+            Some(OscAction::Notify {
+                title: Some(title),
+                body,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn decode_palette(args: &[&[u8]]) -> Option<OscAction> {
+    if args.is_empty() || args.len() % 2 != 0 {
+        return None;
+    }
+    args.chunks(2)
+        .map(|pair| Some((parse_index(pair[0])?, parse_color_request(pair[1])?)))
+        .collect::<Option<Vec<_>>>()
+        .map(OscAction::Palette)
+}
+
+fn decode_dynamic_colors(command: u16, args: &[&[u8]]) -> Option<OscAction> {
+    if args.is_empty() {
+        return None;
+    }
+
+    // Each extra argument addresses the next color number, as in xterm;
+    // colors we do not track are skipped but still validated.
+    let mut colors = Vec::with_capacity(args.len());
+    for (offset, arg) in args.iter().enumerate() {
+        let request = parse_color_request(arg)?;
+        if let Some(color) = DynamicColor::from_code(command + offset as u16) {
+            colors.push((color, request));
+        }
+    }
+    (!colors.is_empty()).then_some(OscAction::DynamicColors(colors))
+}
+
+fn decode_working_directory(uri: &[u8]) -> Option<OscAction> {
+    let uri = text(uri)?;
+    let rest = uri
+        .strip_prefix("file://")
+        .or_else(|| uri.strip_prefix("kitty-shell-cwd://"))?;
+    let slash = rest.find('/')?;
+    let (host, path) = rest.split_at(slash);
+
+    let path = String::from_utf8(percent_decode(path.as_bytes())?).ok()?;
+    if path.chars().any(char::is_control) {
+        return None;
+    }
+    let host = (!host.is_empty()).then(|| host.to_string());
+    Some(OscAction::WorkingDirectory { host, path })
+}
+
+fn decode_hyperlink(args: &[&[u8]]) -> Option<OscAction> {
+    let (options, uri) = args.split_first()?;
+    let uri = join(uri);
+    if uri.is_empty() {
+        return Some(OscAction::Hyperlink(None));
+    }
+
+    // URIs must be printable ASCII with a scheme
+    if uri.len() > MAX_OSC_TEXT_BYTES || !uri.iter().all(|b| (0x21..0x7f).contains(b)) {
+        return None;
+    }
+    let uri = String::from_utf8(uri).ok()?;
+    let scheme = &uri[..uri.find(':')?];
+    if scheme.is_empty()
+        || !scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    {
+        return None;
+    }
+
+    let mut id = None;
+    for option in options.split(|&b| b == b':').filter(|o| !o.is_empty()) {
+        let eq = option.iter().position(|&b| b == b'=')?;
+        let (key, value) = (&option[..eq], &option[eq + 1..]);
+        if key == b"id" {
+            if value.is_empty() || !value.iter().all(|b| (0x21..0x7f).contains(b)) {
+                return None;
+            }
+            id = Some(text(value)?);
+        }
+    }
+
+    Some(OscAction::Hyperlink(Some(Hyperlink { id, uri })))
+}
+
+fn decode_clipboard(args: &[&[u8]]) -> Option<OscAction> {
+    let [selections, data] = args else {
+        return None;
+    };
+
+    if !selections
+        .iter()
+        .all(|b| matches!(b, b'c' | b'p' | b'q' | b's' | b'0'..=b'7'))
+    {
+        return None;
+    }
+    // An empty selection means "s 0", as in xterm
+    let selections = if selections.is_empty() {
+        "s0".to_string()
+    } else {
+        text(selections)?
+    };
+
+    let request = if *data == b"?" {
+        ClipboardRequest::Query
+    } else {
+        if data.len() > MAX_CLIPBOARD_BYTES {
+            return None;
+        }
+        ClipboardRequest::Set(CLIPBOARD_BASE64.decode(data).ok()?)
+    };
+    Some(OscAction::Clipboard {
+        selections,
+        request,
+    })
+}
+
+fn decode_prompt_mark(args: &[&[u8]]) -> Option<OscAction> {
+    let (kind, rest) = args.split_first()?;
+    let mark = match *kind {
+        b"A" => PromptMark::PromptStart,
+        b"B" => PromptMark::CommandStart,
+        b"C" => PromptMark::CommandExecuted,
+        b"D" => {
+            // The exit status is the first argument that is not a key=value option
+            let exit_code = match rest.first() {
+                Some(arg) if !arg.is_empty() && !arg.contains(&b'=') => {
+                    Some(std::str::from_utf8(arg).ok()?.parse().ok()?)
+                }
+                _ => None,
+            };
+            PromptMark::CommandFinished { exit_code }
+        }
+        _ => return None,
+    };
+    Some(OscAction::SemanticPrompt(mark))
+}
+
+/// Parse an X11 color specification as accepted by xterm
+///
+/// Supports `rgb:r/g/b` with 1-4 hex digits per component and `#rgb` forms
+/// with 1-4 digits per component.
+pub fn parse_color_spec(spec: &[u8]) -> Option<Color> {
+    let spec = std::str::from_utf8(spec).ok()?;
+
+    if let Some(components) = spec.strip_prefix("rgb:") {
+        let mut channels = components.split('/');
+        let mut next = || -> Option<u8> {
+            let digits = channels.next()?;
+            if digits.is_empty() || digits.len() > 4 || !is_hex(digits) {
+                return None;
+            }
+            let value = u32::from_str_radix(digits, 16).ok()?;
+            let max = (1u32 << (4 * digits.len())) - 1;
+            Some((value * 255 / max) as u8)
+        };
+        let color = Color::rgb(next()?, next()?, next()?);
+        return channels.next().is_none().then_some(color);
+    }
+
+    let digits = spec.strip_prefix('#')?;
+    // Checked before slicing by byte count, which would split a multi-byte character
+    if digits.is_empty() || digits.len() % 3 != 0 || digits.len() > 12 || !is_hex(digits) {
+        return None;
+    }
+    let width = digits.len() / 3;
+    let channel = |index: usize| -> Option<u8> {
+        let value = u32::from_str_radix(&digits[index * width..(index + 1) * width], 16).ok()?;
+        // The '#' form keeps the most significant bits rather than scaling
+        Some(match width {
+            1 => value << 4,
+            2 => value,
+            3 => value >> 4,
+            _ => value >> 8,
+        } as u8)
+    };
+    Some(Color::rgb(channel(0)?, channel(1)?, channel(2)?))
+}
+
+/// Whether `digits` are all hex digits; `from_str_radix` also takes a sign
+fn is_hex(digits: &str) -> bool {
+    digits.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn parse_color_request(arg: &[u8]) -> Option<ColorRequest> {
+    if arg == b"?" {
+        Some(ColorRequest::Query)
+    } else {
+        parse_color_spec(arg).map(ColorRequest::Set)
+    }
+}
+
+fn parse_number(arg: &[u8]) -> Option<u16> {
+    if arg.is_empty() || arg.len() > 5 || !arg.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+fn parse_index(arg: &[u8]) -> Option<u8> {
+    parse_number(arg).and_then(|n| u8::try_from(n).ok())
+}
+
+/// Validate free text such as titles and notification bodies
+fn text(bytes: &[u8]) -> Option<String> {
+    if bytes.len() > MAX_OSC_TEXT_BYTES {
+        return None;
+    }
+    let text = std::str::from_utf8(bytes).ok()?;
+    if text.chars().any(char::is_control) {
+        return None;
+    }
+    Some(text.to_string())
+}
+
+/// Re-join arguments that were split on `;` by the parser
+fn join(args: &[&[u8]]) -> Vec<u8> {
+    args.join(&b';')
+}
+
+fn percent_decode(input: &[u8]) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' {
+            let hex = input.get(i + 1..i + 3)?;
+            let hex = std::str::from_utf8(hex).ok()?;
+            output.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            output.push(input[i]);
+            i += 1;
+        }
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn osc(raw: &str) -> OscAction {
+        let params: Vec<&[u8]> = raw.split(';').map(str::as_bytes).collect();
+        parse_osc(&params)
+    }
+
+    fn is_unknown(action: &OscAction) -> bool {
+        matches!(action, OscAction::Unknown(_))
+    }
+
+    #[test]
+    fn test_titles() {
+        assert_eq!(
+            osc("0;vim main.rs"),
+            OscAction::SetTitle {
+                target: TitleTarget::Both,
+                title: "vim main.rs".to_string()
+            }
+        );
+        assert_eq!(
+            osc("2;a;b"),
+            OscAction::SetTitle {
+                target: TitleTarget::Window,
+                title: "a;b".to_string()
+            }
+        );
+        assert!(matches!(
+            osc("1;"),
+            OscAction::SetTitle {
+                target: TitleTarget::Icon,
+                ..
+            }
+        ));
+
+        // Invalid UTF-8, control characters and oversized titles are rejected
+        assert!(is_unknown(&parse_osc(&[b"2", b"\xff\xfe"])));
+        assert!(is_unknown(&osc("2;bad\u{1b}title")));
+        assert!(is_unknown(&osc(&format!("2;{}", "x".repeat(5000)))));
+    }
+
+    #[test]
+    fn test_color_specs() {
+        assert_eq!(
+            parse_color_spec(b"rgb:ff/80/00"),
+            Some(Color::rgb(255, 128, 0))
+        );
+        assert_eq!(
+            parse_color_spec(b"rgb:f/8/0"),
+            Some(Color::rgb(255, 136, 0))
+        );
+        assert_eq!(
+            parse_color_spec(b"rgb:ffff/0000/8080"),
+            Some(Color::rgb(255, 0, 128))
+        );
+        assert_eq!(parse_color_spec(b"#f80"), Some(Color::rgb(240, 128, 0)));
+        assert_eq!(parse_color_spec(b"#ff8000"), Some(Color::rgb(255, 128, 0)));
+        assert_eq!(
+            parse_color_spec(b"#fff888000"),
+            Some(Color::rgb(255, 136, 0))
+        );
+
+        for bad in [
+            &b""[..],
+            b"rgb:",
+            b"rgb:ff/ff",
+            b"rgb:ff/ff/ff/ff",
+            b"rgb:fffff/0/0",
+            b"rgb:gg/00/00",
+            b"rgb:+f/00/00",
+            b"#ff80",
+            b"#",
+            b"#12",
+            "#\u{e9}0".as_bytes(),
+            b"#+f+f+f",
+            b"red",
+        ] {
+            assert_eq!(parse_color_spec(bad), None, "spec {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_palette() {
+        assert_eq!(
+            osc("4;1;rgb:ff/00/00;200;?"),
+            OscAction::Palette(vec![
+                (1, ColorRequest::Set(Color::rgb(255, 0, 0))),
+                (200, ColorRequest::Query),
+            ])
+        );
+        assert_eq!(osc("104"), OscAction::ResetPalette(vec![]));
+        assert_eq!(osc("104;3;5"), OscAction::ResetPalette(vec![3, 5]));
+
+        assert!(is_unknown(&osc("4")));
+        assert!(is_unknown(&osc("4;1")));
+        assert!(is_unknown(&osc("4;256;#fff")));
+        assert!(is_unknown(&osc("4;x;#fff")));
+        assert!(is_unknown(&osc("4;1;nonsense")));
+        assert!(is_unknown(&osc("4;1;#\u{e9}0")));
+        assert!(is_unknown(&osc("104;-1")));
+    }
+
+    #[test]
+    fn test_dynamic_colors() {
+        assert_eq!(
+            osc("11;?"),
+            OscAction::DynamicColors(vec![(DynamicColor::Background, ColorRequest::Query)])
+        );
+        // Extra arguments address the following colors
+        assert_eq!(
+            osc("10;#ffffff;#000000;?"),
+            OscAction::DynamicColors(vec![
+                (
+                    DynamicColor::Foreground,
+                    ColorRequest::Set(Color::rgb(255, 255, 255))
+                ),
+                (
+                    DynamicColor::Background,
+                    ColorRequest::Set(Color::rgb(0, 0, 0))
+                ),
+                (DynamicColor::Cursor, ColorRequest::Query),
+            ])
+        );
+        assert_eq!(
+            osc("17;#123456"),
+            OscAction::DynamicColors(vec![(
+                DynamicColor::HighlightBackground,
+                ColorRequest::Set(Color::rgb(0x12, 0x34, 0x56))
+            )])
+        );
+        assert_eq!(
+            osc("112"),
+            OscAction::ResetDynamicColor(DynamicColor::Cursor)
+        );
+        assert_eq!(
+            osc("119"),
+            OscAction::ResetDynamicColor(DynamicColor::HighlightForeground)
+        );
+
+        assert!(is_unknown(&osc("10")));
+        assert!(is_unknown(&osc("10;bogus")));
+        assert!(is_unknown(&osc("13;?")));
+        assert!(is_unknown(&osc("113")));
+    }
+
+    #[test]
+    fn test_working_directory() {
+        assert_eq!(
+            osc("7;file://host/home/user/My%20Docs"),
+            OscAction::WorkingDirectory {
+                host: Some("host".to_string()),
+                path: "/home/user/My Docs".to_string()
+            }
+        );
+        assert_eq!(
+            osc("7;file:///tmp"),
+            OscAction::WorkingDirectory {
+                host: None,
+                path: "/tmp".to_string()
+            }
+        );
+
+        assert!(is_unknown(&osc("7;http://host/tmp")));
+        assert!(is_unknown(&osc("7;file://host")));
+        assert!(is_unknown(&osc("7;file:///bad%2")));
+        assert!(is_unknown(&osc("7;file:///bad%zz")));
+        assert!(is_unknown(&osc("7;file:///bad%0a")));
+        assert!(is_unknown(&osc("7;file:///bad%ff")));
+    }
+
+    #[test]
+    fn test_hyperlinks() {
+        assert_eq!(
+            osc("8;id=x1;https://example.com/a;b"),
+            OscAction::Hyperlink(Some(Hyperlink {
+                id: Some("x1".to_string()),
+                uri: "https://example.com/a;b".to_string()
+            }))
+        );
+        assert_eq!(
+            osc("8;;file:///etc/hosts"),
+            OscAction::Hyperlink(Some(Hyperlink {
+                id: None,
+                uri: "file:///etc/hosts".to_string()
+            }))
+        );
+        assert_eq!(osc("8;;"), OscAction::Hyperlink(None));
+
+        assert!(is_unknown(&osc("8")));
+        assert!(is_unknown(&osc("8;;no-scheme")));
+        assert!(is_unknown(&osc("8;;:empty-scheme")));
+        assert!(is_unknown(&osc("8;;https://a b")));
+        assert!(is_unknown(&osc("8;id=;https://a")));
+        assert!(is_unknown(&osc("8;junk;https://a")));
+    }
+
+    #[test]
+    fn test_clipboard() {
+        assert_eq!(
+            osc("52;c;aGVsbG8="),
+            OscAction::Clipboard {
+                selections: "c".to_string(),
+                request: ClipboardRequest::Set(b"hello".to_vec())
+            }
+        );
+        // Missing padding is tolerated and an empty selection means s0
+        assert_eq!(
+            osc("52;;aGVsbG8"),
+            OscAction::Clipboard {
+                selections: "s0".to_string(),
+                request: ClipboardRequest::Set(b"hello".to_vec())
+            }
+        );
+        assert_eq!(
+            osc("52;p;?"),
+            OscAction::Clipboard {
+                selections: "p".to_string(),
+                request: ClipboardRequest::Query
+            }
+        );
+
+        assert!(is_unknown(&osc("52;c")));
+        assert!(is_unknown(&osc("52;x;aGVsbG8=")));
+        assert!(is_unknown(&osc("52;c;not base64!")));
+        assert!(is_unknown(&osc("52;c;YQ==;extra")));
+    }
+
+    #[test]
+    fn test_prompt_marks() {
+        assert_eq!(
+            osc("133;A"),
+            OscAction::SemanticPrompt(PromptMark::PromptStart)
+        );
+        assert_eq!(
+            osc("133;B"),
+            OscAction::SemanticPrompt(PromptMark::CommandStart)
+        );
+        assert_eq!(
+            osc("133;C"),
+            OscAction::SemanticPrompt(PromptMark::CommandExecuted)
+        );
+        assert_eq!(
+            osc("133;D;127"),
+            OscAction::SemanticPrompt(PromptMark::CommandFinished {
+                exit_code: Some(127)
+            })
+        );
+        assert_eq!(
+            osc("133;D;aid=1"),
+            OscAction::SemanticPrompt(PromptMark::CommandFinished { exit_code: None })
+        );
+        assert_eq!(
+            osc("133;A;k=i"),
+            OscAction::SemanticPrompt(PromptMark::PromptStart)
+        );
+
+        assert!(is_unknown(&osc("133")));
+        assert!(is_unknown(&osc("133;Z")));
+        assert!(is_unknown(&osc("133;D;oops")));
+    }
+
+    #[test]
+    fn test_notifications() {
+        assert_eq!(
+            osc("9;Build finished"),
+            OscAction::Notify {
+                title: None,
+                body: "Build finished".to_string()
+            }
+        );
+        assert_eq!(
+            osc("777;notify;make;done; 0 errors"),
+            OscAction::Notify {
+                title: Some("make".to_string()),
+                body: "done; 0 errors".to_string()
+            }
+        );
+
+        assert!(is_unknown(&osc("9")));
+        assert!(is_unknown(&osc("9;4;1;50")));
+        assert!(is_unknown(&osc("777;notify;title")));
+        assert!(is_unknown(&osc("777;other;a;b")));
+    }
+
+    #[test]
+    fn test_unknown_commands_keep_raw_params() {
+        assert_eq!(
+            osc("1337;File=x"),
+            OscAction::Unknown(vec![b"1337".to_vec(), b"File=x".to_vec()])
+        );
+        assert!(is_unknown(&parse_osc(&[])));
+        assert!(is_unknown(&osc("")));
+        assert!(is_unknown(&osc("abc;def")));
+        assert!(is_unknown(&osc("999999;x")));
+    }
+}
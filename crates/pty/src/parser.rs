@@ -1,10 +1,17 @@
 //! Terminal escape sequence parser
 //!
 //! This module provides VTE-based parsing for terminal escape sequences,
-//! with a focus on SGR (Select Graphic Rendition) codes.
-
-use quantaterm_blocks::{CellAttrs, Color};
-pub use quantaterm_core::CsiAction;
+//! with a focus on SGR (Select Graphic Rendition) codes. Decoded events are
+//! streamed to a [`TerminalHandler`] as they are found.
+
+use crate::dcs::{DcsAction, DcsBuffer};
+use crate::handler::TerminalHandler;
+use crate::osc::{parse_osc, OscAction, OscTerminator};
+use quantaterm_blocks::{CellAttrs, CellColor, Color};
+pub use quantaterm_core::{
+    AnsiMode, Charset, CharsetSlot, CsiAction, CursorShape, CursorStyle, DecMode, DisplayErase,
+    EscAction, LineErase, LineSize, Mode, ModeAction, TabClear, TerminalQuery,
+};
 use tracing::{debug, trace};
 use vte::{Params, Perform};
 
@@ -19,36 +26,32 @@ pub enum ParseAction {
     CsiDispatch(CsiAction),
     /// Perform an escape sequence action
     EscDispatch(EscAction),
-    /// OSC (Operating System Command) action
-    OscDispatch(Vec<Vec<u8>>),
-}
-
-/// ESC sequence actions
-#[derive(Debug, Clone)]
-pub enum EscAction {
-    /// Reset terminal state
-    Reset,
-    /// Other escape sequences
-    Other(char),
+    /// OSC (Operating System Command) action and how it was terminated
+    OscDispatch(OscAction, OscTerminator),
+    /// DCS (Device Control String) sequence, dispatched once terminated
+    DcsDispatch(DcsAction),
 }
 
 /// Parser state for tracking current formatting attributes
 #[derive(Debug, Clone)]
 pub struct ParserState {
     /// Current foreground color
-    pub fg_color: Color,
+    pub fg_color: CellColor,
     /// Current background color
-    pub bg_color: Color,
+    pub bg_color: CellColor,
     /// Current cell attributes
     pub attrs: CellAttrs,
+    /// Current underline color, `None` meaning the foreground color
+    pub underline_color: Option<CellColor>,
 }
 
 impl Default for ParserState {
     fn default() -> Self {
         Self {
-            fg_color: Color::DEFAULT_FG,
-            bg_color: Color::DEFAULT_BG,
+            fg_color: CellColor::Default,
+            bg_color: CellColor::Default,
             attrs: CellAttrs::empty(),
+            underline_color: None,
         }
     }
 }
@@ -56,30 +59,55 @@ impl Default for ParserState {
 impl ParserState {
     /// Reset all formatting to defaults
     pub fn reset(&mut self) {
-        self.fg_color = Color::DEFAULT_FG;
-        self.bg_color = Color::DEFAULT_BG;
+        self.fg_color = CellColor::Default;
+        self.bg_color = CellColor::Default;
         self.attrs = CellAttrs::empty();
+        self.underline_color = None;
     }
 
     /// Apply SGR parameters to update the current state
-    pub fn apply_sgr(&mut self, params: &[u16]) {
+    ///
+    /// Each entry is one parameter followed by its colon-separated
+    /// subparameters, so `4:3` arrives as `[4, 3]` while `4;3` arrives as
+    /// `[4], [3]`.
+    pub fn apply_sgr<P: AsRef<[u16]>>(&mut self, params: &[P]) {
         let mut i = 0;
         while i < params.len() {
-            match params[i] {
+            let group = params[i].as_ref();
+            let Some(&code) = group.first() else {
+                i += 1;
+                continue;
+            };
+
+            match code {
                 // Reset/default
                 0 => self.reset(),
 
                 // Bold
                 1 => self.attrs |= CellAttrs::BOLD,
 
-                // Dim (interpreted as bold off)
-                2 => self.attrs &= !CellAttrs::BOLD,
+                // Dim
+                2 => self.attrs |= CellAttrs::DIM,
 
                 // Italic
                 3 => self.attrs |= CellAttrs::ITALIC,
 
-                // Underline
-                4 => self.attrs |= CellAttrs::UNDERLINE,
+                // Underline, with an optional style subparameter
+                4 => {
+                    let style = match group.get(1).copied() {
+                        None | Some(1) => CellAttrs::UNDERLINE,
+                        Some(0) => CellAttrs::empty(),
+                        Some(2) => CellAttrs::DOUBLE_UNDERLINE,
+                        Some(3) => CellAttrs::CURLY_UNDERLINE,
+                        Some(4) => CellAttrs::DOTTED_UNDERLINE,
+                        Some(5) => CellAttrs::DASHED_UNDERLINE,
+                        Some(n) => {
+                            debug!("Unknown underline style: {}", n);
+                            CellAttrs::UNDERLINE
+                        }
+                    };
+                    self.set_underline_style(style);
+                }
 
                 // Blink
                 5 => self.attrs |= CellAttrs::BLINK,
@@ -87,17 +115,23 @@ impl ParserState {
                 // Reverse
                 7 => self.attrs |= CellAttrs::REVERSE,
 
+                // Conceal
+                8 => self.attrs |= CellAttrs::HIDDEN,
+
                 // Strikethrough
                 9 => self.attrs |= CellAttrs::STRIKETHROUGH,
 
-                // Bold off
-                22 => self.attrs &= !CellAttrs::BOLD,
+                // Double underline
+                21 => self.set_underline_style(CellAttrs::DOUBLE_UNDERLINE),
+
+                // Normal intensity (bold and dim off)
+                22 => self.attrs &= !(CellAttrs::BOLD | CellAttrs::DIM),
 
                 // Italic off
                 23 => self.attrs &= !CellAttrs::ITALIC,
 
                 // Underline off
-                24 => self.attrs &= !CellAttrs::UNDERLINE,
+                24 => self.set_underline_style(CellAttrs::empty()),
 
                 // Blink off
                 25 => self.attrs &= !CellAttrs::BLINK,
@@ -105,49 +139,72 @@ impl ParserState {
                 // Reverse off
                 27 => self.attrs &= !CellAttrs::REVERSE,
 
+                // Conceal off
+                28 => self.attrs &= !CellAttrs::HIDDEN,
+
                 // Strikethrough off
                 29 => self.attrs &= !CellAttrs::STRIKETHROUGH,
 
                 // Standard foreground colors (30-37)
                 30..=37 => {
-                    self.fg_color = standard_color(params[i] - 30);
+                    self.fg_color = CellColor::Indexed((code - 30) as u8);
                 }
 
                 // Extended foreground color
                 38 => {
-                    if let Some(color) = parse_extended_color(&params[i..]) {
-                        self.fg_color = color.0;
-                        i += color.1; // Skip consumed parameters
+                    let (color, consumed) = extended_color_param(&params[i..]);
+                    if let Some(color) = color {
+                        self.fg_color = color;
                     }
+                    i += consumed;
                 }
 
                 // Default foreground
-                39 => self.fg_color = Color::DEFAULT_FG,
+                39 => self.fg_color = CellColor::Default,
 
                 // Standard background colors (40-47)
                 40..=47 => {
-                    self.bg_color = standard_color(params[i] - 40);
+                    self.bg_color = CellColor::Indexed((code - 40) as u8);
                 }
 
                 // Extended background color
                 48 => {
-                    if let Some(color) = parse_extended_color(&params[i..]) {
-                        self.bg_color = color.0;
-                        i += color.1; // Skip consumed parameters
+                    let (color, consumed) = extended_color_param(&params[i..]);
+                    if let Some(color) = color {
+                        self.bg_color = color;
                     }
+                    i += consumed;
                 }
 
                 // Default background
-                49 => self.bg_color = Color::DEFAULT_BG,
+                49 => self.bg_color = CellColor::Default,
+
+                // Overline
+                53 => self.attrs |= CellAttrs::OVERLINE,
+
+                // Overline off
+                55 => self.attrs &= !CellAttrs::OVERLINE,
+
+                // Underline color
+                58 => {
+                    let (color, consumed) = extended_color_param(&params[i..]);
+                    if color.is_some() {
+                        self.underline_color = color;
+                    }
+                    i += consumed;
+                }
+
+                // Default underline color
+                59 => self.underline_color = None,
 
                 // Bright foreground colors (90-97)
                 90..=97 => {
-                    self.fg_color = bright_color(params[i] - 90);
+                    self.fg_color = CellColor::Indexed((code - 90 + 8) as u8);
                 }
 
                 // Bright background colors (100-107)
                 100..=107 => {
-                    self.bg_color = bright_color(params[i] - 100);
+                    self.bg_color = CellColor::Indexed((code - 100 + 8) as u8);
                 }
 
                 // Unknown parameter
@@ -158,6 +215,11 @@ impl ParserState {
             i += 1;
         }
     }
+
+    /// Replace the underline style, keeping at most one style set
+    fn set_underline_style(&mut self, style: CellAttrs) {
+        self.attrs = (self.attrs - CellAttrs::ALL_UNDERLINES) | style;
+    }
 }
 
 /// VTE-based terminal parser
@@ -166,22 +228,59 @@ pub struct TerminalParser {
     parser: vte::Parser,
     /// Current parser state
     state: ParserState,
+    /// DCS sequence still being received, kept across `parse` calls
+    dcs: Option<DcsBuffer>,
 }
 
-/// Parser performer that collects actions
-struct ParsePerformer {
-    /// Current parser state
-    state: ParserState,
-    /// Buffer for collected actions
+/// Maximum number of parameters VTE keeps for one sequence
+const MAX_PARAMS: usize = 32;
+
+/// VTE performer that decodes sequences and hands them to a handler
+struct Performer<'a, H> {
+    /// Formatting tracked across sequences
+    state: &'a mut ParserState,
+    /// DCS sequence being collected
+    dcs: &'a mut Option<DcsBuffer>,
+    /// Receiver of the decoded events
+    handler: &'a mut H,
+}
+
+/// Handler behind `TerminalParser::parse` that collects actions in order
+#[derive(Default)]
+struct ActionCollector {
     actions: Vec<ParseAction>,
 }
 
-impl ParsePerformer {
-    fn new(state: ParserState) -> Self {
-        Self {
-            state,
-            actions: Vec::new(),
-        }
+impl TerminalHandler for ActionCollector {
+    fn print(&mut self, c: char) {
+        self.actions.push(ParseAction::Print(c));
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.actions.push(ParseAction::Execute(byte));
+    }
+
+    fn sgr(&mut self, params: &[&[u16]], _state: &ParserState) {
+        let groups = params.iter().map(|group| group.to_vec()).collect();
+        self.actions
+            .push(ParseAction::CsiDispatch(CsiAction::Sgr(groups)));
+    }
+
+    fn csi_dispatch(&mut self, action: CsiAction) {
+        self.actions.push(ParseAction::CsiDispatch(action));
+    }
+
+    fn esc_dispatch(&mut self, action: EscAction) {
+        self.actions.push(ParseAction::EscDispatch(action));
+    }
+
+    fn osc_dispatch(&mut self, action: OscAction, terminator: OscTerminator) {
+        self.actions
+            .push(ParseAction::OscDispatch(action, terminator));
+    }
+
+    fn dcs_dispatch(&mut self, action: DcsAction) {
+        self.actions.push(ParseAction::DcsDispatch(action));
     }
 }
 
@@ -191,19 +290,30 @@ impl TerminalParser {
         Self {
             parser: vte::Parser::new(),
             state: ParserState::default(),
+            dcs: None,
         }
     }
 
-    /// Parse a chunk of data and return the resulting actions
-    pub fn parse(&mut self, data: &[u8]) -> Vec<ParseAction> {
-        let mut performer = ParsePerformer::new(self.state.clone());
-
+    /// Parse a chunk of data, handing each decoded event to `handler` as it
+    /// is found
+    ///
+    /// Sequences split across chunks resume on the next call.
+    pub fn advance<H: TerminalHandler>(&mut self, data: &[u8], handler: &mut H) {
+        let mut performer = Performer {
+            state: &mut self.state,
+            dcs: &mut self.dcs,
+            handler,
+        };
         self.parser.advance(&mut performer, data);
+    }
 
-        // Update our state from the performer
-        self.state = performer.state;
-
-        performer.actions
+    /// Parse a chunk of data and return the resulting actions
+    ///
+    /// Collects what [`advance`](Self::advance) would hand to a handler.
+    pub fn parse(&mut self, data: &[u8]) -> Vec<ParseAction> {
+        let mut collector = ActionCollector::default();
+        self.advance(data, &mut collector);
+        collector.actions
     }
 
     /// Get the current parser state
@@ -223,170 +333,424 @@ impl Default for TerminalParser {
     }
 }
 
-impl Perform for ParsePerformer {
+impl<H: TerminalHandler> Performer<'_, H> {
+    /// Take over the formatting the handler restored with the cursor
+    ///
+    /// The saved cursor lives in the grid, one per screen buffer, so the
+    /// parser reads the result back rather than keeping a copy of its own.
+    fn sync_formatting(&mut self) {
+        if let Some(state) = self.handler.formatting() {
+            *self.state = state;
+        }
+    }
+}
+
+impl<H: TerminalHandler> Perform for Performer<'_, H> {
     fn print(&mut self, c: char) {
         trace!("Parser: print '{}'", c);
-        self.actions.push(ParseAction::Print(c));
+        self.handler.print(c);
     }
 
     fn execute(&mut self, byte: u8) {
         trace!("Parser: execute {:#x}", byte);
-        self.actions.push(ParseAction::Execute(byte));
+        self.handler.execute(byte);
     }
 
-    fn hook(&mut self, _params: &Params, _intermediates: &[u8], _ignore: bool, _c: char) {
-        // DCS sequences - not implemented yet
+    fn hook(&mut self, params: &Params, intermediates: &[u8], ignore: bool, c: char) {
+        trace!("Parser: DCS hook '{}'", c);
+        if ignore {
+            // Too many parameters or intermediates for VTE to keep
+            *self.dcs = None;
+            return;
+        }
+        let params_vec: Vec<u16> = params.iter().map(|p| p[0]).collect();
+        *self.dcs = Some(DcsBuffer::new(params_vec, intermediates, c));
     }
 
-    fn put(&mut self, _byte: u8) {
-        // DCS data - not implemented yet
+    fn put(&mut self, byte: u8) {
+        if let Some(dcs) = self.dcs.as_mut() {
+            dcs.push(byte);
+        }
     }
 
     fn unhook(&mut self) {
-        // End of DCS - not implemented yet
+        trace!("Parser: DCS unhook");
+        if let Some(action) = self.dcs.take().and_then(DcsBuffer::finish) {
+            self.handler.dcs_dispatch(action);
+        }
     }
 
-    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+    fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
         trace!("Parser: OSC dispatch with {} params", params.len());
-        let params_vec: Vec<Vec<u8>> = params.iter().map(|p| p.to_vec()).collect();
-        self.actions.push(ParseAction::OscDispatch(params_vec));
+        let terminator = if bell_terminated {
+            OscTerminator::Bel
+        } else {
+            OscTerminator::St
+        };
+        self.handler.osc_dispatch(parse_osc(params), terminator);
     }
 
-    fn csi_dispatch(&mut self, params: &Params, _intermediates: &[u8], _ignore: bool, c: char) {
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, c: char) {
         trace!("Parser: CSI dispatch '{}'", c);
 
-        let params_vec: Vec<u16> = params.iter().map(|p| p[0]).collect();
+        let mut values = [0u16; MAX_PARAMS];
+        let mut len = 0;
+        for (value, param) in values.iter_mut().zip(params.iter()) {
+            *value = param[0];
+            len += 1;
+        }
+        let values = &values[..len];
+
+        let private = intermediates == b"?";
+        if (matches!(c, 'h' | 'l') && (intermediates.is_empty() || private))
+            || (private && matches!(c, 's' | 'r'))
+        {
+            // SM/RM, DECSET/DECRST and XTSAVE/XTRESTORE - one action per mode
+            for &code in values {
+                let mode = if private {
+                    Mode::Dec(DecMode::from_code(code))
+                } else {
+                    Mode::Ansi(AnsiMode::from_code(code))
+                };
+                let action = match c {
+                    'h' => ModeAction::Set(mode),
+                    'l' => ModeAction::Reset(mode),
+                    's' => ModeAction::Save(mode),
+                    _ => ModeAction::Restore(mode),
+                };
+                self.handler.csi_dispatch(CsiAction::Mode(action));
+                if matches!(
+                    mode,
+                    Mode::Dec(DecMode::SaveCursor | DecMode::AltScreenSaveCursor)
+                ) {
+                    // Leaving these modes may have restored the cursor's formatting
+                    self.sync_formatting();
+                }
+            }
+            return;
+        }
+
+        if intermediates == b"!" && c == 'p' {
+            // DECSTR - Soft Terminal Reset
+            *self.state = ParserState::default();
+            self.handler.csi_dispatch(CsiAction::SoftReset);
+            return;
+        }
+
+        if intermediates.is_empty() && c == 't' && matches!(values.first(), Some(22 | 23)) {
+            // XTPUSHTITLE / XTPOPTITLE; only the window title is tracked, so
+            // icon-only requests (second parameter 1) fall through
+            if matches!(values.get(1).copied().unwrap_or(0), 0 | 2) {
+                let action = if values[0] == 22 {
+                    CsiAction::PushTitle
+                } else {
+                    CsiAction::PopTitle
+                };
+                self.handler.csi_dispatch(action);
+                return;
+            }
+        }
+
+        if let Some(query) = decode_query(intermediates, c, values) {
+            self.handler.csi_dispatch(CsiAction::Query(query));
+            return;
+        }
+
+        if private && matches!(c, 'J' | 'K') {
+            // DECSED/DECSEL - Selective Erase in Display / Line
+            let mode = values.first().copied().unwrap_or(0);
+            let action = if c == 'J' {
+                DisplayErase::from_param(mode).map(CsiAction::SelectiveEraseInDisplay)
+            } else {
+                LineErase::from_param(mode).map(CsiAction::SelectiveEraseInLine)
+            };
+            if let Some(action) = action {
+                self.handler.csi_dispatch(action);
+                return;
+            }
+        }
+
+        if intermediates == b"\"" && c == 'q' {
+            // DECSCA - Select Character Protection Attribute; 0 and 2 clear it
+            let protected = values.first() == Some(&1);
+            self.handler
+                .csi_dispatch(CsiAction::SetCharProtection(protected));
+            return;
+        }
+
+        if intermediates == b" " && c == 'q' {
+            // DECSCUSR - Set Cursor Style; 0 restores the default
+            let action = match values.first().copied().unwrap_or(0) {
+                0 => Some(CsiAction::SetCursorStyle(None)),
+                n => CursorStyle::from_param(n).map(|style| CsiAction::SetCursorStyle(Some(style))),
+            };
+            if let Some(action) = action {
+                self.handler.csi_dispatch(action);
+                return;
+            }
+        }
+
+        if !intermediates.is_empty() {
+            // Private and intermediate variants are not typed yet
+            self.handler.csi_dispatch(CsiAction::Other {
+                command: c,
+                params: values.to_vec(),
+            });
+            return;
+        }
 
         match c {
             'm' => {
-                // SGR - Select Graphic Rendition
-                self.state.apply_sgr(&params_vec);
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::Sgr(params_vec)));
+                // SGR - Select Graphic Rendition, keeping colon subparameters
+                let mut groups: [&[u16]; MAX_PARAMS] = [&[]; MAX_PARAMS];
+                let mut len = 0;
+                for (slot, group) in groups.iter_mut().zip(params.iter()) {
+                    *slot = group;
+                    len += 1;
+                }
+                let groups = &groups[..len];
+                self.state.apply_sgr(groups);
+                self.handler.sgr(groups, self.state);
             }
             'A' => {
                 // CUU - Cursor Up
-                let lines = params_vec.first().copied().unwrap_or(0);
+                let lines = values.first().copied().unwrap_or(0);
                 let lines = if lines == 0 { 1 } else { lines }; // Default to 1 if not specified
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::CursorUp(lines)));
+                self.handler.csi_dispatch(CsiAction::CursorUp(lines));
             }
             'B' => {
                 // CUD - Cursor Down
-                let lines = params_vec.first().copied().unwrap_or(0);
+                let lines = values.first().copied().unwrap_or(0);
                 let lines = if lines == 0 { 1 } else { lines };
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::CursorDown(lines)));
+                self.handler.csi_dispatch(CsiAction::CursorDown(lines));
             }
             'C' => {
                 // CUF - Cursor Forward
-                let cols = params_vec.first().copied().unwrap_or(0);
+                let cols = values.first().copied().unwrap_or(0);
                 let cols = if cols == 0 { 1 } else { cols };
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::CursorForward(cols)));
+                self.handler.csi_dispatch(CsiAction::CursorForward(cols));
             }
             'D' => {
                 // CUB - Cursor Backward
-                let cols = params_vec.first().copied().unwrap_or(0);
+                let cols = values.first().copied().unwrap_or(0);
                 let cols = if cols == 0 { 1 } else { cols };
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::CursorBackward(cols)));
+                self.handler.csi_dispatch(CsiAction::CursorBackward(cols));
             }
             'E' => {
                 // CNL - Cursor Next Line
-                let lines = params_vec.first().copied().unwrap_or(0);
+                let lines = values.first().copied().unwrap_or(0);
                 let lines = if lines == 0 { 1 } else { lines };
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::CursorNextLine(lines)));
+                self.handler.csi_dispatch(CsiAction::CursorNextLine(lines));
             }
             'F' => {
                 // CPL - Cursor Previous Line
-                let lines = params_vec.first().copied().unwrap_or(0);
+                let lines = values.first().copied().unwrap_or(0);
                 let lines = if lines == 0 { 1 } else { lines };
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::CursorPreviousLine(
-                        lines,
-                    )));
-            }
-            'G' => {
-                // CHA - Cursor Horizontal Absolute
-                let col = params_vec.first().copied().unwrap_or(1);
-                self.actions.push(ParseAction::CsiDispatch(
-                    CsiAction::CursorHorizontalAbsolute(col.saturating_sub(1)),
-                ));
+                self.handler
+                    .csi_dispatch(CsiAction::CursorPreviousLine(lines));
+            }
+            'G' | '`' => {
+                // CHA/HPA - Cursor Horizontal Absolute
+                let col = values.first().copied().unwrap_or(1);
+                self.handler
+                    .csi_dispatch(CsiAction::CursorHorizontalAbsolute(col.saturating_sub(1)));
             }
             'H' | 'f' => {
                 // CUP/HVP - Cursor Position
-                let row = params_vec.first().copied().unwrap_or(1);
-                let col = params_vec.get(1).copied().unwrap_or(1);
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::CursorPosition(
-                        row.saturating_sub(1),
-                        col.saturating_sub(1),
-                    )));
+                let row = values.first().copied().unwrap_or(1);
+                let col = values.get(1).copied().unwrap_or(1);
+                self.handler.csi_dispatch(CsiAction::CursorPosition(
+                    row.saturating_sub(1),
+                    col.saturating_sub(1),
+                ));
+            }
+            'd' => {
+                // VPA - Line Position Absolute
+                let row = values.first().copied().unwrap_or(1);
+                self.handler
+                    .csi_dispatch(CsiAction::LinePositionAbsolute(row.saturating_sub(1)));
+            }
+            'I' => {
+                // CHT - Cursor Forward Tabulation
+                self.handler
+                    .csi_dispatch(CsiAction::CursorForwardTab(count_param(values)));
+            }
+            'Z' => {
+                // CBT - Cursor Backward Tabulation
+                self.handler
+                    .csi_dispatch(CsiAction::CursorBackwardTab(count_param(values)));
+            }
+            'g' => {
+                // TBC - Tab Clear
+                let mode = values.first().copied().unwrap_or(0);
+                match TabClear::from_param(mode) {
+                    Some(clear) => self.handler.csi_dispatch(CsiAction::TabClear(clear)),
+                    None => {
+                        debug!("Unknown tab clear mode {}", mode);
+                        self.handler.csi_dispatch(CsiAction::Other {
+                            command: c,
+                            params: values.to_vec(),
+                        });
+                    }
+                }
+            }
+            'J' | 'K' => {
+                // ED/EL - Erase in Display / Erase in Line
+                let mode = values.first().copied().unwrap_or(0);
+                let action = if c == 'J' {
+                    DisplayErase::from_param(mode).map(CsiAction::EraseInDisplay)
+                } else {
+                    LineErase::from_param(mode).map(CsiAction::EraseInLine)
+                };
+                match action {
+                    Some(action) => self.handler.csi_dispatch(action),
+                    None => {
+                        debug!("Unknown erase mode {} for CSI '{}'", mode, c);
+                        self.handler.csi_dispatch(CsiAction::Other {
+                            command: c,
+                            params: values.to_vec(),
+                        });
+                    }
+                }
+            }
+            'r' => {
+                // DECSTBM - Set Top and Bottom Margins
+                let top = values.first().copied().unwrap_or(1).saturating_sub(1);
+                let bottom = values
+                    .get(1)
+                    .copied()
+                    .filter(|&bottom| bottom > 0)
+                    .map(|bottom| bottom - 1);
+                self.handler
+                    .csi_dispatch(CsiAction::SetScrollRegion { top, bottom });
+            }
+            '@' | 'P' | 'X' | 'L' | 'M' | 'S' | 'T' | 'b' => {
+                // ICH, DCH, ECH, IL, DL, SU, SD, REP - all take a count
+                let count = count_param(values);
+                let action = match c {
+                    '@' => CsiAction::InsertChars(count),
+                    'P' => CsiAction::DeleteChars(count),
+                    'X' => CsiAction::EraseChars(count),
+                    'L' => CsiAction::InsertLines(count),
+                    'M' => CsiAction::DeleteLines(count),
+                    'S' => CsiAction::ScrollUp(count),
+                    'T' => CsiAction::ScrollDown(count),
+                    _ => CsiAction::RepeatChar(count),
+                };
+                self.handler.csi_dispatch(action);
             }
             _ => {
                 // Other CSI commands
-                self.actions
-                    .push(ParseAction::CsiDispatch(CsiAction::Other {
-                        command: c,
-                        params: params_vec,
-                    }));
+                self.handler.csi_dispatch(CsiAction::Other {
+                    command: c,
+                    params: values.to_vec(),
+                });
             }
         }
     }
 
-    fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, byte: u8) {
+    fn esc_dispatch(&mut self, intermediates: &[u8], _ignore: bool, byte: u8) {
         trace!("Parser: ESC dispatch {:#x}", byte);
 
-        match byte {
-            b'c' => {
+        let action = match (intermediates, byte) {
+            // DECDHL, DECSWL, DECDWL
+            (b"#", _) => LineSize::from_final(byte)
+                .map_or(EscAction::Other(byte as char), EscAction::LineSize),
+            (&[intermediate], _) => {
+                // SCS - Select Character Set
+                match (
+                    CharsetSlot::from_intermediate(intermediate),
+                    Charset::from_final(byte),
+                ) {
+                    (Some(slot), Some(charset)) => EscAction::DesignateCharset(slot, charset),
+                    _ => EscAction::Other(byte as char),
+                }
+            }
+            ([_, ..], _) => EscAction::Other(byte as char),
+            (_, b'7') => EscAction::SaveCursor,
+            (_, b'8') => EscAction::RestoreCursor,
+            (_, b'D') => EscAction::Index,
+            (_, b'E') => EscAction::NextLine,
+            (_, b'M') => EscAction::ReverseIndex,
+            (_, b'H') => EscAction::HorizontalTabSet,
+            (_, b'=') => EscAction::ApplicationKeypad,
+            (_, b'>') => EscAction::NumericKeypad,
+            (_, b'n') => EscAction::LockingShift(CharsetSlot::G2),
+            (_, b'o') => EscAction::LockingShift(CharsetSlot::G3),
+            (_, b'c') => {
                 // RIS - Reset to Initial State
-                self.state.reset();
-                self.actions
-                    .push(ParseAction::EscDispatch(EscAction::Reset));
+                *self.state = ParserState::default();
+                EscAction::Reset
             }
-            _ => {
-                self.actions
-                    .push(ParseAction::EscDispatch(EscAction::Other(byte as char)));
+            (_, b'\\') => {
+                // ST - String Terminator, already consumed by the OSC/DCS dispatch
+                return;
             }
+            _ => EscAction::Other(byte as char),
+        };
+        self.handler.esc_dispatch(action);
+        if action == EscAction::RestoreCursor {
+            self.sync_formatting();
         }
     }
 }
 
-/// Convert a standard color index (0-7) to a Color
-fn standard_color(index: u16) -> Color {
-    match index {
-        0 => Color::rgb(0, 0, 0),       // Black
-        1 => Color::rgb(128, 0, 0),     // Red
-        2 => Color::rgb(0, 128, 0),     // Green
-        3 => Color::rgb(128, 128, 0),   // Yellow
-        4 => Color::rgb(0, 0, 128),     // Blue
-        5 => Color::rgb(128, 0, 128),   // Magenta
-        6 => Color::rgb(0, 128, 128),   // Cyan
-        7 => Color::rgb(192, 192, 192), // White
-        _ => Color::DEFAULT_FG,
+/// Recognise CSI sequences that ask the terminal to report its state
+fn decode_query(intermediates: &[u8], c: char, params: &[u16]) -> Option<TerminalQuery> {
+    let first = params.first().copied().unwrap_or(0);
+    match (intermediates, c) {
+        (b"", 'c') if first == 0 => Some(TerminalQuery::PrimaryDeviceAttributes),
+        (b">", 'c') if first == 0 => Some(TerminalQuery::SecondaryDeviceAttributes),
+        (b"", 'n') if first == 5 => Some(TerminalQuery::OperatingStatus),
+        (b"", 'n') if first == 6 => Some(TerminalQuery::CursorPosition { private: false }),
+        (b"?", 'n') if first == 6 => Some(TerminalQuery::CursorPosition { private: true }),
+        (b"$", 'p') => Some(TerminalQuery::Mode(Mode::Ansi(AnsiMode::from_code(first)))),
+        (b"?$", 'p') => Some(TerminalQuery::Mode(Mode::Dec(DecMode::from_code(first)))),
+        (b">", 'q') if first == 0 => Some(TerminalQuery::Version),
+        _ => None,
+    }
+}
+
+/// Read a count parameter, treating a missing or zero value as 1
+fn count_param(params: &[u16]) -> u16 {
+    match params.first().copied().unwrap_or(0) {
+        0 => 1,
+        n => n,
     }
 }
 
-/// Convert a bright color index (0-7) to a Color
-fn bright_color(index: u16) -> Color {
-    match index {
-        0 => Color::rgb(128, 128, 128), // Bright Black (Gray)
-        1 => Color::rgb(255, 0, 0),     // Bright Red
-        2 => Color::rgb(0, 255, 0),     // Bright Green
-        3 => Color::rgb(255, 255, 0),   // Bright Yellow
-        4 => Color::rgb(0, 0, 255),     // Bright Blue
-        5 => Color::rgb(255, 0, 255),   // Bright Magenta
-        6 => Color::rgb(0, 255, 255),   // Bright Cyan
-        7 => Color::rgb(255, 255, 255), // Bright White
-        _ => Color::DEFAULT_FG,
+/// Decode an extended color (SGR 38/48/58) starting at `params[0]`
+///
+/// Handles both the colon form (`38:2::r:g:b`, `38:5:n`), where everything is
+/// in one group, and the legacy semicolon form (`38;2;r;g;b`), where the
+/// values are spread over the following groups. Returns the color, if valid,
+/// and the number of extra groups consumed.
+fn extended_color_param<P: AsRef<[u16]>>(params: &[P]) -> (Option<CellColor>, usize) {
+    let group = params[0].as_ref();
+    if group.len() > 1 {
+        let color = match group[1] {
+            // 38:2:Pi:r:g:b with a color space id, or 38:2:r:g:b without
+            2 if group.len() >= 6 => Some(rgb_color(group[3], group[4], group[5])),
+            2 if group.len() == 5 => Some(rgb_color(group[2], group[3], group[4])),
+            5 if group.len() >= 3 => Some(indexed_color(group[2])),
+            _ => None,
+        };
+        return (color, 0);
+    }
+
+    let mut flat = [0u16; 5];
+    for (value, group) in flat.iter_mut().zip(params) {
+        *value = group.as_ref().first().copied().unwrap_or(0);
+    }
+    match parse_extended_color(&flat[..params.len().min(flat.len())]) {
+        Some((color, consumed)) => (Some(color), consumed),
+        None => (None, 0),
     }
 }
 
 /// Parse extended color sequences (256-color or RGB)
-/// Returns (Color, consumed_params_count) or None if invalid
-fn parse_extended_color(params: &[u16]) -> Option<(Color, usize)> {
+/// Returns (CellColor, consumed_params_count) or None if invalid
+fn parse_extended_color(params: &[u16]) -> Option<(CellColor, usize)> {
     if params.len() < 2 {
         return None;
     }
@@ -397,8 +761,7 @@ fn parse_extended_color(params: &[u16]) -> Option<(Color, usize)> {
             if params.len() < 3 {
                 return None;
             }
-            let color_index = params[2];
-            Some((color_256(color_index), 2))
+            Some((indexed_color(params[2]), 2))
         }
 
         // RGB mode
@@ -406,51 +769,34 @@ fn parse_extended_color(params: &[u16]) -> Option<(Color, usize)> {
             if params.len() < 5 {
                 return None;
             }
-            let r = params[2] as u8;
-            let g = params[3] as u8;
-            let b = params[4] as u8;
-            Some((Color::rgb(r, g, b), 4))
+            Some((rgb_color(params[2], params[3], params[4]), 4))
         }
 
         _ => None,
     }
 }
 
-/// Convert a 256-color palette index to a Color
-fn color_256(index: u16) -> Color {
-    match index {
-        // Standard colors (0-15)
-        0..=7 => standard_color(index),
-        8..=15 => bright_color(index - 8),
-
-        // 216 color cube (16-231)
-        16..=231 => {
-            let index = index - 16;
-            let r = (index / 36) * 51;
-            let g = ((index % 36) / 6) * 51;
-            let b = (index % 6) * 51;
-            Color::rgb(r as u8, g as u8, b as u8)
-        }
-
-        // Grayscale ramp (232-255)
-        232..=255 => {
-            let gray = 8 + (index - 232) * 10;
-            Color::rgb(gray as u8, gray as u8, gray as u8)
-        }
+/// A palette color (`38;5;n`); indices past the palette select the default
+fn indexed_color(index: u16) -> CellColor {
+    u8::try_from(index).map_or(CellColor::Default, CellColor::Indexed)
+}
 
-        _ => Color::DEFAULT_FG,
-    }
+/// A direct color (`38;2;r;g;b`)
+fn rgb_color(r: u16, g: u16, b: u16) -> CellColor {
+    CellColor::Rgb(Color::rgb(r as u8, g as u8, b as u8))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dcs::{StatusRequest, MAX_DCS_BYTES};
+    use quantaterm_blocks::TerminalGrid;
 
     #[test]
     fn test_parser_creation() {
         let parser = TerminalParser::new();
-        assert_eq!(parser.state.fg_color, Color::DEFAULT_FG);
-        assert_eq!(parser.state.bg_color, Color::DEFAULT_BG);
+        assert_eq!(parser.state.fg_color, CellColor::Default);
+        assert_eq!(parser.state.bg_color, CellColor::Default);
         assert!(parser.state.attrs.is_empty());
     }
 
@@ -566,6 +912,426 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_erase_parsing() {
+        let mut parser = TerminalParser::new();
+
+        let cases: &[(&[u8], CsiAction)] = &[
+            (b"\x1b[J", CsiAction::EraseInDisplay(DisplayErase::Below)),
+            (b"\x1b[1J", CsiAction::EraseInDisplay(DisplayErase::Above)),
+            (b"\x1b[2J", CsiAction::EraseInDisplay(DisplayErase::All)),
+            (
+                b"\x1b[3J",
+                CsiAction::EraseInDisplay(DisplayErase::Scrollback),
+            ),
+            (b"\x1b[K", CsiAction::EraseInLine(LineErase::Right)),
+            (b"\x1b[1K", CsiAction::EraseInLine(LineErase::Left)),
+            (b"\x1b[2K", CsiAction::EraseInLine(LineErase::All)),
+            (
+                b"\x1b[?J",
+                CsiAction::SelectiveEraseInDisplay(DisplayErase::Below),
+            ),
+            (b"\x1b[?2K", CsiAction::SelectiveEraseInLine(LineErase::All)),
+            (b"\x1b[1\"q", CsiAction::SetCharProtection(true)),
+            (b"\x1b[\"q", CsiAction::SetCharProtection(false)),
+            (b"\x1b[2\"q", CsiAction::SetCharProtection(false)),
+            (b"\x1b[ q", CsiAction::SetCursorStyle(None)),
+            (
+                b"\x1b[6 q",
+                CsiAction::SetCursorStyle(Some(CursorStyle {
+                    shape: CursorShape::Bar,
+                    blinking: false,
+                })),
+            ),
+            (
+                b"\x1b[3 q",
+                CsiAction::SetCursorStyle(Some(CursorStyle {
+                    shape: CursorShape::Underline,
+                    blinking: true,
+                })),
+            ),
+        ];
+
+        for (sequence, expected) in cases {
+            let actions = parser.parse(sequence);
+            assert_eq!(actions.len(), 1);
+            match &actions[0] {
+                ParseAction::CsiDispatch(action) => assert_eq!(action, expected),
+                other => panic!("Expected CSI action, got: {:?}", other),
+            }
+        }
+
+        // Unknown erase modes are passed through untyped
+        let actions = parser.parse(b"\x1b[5J");
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::Other { command: 'J', .. })
+        ));
+    }
+
+    #[test]
+    fn test_edit_and_scroll_parsing() {
+        let mut parser = TerminalParser::new();
+
+        let cases: &[(&[u8], CsiAction)] = &[
+            (b"\x1b[@", CsiAction::InsertChars(1)),
+            (b"\x1b[4@", CsiAction::InsertChars(4)),
+            (b"\x1b[2P", CsiAction::DeleteChars(2)),
+            (b"\x1b[0X", CsiAction::EraseChars(1)),
+            (b"\x1b[3L", CsiAction::InsertLines(3)),
+            (b"\x1b[M", CsiAction::DeleteLines(1)),
+            (b"\x1b[5S", CsiAction::ScrollUp(5)),
+            (b"\x1b[T", CsiAction::ScrollDown(1)),
+            (b"\x1b[7d", CsiAction::LinePositionAbsolute(6)),
+            (b"\x1b[2I", CsiAction::CursorForwardTab(2)),
+            (b"\x1b[Z", CsiAction::CursorBackwardTab(1)),
+            (b"\x1b[g", CsiAction::TabClear(TabClear::Current)),
+            (b"\x1b[3g", CsiAction::TabClear(TabClear::All)),
+            (b"\x1b[9b", CsiAction::RepeatChar(9)),
+            (b"\x1b[12`", CsiAction::CursorHorizontalAbsolute(11)),
+        ];
+
+        for (sequence, expected) in cases {
+            let actions = parser.parse(sequence);
+            assert_eq!(actions.len(), 1, "sequence {:?}", sequence);
+            match &actions[0] {
+                ParseAction::CsiDispatch(action) => assert_eq!(action, expected),
+                other => panic!("Expected CSI action, got: {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mode_parsing() {
+        let mut parser = TerminalParser::new();
+
+        let actions = parser.parse(b"\x1b[?1049h");
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::Mode(ModeAction::Set(Mode::Dec(
+                DecMode::AltScreenSaveCursor
+            ))))
+        ));
+
+        // Multiple modes in one sequence produce one action each
+        let actions = parser.parse(b"\x1b[?25;7;2004l");
+        let modes: Vec<_> = actions
+            .iter()
+            .map(|action| match action {
+                ParseAction::CsiDispatch(CsiAction::Mode(ModeAction::Reset(mode))) => *mode,
+                other => panic!("Expected mode reset, got: {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            modes,
+            vec![
+                Mode::Dec(DecMode::ShowCursor),
+                Mode::Dec(DecMode::Autowrap),
+                Mode::Dec(DecMode::BracketedPaste),
+            ]
+        );
+
+        // ANSI modes have no private marker
+        let actions = parser.parse(b"\x1b[4h");
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::Mode(ModeAction::Set(Mode::Ansi(
+                AnsiMode::Insert
+            ))))
+        ));
+
+        // XTSAVE / XTRESTORE
+        let actions = parser.parse(b"\x1b[?1s\x1b[?1r");
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::Mode(ModeAction::Save(Mode::Dec(
+                DecMode::CursorKeys
+            ))))
+        ));
+        assert!(matches!(
+            actions[1],
+            ParseAction::CsiDispatch(CsiAction::Mode(ModeAction::Restore(Mode::Dec(
+                DecMode::CursorKeys
+            ))))
+        ));
+
+        // Unknown modes are kept with their number
+        let actions = parser.parse(b"\x1b[?9999h");
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::Mode(ModeAction::Set(Mode::Dec(
+                DecMode::Unknown(9999)
+            ))))
+        ));
+
+        // Other intermediates are still passed through untyped
+        let actions = parser.parse(b"\x1b[>1h");
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::Other { command: 'h', .. })
+        ));
+    }
+
+    #[test]
+    fn test_alt_screen_restores_formatting() {
+        let mut parser = TerminalParser::new();
+        let mut grid = TerminalGrid::new(10, 3);
+        parser.advance(b"\x1b[1;31m\x1b[?1049h\x1b[0;44m", &mut grid);
+        assert!(parser.state.attrs.is_empty());
+
+        // The grid saved the formatting with the cursor; the parser reads it back
+        parser.advance(b"\x1b[?1049l", &mut grid);
+        assert!(parser.state.attrs.contains(CellAttrs::BOLD));
+        assert_eq!(parser.state.fg_color, CellColor::Indexed(1));
+        assert_eq!(parser.state.bg_color, CellColor::Default);
+    }
+
+    #[test]
+    fn test_scroll_region_and_index_parsing() {
+        let mut parser = TerminalParser::new();
+
+        let actions = parser.parse(b"\x1b[5;20r");
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::SetScrollRegion {
+                top: 4,
+                bottom: Some(19)
+            })
+        ));
+
+        // Missing parameters reset to the full screen
+        let actions = parser.parse(b"\x1b[r");
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::SetScrollRegion {
+                top: 0,
+                bottom: None
+            })
+        ));
+
+        let actions = parser.parse(b"\x1bD\x1bE\x1bM");
+        assert!(matches!(
+            actions[..],
+            [
+                ParseAction::EscDispatch(EscAction::Index),
+                ParseAction::EscDispatch(EscAction::NextLine),
+                ParseAction::EscDispatch(EscAction::ReverseIndex),
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_esc_parsing() {
+        let mut parser = TerminalParser::new();
+
+        let actions =
+            parser.parse(b"\x1b(0\x1b)B\x1b+A\x1b(Z\x1bn\x1bH\x1b=\x1b>\x1b#8\x1b#6\x1b#3");
+        let escs: Vec<EscAction> = actions
+            .iter()
+            .map(|action| match action {
+                ParseAction::EscDispatch(esc) => *esc,
+                other => panic!("Expected ESC action, got: {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            escs,
+            vec![
+                EscAction::DesignateCharset(CharsetSlot::G0, Charset::DecSpecialGraphics),
+                EscAction::DesignateCharset(CharsetSlot::G1, Charset::Ascii),
+                EscAction::DesignateCharset(CharsetSlot::G3, Charset::Uk),
+                EscAction::Other('Z'),
+                EscAction::LockingShift(CharsetSlot::G2),
+                EscAction::HorizontalTabSet,
+                EscAction::ApplicationKeypad,
+                EscAction::NumericKeypad,
+                EscAction::Other('8'),
+                EscAction::LineSize(LineSize::DoubleWidth),
+                EscAction::LineSize(LineSize::DoubleHeightTop),
+            ]
+        );
+
+        let actions = parser.parse(b"\x1b7\x1b8");
+        assert!(matches!(
+            actions[..],
+            [
+                ParseAction::EscDispatch(EscAction::SaveCursor),
+                ParseAction::EscDispatch(EscAction::RestoreCursor)
+            ]
+        ));
+
+        // DECSC/DECRC carry the formatting along with the cursor
+        let mut grid = TerminalGrid::new(10, 3);
+        parser.advance(b"\x1b[1;31m\x1b7\x1b[0m", &mut grid);
+        assert_eq!(parser.state().attrs, CellAttrs::empty());
+        parser.advance(b"\x1b8", &mut grid);
+        assert_eq!(parser.state().attrs, CellAttrs::BOLD);
+        assert_eq!(parser.state().fg_color, CellColor::Indexed(1));
+    }
+
+    #[test]
+    fn test_title_stack_parsing() {
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1b[22;0t\x1b[23t\x1b[22;1t");
+        assert!(matches!(
+            actions[..],
+            [
+                ParseAction::CsiDispatch(CsiAction::PushTitle),
+                ParseAction::CsiDispatch(CsiAction::PopTitle),
+                ParseAction::CsiDispatch(CsiAction::Other { command: 't', .. }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_reset_parsing() {
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1b[!p\x1bc");
+        assert!(matches!(
+            actions[..],
+            [
+                ParseAction::CsiDispatch(CsiAction::SoftReset),
+                ParseAction::EscDispatch(EscAction::Reset),
+            ]
+        ));
+
+        // Both resets drop the current and the saved formatting
+        let mut grid = TerminalGrid::new(10, 3);
+        parser.advance(b"\x1b[1m\x1b7\x1b[3m\x1b[!p", &mut grid);
+        assert_eq!(parser.state().attrs, CellAttrs::empty());
+        parser.advance(b"\x1b8", &mut grid);
+        assert_eq!(parser.state().attrs, CellAttrs::empty());
+
+        parser.advance(b"\x1b[1m\x1b7\x1bc", &mut grid);
+        assert_eq!(parser.state().attrs, CellAttrs::empty());
+        parser.advance(b"\x1b8", &mut grid);
+        assert_eq!(parser.state().attrs, CellAttrs::empty());
+    }
+
+    #[test]
+    fn test_osc_parsing() {
+        let mut parser = TerminalParser::new();
+
+        let actions = parser.parse(b"\x1b]2;hello; world\x07");
+        assert_eq!(actions.len(), 1);
+        match &actions[0] {
+            ParseAction::OscDispatch(OscAction::SetTitle { title, .. }, terminator) => {
+                assert_eq!(title, "hello; world");
+                assert_eq!(*terminator, OscTerminator::Bel);
+            }
+            other => panic!("Expected title action, got: {:?}", other),
+        }
+
+        let actions = parser.parse(b"\x1b]133;A\x1b\\");
+        assert!(matches!(
+            actions[0],
+            ParseAction::OscDispatch(OscAction::SemanticPrompt(_), OscTerminator::St)
+        ));
+    }
+
+    #[test]
+    fn test_dcs_parsing() {
+        let mut parser = TerminalParser::new();
+
+        let actions = parser.parse(b"\x1bP$qm\x1b\\");
+        assert!(matches!(
+            actions[..],
+            [ParseAction::DcsDispatch(DcsAction::RequestStatus(
+                StatusRequest::Sgr
+            ))]
+        ));
+
+        let actions = parser.parse(b"\x1bP=1s\x1b\\a\x1bP=2s\x1b\\");
+        assert!(matches!(
+            actions[..],
+            [
+                ParseAction::DcsDispatch(DcsAction::SynchronizedUpdate(true)),
+                ParseAction::Print('a'),
+                ParseAction::DcsDispatch(DcsAction::SynchronizedUpdate(false)),
+            ]
+        ));
+
+        // A payload split across reads is reassembled
+        assert!(parser.parse(b"\x1bP+q544E;43").is_empty());
+        let actions = parser.parse(b"6F\x1b\\x");
+        match &actions[..] {
+            [ParseAction::DcsDispatch(DcsAction::RequestTermcap(names)), ParseAction::Print('x')] =>
+            {
+                assert_eq!(names, &["TN", "Co"])
+            }
+            other => panic!("Expected termcap request, got: {:?}", other),
+        }
+
+        // Unrecognised sequences keep their raw contents
+        let actions = parser.parse(b"\x1bP0;1q#0;2;0;0;0\x1b\\");
+        match &actions[..] {
+            [ParseAction::DcsDispatch(DcsAction::Other {
+                params,
+                command,
+                data,
+                ..
+            })] => {
+                assert_eq!(params, &[0, 1]);
+                assert_eq!(*command, 'q');
+                assert_eq!(data, b"#0;2;0;0;0");
+            }
+            other => panic!("Expected raw DCS, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_dcs_size_cap() {
+        let mut parser = TerminalParser::new();
+        let mut input = b"\x1bPq".to_vec();
+        input.resize(input.len() + MAX_DCS_BYTES + 1, b'~');
+        input.extend_from_slice(b"\x1b\\ok");
+
+        let actions = parser.parse(&input);
+        assert!(matches!(
+            actions[..],
+            [ParseAction::Print('o'), ParseAction::Print('k')]
+        ));
+    }
+
+    #[test]
+    fn test_query_parsing() {
+        let mut parser = TerminalParser::new();
+
+        let cases: &[(&[u8], TerminalQuery)] = &[
+            (b"\x1b[c", TerminalQuery::PrimaryDeviceAttributes),
+            (b"\x1b[0c", TerminalQuery::PrimaryDeviceAttributes),
+            (b"\x1b[>c", TerminalQuery::SecondaryDeviceAttributes),
+            (b"\x1b[5n", TerminalQuery::OperatingStatus),
+            (b"\x1b[6n", TerminalQuery::CursorPosition { private: false }),
+            (b"\x1b[?6n", TerminalQuery::CursorPosition { private: true }),
+            (
+                b"\x1b[4$p",
+                TerminalQuery::Mode(Mode::Ansi(AnsiMode::Insert)),
+            ),
+            (
+                b"\x1b[?2004$p",
+                TerminalQuery::Mode(Mode::Dec(DecMode::BracketedPaste)),
+            ),
+            (b"\x1b[>q", TerminalQuery::Version),
+        ];
+
+        for (sequence, expected) in cases {
+            let actions = parser.parse(sequence);
+            assert_eq!(actions.len(), 1, "sequence {:?}", sequence);
+            match &actions[0] {
+                ParseAction::CsiDispatch(CsiAction::Query(query)) => assert_eq!(query, expected),
+                other => panic!("Expected query, got: {:?}", other),
+            }
+        }
+
+        // Unsupported report numbers are not treated as queries
+        let actions = parser.parse(b"\x1b[7n");
+        assert!(matches!(
+            actions[0],
+            ParseAction::CsiDispatch(CsiAction::Other { command: 'n', .. })
+        ));
+    }
+
     #[test]
     fn test_sgr_bold() {
         let mut parser = TerminalParser::new();
@@ -573,7 +1339,7 @@ mod tests {
 
         // Should have SGR action followed by Print actions
         let sgr_found = actions.iter().any(|action| {
-            matches!(action, ParseAction::CsiDispatch(CsiAction::Sgr(params)) if params == &[1])
+            matches!(action, ParseAction::CsiDispatch(CsiAction::Sgr(params)) if params == &[vec![1]])
         });
         assert!(sgr_found);
         assert!(parser.state.attrs.contains(CellAttrs::BOLD));
@@ -585,10 +1351,10 @@ mod tests {
         let actions = parser.parse(b"\x1b[31m"); // Red foreground
 
         let sgr_found = actions.iter().any(|action| {
-            matches!(action, ParseAction::CsiDispatch(CsiAction::Sgr(params)) if params == &[31])
+            matches!(action, ParseAction::CsiDispatch(CsiAction::Sgr(params)) if params == &[vec![31]])
         });
         assert!(sgr_found);
-        assert_eq!(parser.state.fg_color, standard_color(1)); // Red
+        assert_eq!(parser.state.fg_color, CellColor::Indexed(1)); // Red
     }
 
     #[test]
@@ -601,22 +1367,128 @@ mod tests {
         // Reset
         parser.parse(b"\x1b[0m");
         assert!(!parser.state.attrs.contains(CellAttrs::BOLD));
-        assert_eq!(parser.state.fg_color, Color::DEFAULT_FG);
+        assert_eq!(parser.state.fg_color, CellColor::Default);
+    }
+
+    #[test]
+    fn test_sgr_subparameters_are_kept() {
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1b[1;4:3;38:2::10:20:30m");
+
+        match &actions[0] {
+            ParseAction::CsiDispatch(CsiAction::Sgr(params)) => {
+                assert_eq!(params, &[vec![1], vec![4, 3], vec![38, 2, 0, 10, 20, 30]]);
+            }
+            other => panic!("Expected SGR action, got: {:?}", other),
+        }
+        assert!(parser.state.attrs.contains(CellAttrs::BOLD));
+        assert!(parser.state.attrs.contains(CellAttrs::CURLY_UNDERLINE));
+        assert!(!parser.state.attrs.contains(CellAttrs::UNDERLINE));
+        assert_eq!(
+            parser.state.fg_color,
+            CellColor::Rgb(Color::rgb(10, 20, 30))
+        );
     }
 
     #[test]
-    fn test_color_256() {
-        // Test standard colors
-        assert_eq!(color_256(1), standard_color(1));
-        assert_eq!(color_256(9), bright_color(1));
+    fn test_sgr_colon_and_semicolon_colors() {
+        let mut parser = TerminalParser::new();
 
-        // Test color cube
-        let cube_color = color_256(16); // First color cube entry
-        assert_eq!(cube_color, Color::rgb(0, 0, 0));
+        // Colon RGB without a color space id
+        parser.parse(b"\x1b[48:2:1:2:3m");
+        assert_eq!(parser.state.bg_color, CellColor::Rgb(Color::rgb(1, 2, 3)));
 
-        // Test grayscale
-        let gray_color = color_256(232); // First grayscale entry
-        assert_eq!(gray_color, Color::rgb(8, 8, 8));
+        // Colon indexed color does not swallow the following parameter
+        parser.parse(b"\x1b[38:5:196;1m");
+        assert_eq!(parser.state.fg_color, CellColor::Indexed(196));
+        assert!(parser.state.attrs.contains(CellAttrs::BOLD));
+
+        // Legacy semicolon form still works
+        parser.parse(b"\x1b[0;38;2;7;8;9;3m");
+        assert_eq!(parser.state.fg_color, CellColor::Rgb(Color::rgb(7, 8, 9)));
+        assert!(parser.state.attrs.contains(CellAttrs::ITALIC));
+
+        // Malformed colon colors are ignored
+        parser.parse(b"\x1b[38:2:1m");
+        assert_eq!(parser.state.fg_color, CellColor::Rgb(Color::rgb(7, 8, 9)));
+    }
+
+    #[test]
+    fn test_sgr_underline_styles_and_color() {
+        let mut parser = TerminalParser::new();
+
+        let styles: &[(&[u8], CellAttrs)] = &[
+            (b"\x1b[4m", CellAttrs::UNDERLINE),
+            (b"\x1b[4:2m", CellAttrs::DOUBLE_UNDERLINE),
+            (b"\x1b[21m", CellAttrs::DOUBLE_UNDERLINE),
+            (b"\x1b[4:3m", CellAttrs::CURLY_UNDERLINE),
+            (b"\x1b[4:4m", CellAttrs::DOTTED_UNDERLINE),
+            (b"\x1b[4:5m", CellAttrs::DASHED_UNDERLINE),
+        ];
+        for (sequence, expected) in styles {
+            parser.parse(sequence);
+            assert_eq!(
+                parser.state.attrs & CellAttrs::ALL_UNDERLINES,
+                *expected,
+                "sequence {:?}",
+                sequence
+            );
+        }
+
+        parser.parse(b"\x1b[4:0m");
+        assert!(!parser.state.attrs.intersects(CellAttrs::ALL_UNDERLINES));
+        parser.parse(b"\x1b[4:3m\x1b[24m");
+        assert!(!parser.state.attrs.intersects(CellAttrs::ALL_UNDERLINES));
+
+        parser.parse(b"\x1b[58:5:9m");
+        assert_eq!(parser.state.underline_color, Some(CellColor::Indexed(9)));
+        parser.parse(b"\x1b[58;2;1;2;3m");
+        assert_eq!(
+            parser.state.underline_color,
+            Some(CellColor::Rgb(Color::rgb(1, 2, 3)))
+        );
+        parser.parse(b"\x1b[59m");
+        assert_eq!(parser.state.underline_color, None);
+    }
+
+    #[test]
+    fn test_sgr_dim_conceal_overline() {
+        let mut parser = TerminalParser::new();
+
+        parser.parse(b"\x1b[1;2m");
+        assert!(parser
+            .state
+            .attrs
+            .contains(CellAttrs::BOLD | CellAttrs::DIM));
+        parser.parse(b"\x1b[22m");
+        assert!(!parser
+            .state
+            .attrs
+            .intersects(CellAttrs::BOLD | CellAttrs::DIM));
+
+        parser.parse(b"\x1b[8;53m");
+        assert!(parser
+            .state
+            .attrs
+            .contains(CellAttrs::HIDDEN | CellAttrs::OVERLINE));
+        parser.parse(b"\x1b[28;55m");
+        assert!(parser.state.attrs.is_empty());
+    }
+
+    #[test]
+    fn test_indexed_colors() {
+        let mut parser = TerminalParser::new();
+        parser.parse(b"\x1b[31;102m");
+        assert_eq!(parser.state.fg_color, CellColor::Indexed(1));
+        assert_eq!(parser.state.bg_color, CellColor::Indexed(10));
+        parser.parse(b"\x1b[38;5;16;48;5;232m");
+        assert_eq!(parser.state.fg_color, CellColor::Indexed(16));
+        assert_eq!(parser.state.bg_color, CellColor::Indexed(232));
+
+        // Indices past the palette select the default and still consume their value
+        parser.parse(b"\x1b[38;5;300;1m");
+        assert_eq!(parser.state.fg_color, CellColor::Default);
+        assert_eq!(parser.state.attrs, CellAttrs::BOLD);
     }
 
     #[test]
@@ -631,7 +1503,7 @@ mod tests {
         let result = parse_extended_color(&[38, 2, 255, 128, 64]);
         assert!(result.is_some());
         let (color, consumed) = result.unwrap();
-        assert_eq!(color, Color::rgb(255, 128, 64));
+        assert_eq!(color, CellColor::Rgb(Color::rgb(255, 128, 64)));
         assert_eq!(consumed, 4);
     }
 }
@@ -0,0 +1,437 @@
+//! Chunked PTY output reader
+//!
+//! Shell output is read in fixed-size chunks on a dedicated thread and handed
+//! to the PTY task through a bounded ring buffer. Reads never wait for a
+//! newline, so prompts and progress bars are delivered as soon as they arrive.
+//! When the consumer falls behind, queued chunks are coalesced; once the ring is
+//! full the reader thread blocks, which applies backpressure to the shell
+//! through the kernel PTY buffer.
+
+use quantaterm_telemetry::{Telemetry, TelemetryEvent};
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use tokio::sync::Notify;
+use tracing::{debug, error, trace};
+
+/// Size of a single read from the PTY master
+pub const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Configuration for the chunk ring buffer
+#[derive(Debug, Clone, Copy)]
+pub struct RingConfig {
+    /// Maximum number of chunks queued before coalescing kicks in
+    pub capacity: usize,
+    /// Upper bound for a single coalesced chunk in bytes
+    pub max_chunk_bytes: usize,
+}
+
+impl Default for RingConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            max_chunk_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// Counters describing reader throughput and backpressure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReaderStats {
+    /// Number of reads that returned data
+    pub chunks_read: u64,
+    /// Total bytes read from the PTY
+    pub bytes_read: u64,
+    /// Chunks merged into a previous chunk instead of queued separately
+    pub chunks_merged: u64,
+    /// Chunks discarded because the consumer had gone away
+    pub chunks_dropped: u64,
+    /// Bytes discarded because the consumer had gone away
+    pub bytes_dropped: u64,
+    /// Times the reader blocked waiting for the consumer to drain the ring
+    pub stalls: u64,
+}
+
+impl ReaderStats {
+    /// Report these counters to a telemetry collector
+    pub fn record(&self, telemetry: &mut Telemetry) {
+        telemetry.add_counter("pty.chunks_read", self.chunks_read);
+        telemetry.add_counter("pty.bytes_read", self.bytes_read);
+        telemetry.add_counter("pty.chunks_merged", self.chunks_merged);
+        telemetry.add_counter("pty.chunks_dropped", self.chunks_dropped);
+        telemetry.add_counter("pty.reader_stalls", self.stalls);
+        telemetry.record_event(TelemetryEvent::PtyBackpressure {
+            chunks_merged: self.chunks_merged,
+            chunks_dropped: self.chunks_dropped,
+            stalls: self.stalls,
+        });
+    }
+}
+
+/// Ring buffer state protected by the mutex
+#[derive(Debug, Default)]
+struct RingState {
+    /// Queued chunks, oldest first
+    chunks: VecDeque<Vec<u8>>,
+    /// Set once the consumer no longer accepts data
+    closed: bool,
+    /// Read error that stopped the reader, until the consumer takes it
+    error: Option<String>,
+    /// Counters accumulated since the last `take_stats` call
+    stats: ReaderStats,
+}
+
+/// Bounded ring buffer between the reader thread and the PTY task
+#[derive(Debug)]
+pub struct ChunkRing {
+    config: RingConfig,
+    state: Mutex<RingState>,
+    /// Signalled when the consumer frees space
+    space: Condvar,
+    /// Signalled when new data is available
+    readable: Notify,
+}
+
+impl ChunkRing {
+    /// Create a new ring buffer with the given configuration
+    pub fn new(config: RingConfig) -> Self {
+        Self {
+            config: RingConfig {
+                capacity: config.capacity.max(1),
+                max_chunk_bytes: config.max_chunk_bytes.max(1),
+            },
+            state: Mutex::new(RingState::default()),
+            space: Condvar::new(),
+            readable: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, RingState> {
+        // A poisoned lock only means another thread panicked mid-update; the
+        // queue itself is still structurally valid.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Queue a chunk of output, blocking while the ring is full
+    ///
+    /// Returns `false` if the ring has been closed and the data was dropped.
+    pub fn push(&self, data: &[u8]) -> bool {
+        if data.is_empty() {
+            return true;
+        }
+
+        let mut state = self.lock();
+        state.stats.chunks_read += 1;
+        state.stats.bytes_read += data.len() as u64;
+
+        let mut stalled = false;
+        loop {
+            if state.closed {
+                state.stats.chunks_dropped += 1;
+                state.stats.bytes_dropped += data.len() as u64;
+                return false;
+            }
+
+            if state.chunks.len() < self.config.capacity {
+                state.chunks.push_back(data.to_vec());
+                break;
+            }
+
+            // Ring is full: fold the chunk into the newest one if it fits
+            if let Some(tail) = state.chunks.back_mut() {
+                if tail.len() + data.len() <= self.config.max_chunk_bytes {
+                    tail.extend_from_slice(data);
+                    state.stats.chunks_merged += 1;
+                    break;
+                }
+            }
+
+            if !stalled {
+                stalled = true;
+                state.stats.stalls += 1;
+                trace!(
+                    subsystem = "pty",
+                    queued_chunks = state.chunks.len(),
+                    "Reader stalled waiting for consumer"
+                );
+            }
+            state = self.space.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+
+        drop(state);
+        self.readable.notify_one();
+        true
+    }
+
+    /// Take all queued output as a single coalesced buffer
+    pub fn drain(&self) -> Option<Vec<u8>> {
+        let mut state = self.lock();
+        let mut data = state.chunks.pop_front()?;
+        let extra = state.chunks.len() as u64;
+        for chunk in state.chunks.drain(..) {
+            data.extend_from_slice(&chunk);
+        }
+        state.stats.chunks_merged += extra;
+        drop(state);
+
+        self.space.notify_all();
+        Some(data)
+    }
+
+    /// Check whether any output is waiting to be drained
+    pub fn has_data(&self) -> bool {
+        !self.lock().chunks.is_empty()
+    }
+
+    /// Record the error that stopped the reader and wake the consumer
+    pub fn fail(&self, error: String) {
+        self.lock().error = Some(error);
+        self.readable.notify_one();
+    }
+
+    /// Check whether a read error is waiting to be taken
+    pub fn has_error(&self) -> bool {
+        self.lock().error.is_some()
+    }
+
+    /// Take the error that stopped the reader, if any
+    pub fn take_error(&self) -> Option<String> {
+        self.lock().error.take()
+    }
+
+    /// Wait until new output has been pushed
+    pub async fn readable(&self) {
+        self.readable.notified().await;
+    }
+
+    /// Close the ring, waking a blocked reader and dropping further output
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        let dropped = state.chunks.len() as u64;
+        let dropped_bytes: usize = state.chunks.iter().map(Vec::len).sum();
+        state.chunks.clear();
+        state.stats.chunks_dropped += dropped;
+        state.stats.bytes_dropped += dropped_bytes as u64;
+        drop(state);
+
+        self.space.notify_all();
+    }
+
+    /// Return the counters accumulated since the previous call and reset them
+    pub fn take_stats(&self) -> ReaderStats {
+        std::mem::take(&mut self.lock().stats)
+    }
+}
+
+/// Whether a read error only means the shell side of the PTY was closed
+///
+/// Linux reports a read from a master whose slave has no process left as
+/// `EIO` rather than end of file.
+fn is_hangup(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        error.raw_os_error() == Some(libc::EIO)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = error;
+        false
+    }
+}
+
+/// Spawn the dedicated reader thread for a PTY master
+pub fn spawn_reader<R>(reader: R, ring: Arc<ChunkRing>) -> std::io::Result<()>
+where
+    R: Read + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name("quantaterm-pty-reader".to_string())
+        .spawn(move || read_loop(reader, &ring))
+        .map(|_| ())
+}
+
+/// Read fixed-size chunks until EOF, an error, or the ring closes
+pub fn read_loop<R: Read>(mut reader: R, ring: &ChunkRing) {
+    let mut buffer = vec![0u8; READ_CHUNK_SIZE];
+
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => {
+                debug!(subsystem = "pty", "Shell output stream closed");
+                break;
+            }
+            Err(e) if is_hangup(&e) => {
+                debug!(subsystem = "pty", "Shell hung up the terminal");
+                break;
+            }
+            Ok(bytes_read) => {
+                trace!(
+                    subsystem = "pty",
+                    bytes_read = bytes_read,
+                    "Read data from shell"
+                );
+                if !ring.push(&buffer[..bytes_read]) {
+                    debug!(subsystem = "pty", "Output ring closed, stopping reader");
+                    break;
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => {
+                error!(
+                    subsystem = "pty",
+                    error = %e,
+                    "Failed to read from shell"
+                );
+                ring.fail(e.to_string());
+                break;
+            }
+        }
+    }
+
+    // Wake the consumer so it notices any final output
+    ring.readable.notify_one();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    #[test]
+    fn test_partial_line_is_delivered() {
+        let ring = ChunkRing::new(RingConfig::default());
+        read_loop(Cursor::new(b"user@host:~$ ".to_vec()), &ring);
+
+        assert_eq!(ring.drain().unwrap(), b"user@host:~$ ");
+        assert!(ring.drain().is_none());
+    }
+
+    #[test]
+    fn test_drain_coalesces_queued_chunks() {
+        let ring = ChunkRing::new(RingConfig::default());
+        ring.push(b"abc");
+        ring.push(b"def");
+        ring.push(b"\r\n");
+
+        assert_eq!(ring.drain().unwrap(), b"abcdef\r\n");
+
+        let stats = ring.take_stats();
+        assert_eq!(stats.chunks_read, 3);
+        assert_eq!(stats.bytes_read, 8);
+        assert_eq!(stats.chunks_merged, 2);
+
+        // Stats are reset after being taken
+        assert_eq!(ring.take_stats(), ReaderStats::default());
+    }
+
+    #[test]
+    fn test_full_ring_merges_into_tail() {
+        let ring = ChunkRing::new(RingConfig {
+            capacity: 2,
+            max_chunk_bytes: 16,
+        });
+        ring.push(b"one");
+        ring.push(b"two");
+        ring.push(b"three");
+
+        let stats = ring.take_stats();
+        assert_eq!(stats.chunks_merged, 1);
+        assert_eq!(stats.stalls, 0);
+        assert_eq!(ring.drain().unwrap(), b"onetwothree");
+    }
+
+    #[test]
+    fn test_full_ring_blocks_reader_until_drained() {
+        let ring = Arc::new(ChunkRing::new(RingConfig {
+            capacity: 1,
+            max_chunk_bytes: 4,
+        }));
+        ring.push(b"1234");
+
+        let producer = {
+            let ring = ring.clone();
+            std::thread::spawn(move || ring.push(b"5678"))
+        };
+
+        // Give the producer time to hit the full ring
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!producer.is_finished());
+
+        assert_eq!(ring.drain().unwrap(), b"1234");
+        assert!(producer.join().unwrap());
+        assert_eq!(ring.drain().unwrap(), b"5678");
+        assert_eq!(ring.take_stats().stalls, 1);
+    }
+
+    #[test]
+    fn test_close_drops_pending_and_future_output() {
+        let ring = Arc::new(ChunkRing::new(RingConfig {
+            capacity: 1,
+            max_chunk_bytes: 4,
+        }));
+        ring.push(b"1234");
+
+        let producer = {
+            let ring = ring.clone();
+            std::thread::spawn(move || ring.push(b"5678"))
+        };
+        std::thread::sleep(Duration::from_millis(20));
+
+        ring.close();
+        assert!(!producer.join().unwrap());
+        assert!(ring.drain().is_none());
+
+        let stats = ring.take_stats();
+        assert_eq!(stats.chunks_dropped, 2);
+        assert_eq!(stats.bytes_dropped, 8);
+    }
+
+    #[test]
+    fn test_read_error_is_kept_for_consumer() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(ErrorKind::BrokenPipe, "shell died"))
+            }
+        }
+
+        let ring = ChunkRing::new(RingConfig::default());
+        read_loop(Failing, &ring);
+
+        assert!(ring.has_error());
+        assert_eq!(ring.take_error().as_deref(), Some("shell died"));
+        assert!(ring.take_error().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hangup_is_a_clean_close() {
+        struct HungUp;
+        impl Read for HungUp {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::from_raw_os_error(libc::EIO))
+            }
+        }
+
+        let ring = ChunkRing::new(RingConfig::default());
+        read_loop(HungUp, &ring);
+        assert!(!ring.has_error());
+    }
+
+    #[test]
+    fn test_stats_reported_to_telemetry() {
+        let ring = ChunkRing::new(RingConfig::default());
+        ring.push(b"hello");
+        ring.push(b"world");
+        ring.drain();
+
+        let mut telemetry = Telemetry::new();
+        ring.take_stats().record(&mut telemetry);
+
+        assert_eq!(telemetry.get_counter("pty.chunks_read"), 2);
+        assert_eq!(telemetry.get_counter("pty.bytes_read"), 10);
+        assert_eq!(telemetry.get_counter("pty.chunks_merged"), 1);
+    }
+}
@@ -0,0 +1,516 @@
+//! Replies to terminal queries
+//!
+//! Applications ask the terminal about itself (device attributes, cursor
+//! position, mode state, colors) and wait for an answer on their stdin. The
+//! responder builds those answers from the current grid state and writes them
+//! to a [`ResponseSink`], normally a [`PtyResponder`] that forwards them to the
+//! shell as [`PtyCommand::WriteData`].
+
+use crate::dcs::{hex_encode, DcsAction, StatusRequest};
+use crate::osc::{ColorRequest, DynamicColor, OscAction, OscTerminator};
+use crate::parser::{CsiAction, ParseAction};
+use crate::PtyCommand;
+use quantaterm_blocks::{CellAttrs, CellColor, Color, Palette, TerminalGrid};
+use quantaterm_core::{AnsiMode, DecMode, Mode, TerminalQuery};
+use std::io::Write;
+use tokio::sync::mpsc;
+use tracing::{debug, trace};
+
+/// Name reported by XTVERSION
+pub const TERMINAL_NAME: &str = "QuantaTerm";
+
+/// Primary device attributes: VT220 with ANSI color
+const PRIMARY_DEVICE_ATTRIBUTES: &[u8] = b"\x1b[?62;22c";
+
+/// Capabilities reported through XTGETTCAP
+const TERMCAP: &[(&str, &str)] = &[
+    ("TN", "xterm-256color"),
+    ("name", "xterm-256color"),
+    ("Co", "256"),
+    ("colors", "256"),
+    ("RGB", "8/8/8"),
+    ("Smulx", "\x1b[4:%p1%dm"),
+    (
+        "Setulc",
+        "\x1b[58:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d:%p1%{255}%&%dm",
+    ),
+];
+
+/// SGR codes reported by DECRQSS for each attribute
+const SGR_ATTRIBUTES: &[(CellAttrs, &str)] = &[
+    (CellAttrs::BOLD, "1"),
+    (CellAttrs::DIM, "2"),
+    (CellAttrs::ITALIC, "3"),
+    (CellAttrs::UNDERLINE, "4"),
+    (CellAttrs::DOUBLE_UNDERLINE, "4:2"),
+    (CellAttrs::CURLY_UNDERLINE, "4:3"),
+    (CellAttrs::DOTTED_UNDERLINE, "4:4"),
+    (CellAttrs::DASHED_UNDERLINE, "4:5"),
+    (CellAttrs::BLINK, "5"),
+    (CellAttrs::REVERSE, "7"),
+    (CellAttrs::HIDDEN, "8"),
+    (CellAttrs::STRIKETHROUGH, "9"),
+    (CellAttrs::OVERLINE, "53"),
+];
+
+/// Destination for bytes the terminal sends back to the application
+pub trait ResponseSink {
+    /// Write a complete reply
+    fn write_response(&mut self, bytes: &[u8]);
+}
+
+impl ResponseSink for Vec<u8> {
+    fn write_response(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+/// Forwards replies to the shell through the PTY command channel
+#[derive(Debug, Clone)]
+pub struct PtyResponder {
+    command_tx: mpsc::UnboundedSender<PtyCommand>,
+}
+
+impl PtyResponder {
+    pub(crate) fn new(command_tx: mpsc::UnboundedSender<PtyCommand>) -> Self {
+        Self { command_tx }
+    }
+}
+
+impl ResponseSink for PtyResponder {
+    fn write_response(&mut self, bytes: &[u8]) {
+        trace!(
+            subsystem = "pty",
+            byte_count = bytes.len(),
+            "Sending query reply"
+        );
+        if self
+            .command_tx
+            .send(PtyCommand::WriteData(bytes.to_vec()))
+            .is_err()
+        {
+            debug!(subsystem = "pty", "Dropping query reply, PTY is closed");
+        }
+    }
+}
+
+/// Answer a parsed action if it is a query, returning whether a reply was written
+///
+/// Call this after the action has been applied to the grid so that reports
+/// such as the cursor position reflect everything that preceded the query.
+pub fn respond(action: &ParseAction, grid: &TerminalGrid, sink: &mut impl ResponseSink) -> bool {
+    match action {
+        ParseAction::CsiDispatch(action) => respond_csi(action, grid, sink),
+        ParseAction::OscDispatch(action, terminator) => {
+            respond_osc(action, *terminator, grid, sink)
+        }
+        ParseAction::DcsDispatch(action) => respond_dcs(action, grid, sink),
+        _ => false,
+    }
+}
+
+/// Answer a CSI query such as DA or DSR
+pub(crate) fn respond_csi(
+    action: &CsiAction,
+    grid: &TerminalGrid,
+    sink: &mut impl ResponseSink,
+) -> bool {
+    match action {
+        CsiAction::Query(query) => send(Some(query_reply(query, grid)), sink),
+        _ => false,
+    }
+}
+
+/// Answer an OSC color query from the grid's current palette
+pub(crate) fn respond_osc(
+    action: &OscAction,
+    terminator: OscTerminator,
+    grid: &TerminalGrid,
+    sink: &mut impl ResponseSink,
+) -> bool {
+    send(osc_reply(action, terminator, grid.palette()), sink)
+}
+
+/// Answer a DECRQSS or XTGETTCAP request
+pub(crate) fn respond_dcs(
+    action: &DcsAction,
+    grid: &TerminalGrid,
+    sink: &mut impl ResponseSink,
+) -> bool {
+    send(dcs_reply(action, grid), sink)
+}
+
+fn send(reply: Option<Vec<u8>>, sink: &mut impl ResponseSink) -> bool {
+    match reply {
+        Some(reply) if !reply.is_empty() => {
+            sink.write_response(&reply);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn query_reply(query: &TerminalQuery, grid: &TerminalGrid) -> Vec<u8> {
+    let mut reply = Vec::new();
+    match query {
+        TerminalQuery::PrimaryDeviceAttributes => {
+            reply.extend_from_slice(PRIMARY_DEVICE_ATTRIBUTES)
+        }
+        TerminalQuery::SecondaryDeviceAttributes => {
+            // Terminal type 1 (VT220), firmware version, ROM cartridge 0
+            let _ = write!(reply, "\x1b[>1;{};0c", version_number());
+        }
+        TerminalQuery::OperatingStatus => reply.extend_from_slice(b"\x1b[0n"),
+        TerminalQuery::CursorPosition { private } => {
+            let (col, mut row) = grid.cursor_position();
+            if grid.modes().origin {
+                row = row.saturating_sub(grid.scroll_region().0);
+            }
+            let marker = if *private { "?" } else { "" };
+            let _ = write!(reply, "\x1b[{}{};{}R", marker, row + 1, col + 1);
+        }
+        TerminalQuery::Mode(mode) => {
+            let state = mode_state(*mode, grid);
+            let _ = match mode {
+                Mode::Ansi(ansi) => write!(reply, "\x1b[{};{}$y", ansi.code(), state),
+                Mode::Dec(dec) => write!(reply, "\x1b[?{};{}$y", dec.code(), state),
+            };
+        }
+        TerminalQuery::Version => {
+            let _ = write!(
+                reply,
+                "\x1bP>|{} {}\x1b\\",
+                TERMINAL_NAME,
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+    }
+    reply
+}
+
+/// DECRQM state: 0 unknown, 1 set, 2 reset
+fn mode_state(mode: Mode, grid: &TerminalGrid) -> u8 {
+    let value = match mode {
+        Mode::Dec(DecMode::AltScreen | DecMode::AltScreenClear | DecMode::AltScreenSaveCursor) => {
+            Some(grid.is_alt_screen_active())
+        }
+        Mode::Dec(DecMode::Unknown(_)) | Mode::Ansi(AnsiMode::Unknown(_)) => None,
+        _ => grid.modes().get(mode),
+    };
+    match value {
+        Some(true) => 1,
+        Some(false) => 2,
+        None => 0,
+    }
+}
+
+fn osc_reply(action: &OscAction, terminator: OscTerminator, palette: &Palette) -> Option<Vec<u8>> {
+    let mut reply = Vec::new();
+    match action {
+        OscAction::Palette(entries) => {
+            for (index, request) in entries {
+                if *request == ColorRequest::Query {
+                    let _ = write!(
+                        reply,
+                        "\x1b]4;{};{}",
+                        index,
+                        color_spec(palette.get(*index))
+                    );
+                    reply.extend_from_slice(terminator.as_bytes());
+                }
+            }
+        }
+        OscAction::DynamicColors(entries) => {
+            for (color, request) in entries {
+                if *request == ColorRequest::Query {
+                    let value = dynamic_color(*color, palette);
+                    let _ = write!(reply, "\x1b]{};{}", color.code(), color_spec(value));
+                    reply.extend_from_slice(terminator.as_bytes());
+                }
+            }
+        }
+        _ => return None,
+    }
+    Some(reply)
+}
+
+fn dcs_reply(action: &DcsAction, grid: &TerminalGrid) -> Option<Vec<u8>> {
+    let mut reply = Vec::new();
+    match action {
+        DcsAction::RequestStatus(request) => {
+            let setting = match request {
+                StatusRequest::Sgr => Some(format!("{}m", sgr_string(grid))),
+                StatusRequest::ScrollRegion => {
+                    let (top, bottom) = grid.scroll_region();
+                    Some(format!("{};{}r", top + 1, bottom + 1))
+                }
+                StatusRequest::CursorStyle => Some(format!("{} q", grid.cursor_style().param())),
+                StatusRequest::Unknown(_) => None,
+            };
+            match setting {
+                Some(setting) => {
+                    let _ = write!(reply, "\x1bP1$r{}\x1b\\", setting);
+                }
+                None => reply.extend_from_slice(b"\x1bP0$r\x1b\\"),
+            }
+        }
+        DcsAction::RequestTermcap(names) => {
+            for name in names {
+                let hex_name = hex_encode(name.as_bytes());
+                match TERMCAP.iter().find(|(cap, _)| cap == name) {
+                    Some((_, value)) => {
+                        let _ = write!(
+                            reply,
+                            "\x1bP1+r{}={}\x1b\\",
+                            hex_name,
+                            hex_encode(value.as_bytes())
+                        );
+                    }
+                    None => {
+                        let _ = write!(reply, "\x1bP0+r{}\x1b\\", hex_name);
+                    }
+                }
+            }
+        }
+        DcsAction::SynchronizedUpdate(_) | DcsAction::Other { .. } => return None,
+    }
+    Some(reply)
+}
+
+/// Current graphic rendition as SGR parameters, starting from a reset
+fn sgr_string(grid: &TerminalGrid) -> String {
+    let (fg, bg, attrs) = grid.current_formatting();
+    let mut params = vec!["0".to_string()];
+    params.extend(
+        SGR_ATTRIBUTES
+            .iter()
+            .filter(|(attr, _)| attrs.contains(*attr))
+            .map(|(_, code)| code.to_string()),
+    );
+    params.extend(sgr_color(fg, 30));
+    params.extend(sgr_color(bg, 40));
+    match grid.current_underline_color() {
+        Some(CellColor::Indexed(index)) => params.push(format!("58:5:{}", index)),
+        Some(CellColor::Rgb(color)) => {
+            params.push(format!("58:2::{}:{}:{}", color.r, color.g, color.b))
+        }
+        Some(CellColor::Default) | None => {}
+    }
+    params.join(";")
+}
+
+/// SGR parameters selecting `color`, with `base` 30 for the foreground and
+/// 40 for the background
+fn sgr_color(color: CellColor, base: u16) -> Option<String> {
+    match color {
+        CellColor::Default => None,
+        CellColor::Indexed(index @ 0..=7) => Some((base + index as u16).to_string()),
+        CellColor::Indexed(index @ 8..=15) => Some((base + 52 + index as u16).to_string()),
+        CellColor::Indexed(index) => Some(format!("{};5;{}", base + 8, index)),
+        CellColor::Rgb(color) => Some(format!(
+            "{};2;{};{};{}",
+            base + 8,
+            color.r,
+            color.g,
+            color.b
+        )),
+    }
+}
+
+fn dynamic_color(color: DynamicColor, palette: &Palette) -> Color {
+    match color {
+        DynamicColor::Foreground | DynamicColor::Cursor => palette.foreground(),
+        DynamicColor::Background => palette.background(),
+        // Selections are drawn in reverse video by default
+        DynamicColor::HighlightBackground => palette.foreground(),
+        DynamicColor::HighlightForeground => palette.background(),
+    }
+}
+
+/// Format a color as the 16-bit-per-channel X11 spec xterm reports
+fn color_spec(color: Color) -> String {
+    format!(
+        "rgb:{0:02x}{0:02x}/{1:02x}{1:02x}/{2:02x}{2:02x}",
+        color.r, color.g, color.b
+    )
+}
+
+/// Package version encoded as a single number, e.g. 1.2.3 -> 10203
+fn version_number() -> u32 {
+    env!("CARGO_PKG_VERSION")
+        .split('.')
+        .take(3)
+        .map(|part| part.parse::<u32>().unwrap_or(0))
+        .fold(0, |acc, part| acc * 100 + part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::TerminalParser;
+
+    /// Feed bytes through the parser and grid, collecting everything written back
+    fn run(grid: &mut TerminalGrid, input: &[u8]) -> Vec<u8> {
+        let mut parser = TerminalParser::new();
+        let mut written = Vec::new();
+        for action in parser.parse(input) {
+            match &action {
+                ParseAction::Print(c) => grid.print_char(*c),
+                ParseAction::Execute(byte) => grid.execute_control(*byte),
+                ParseAction::CsiDispatch(csi) => grid.handle_csi_action(csi),
+                _ => {}
+            }
+            let state = parser.state();
+            grid.apply_sgr(state.fg_color, state.bg_color, state.attrs);
+            grid.set_underline_color(state.underline_color);
+            respond(&action, grid, &mut written);
+        }
+        written
+    }
+
+    #[test]
+    fn test_device_attributes() {
+        let mut grid = TerminalGrid::new(80, 24);
+        assert_eq!(run(&mut grid, b"\x1b[c"), b"\x1b[?62;22c");
+        assert_eq!(
+            run(&mut grid, b"\x1b[>c"),
+            format!("\x1b[>1;{};0c", version_number()).as_bytes()
+        );
+        assert_eq!(
+            run(&mut grid, b"\x1b[>q"),
+            format!("\x1bP>|QuantaTerm {}\x1b\\", env!("CARGO_PKG_VERSION")).as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_status_and_cursor_reports() {
+        let mut grid = TerminalGrid::new(80, 24);
+        assert_eq!(run(&mut grid, b"\x1b[5n"), b"\x1b[0n");
+        assert_eq!(run(&mut grid, b"\x1b[10;20H\x1b[6n"), b"\x1b[10;20R");
+        assert_eq!(run(&mut grid, b"abc\x1b[?6n"), b"\x1b[?10;23R");
+
+        // Origin mode reports relative to the top margin
+        assert_eq!(
+            run(&mut grid, b"\x1b[5;20r\x1b[?6h\x1b[3;4H\x1b[6n"),
+            b"\x1b[3;4R"
+        );
+    }
+
+    #[test]
+    fn test_mode_reports() {
+        let mut grid = TerminalGrid::new(80, 24);
+        assert_eq!(run(&mut grid, b"\x1b[?2004$p"), b"\x1b[?2004;2$y");
+        assert_eq!(
+            run(&mut grid, b"\x1b[?2004h\x1b[?2004$p"),
+            b"\x1b[?2004;1$y"
+        );
+        assert_eq!(run(&mut grid, b"\x1b[?7$p"), b"\x1b[?7;1$y");
+        assert_eq!(
+            run(&mut grid, b"\x1b[?1049h\x1b[?1049$p"),
+            b"\x1b[?1049;1$y"
+        );
+        assert_eq!(run(&mut grid, b"\x1b[4$p"), b"\x1b[4;2$y");
+        assert_eq!(run(&mut grid, b"\x1b[?31337$p"), b"\x1b[?31337;0$y");
+        assert_eq!(run(&mut grid, b"\x1b[3$p"), b"\x1b[3;0$y");
+    }
+
+    #[test]
+    fn test_osc_color_queries() {
+        let mut grid = TerminalGrid::new(80, 24);
+        let fg = Color::DEFAULT_FG;
+        let expected = format!(
+            "\x1b]10;rgb:{0:02x}{0:02x}/{1:02x}{1:02x}/{2:02x}{2:02x}\x07",
+            fg.r, fg.g, fg.b
+        );
+        assert_eq!(run(&mut grid, b"\x1b]10;?\x07"), expected.as_bytes());
+
+        // Replies use the query's terminator
+        assert_eq!(
+            run(&mut grid, b"\x1b]4;1;?\x1b\\"),
+            b"\x1b]4;1;rgb:8080/0000/0000\x1b\\"
+        );
+        assert_eq!(
+            run(&mut grid, b"\x1b]4;9;?;15;?\x07"),
+            b"\x1b]4;9;rgb:ffff/0000/0000\x07\x1b]4;15;rgb:ffff/ffff/ffff\x07"
+        );
+
+        // Setting colors produces no reply
+        assert!(run(&mut grid, b"\x1b]4;1;#ffffff\x07").is_empty());
+        assert!(run(&mut grid, b"\x1b]11;#000000\x07").is_empty());
+    }
+
+    #[test]
+    fn test_decrqss() {
+        let mut grid = TerminalGrid::new(80, 24);
+        assert_eq!(run(&mut grid, b"\x1bP$qm\x1b\\"), b"\x1bP1$r0m\x1b\\");
+        assert_eq!(
+            run(
+                &mut grid,
+                b"\x1b[1;4:3;38;2;10;20;30;58:2::1:2:3m\x1bP$qm\x1b\\"
+            ),
+            b"\x1bP1$r0;1;4:3;38;2;10;20;30;58:2::1:2:3m\x1b\\"
+        );
+        assert_eq!(
+            run(&mut grid, b"\x1b[0;31;105;58:5:9m\x1bP$qm\x1b\\"),
+            b"\x1bP1$r0;31;105;58:5:9m\x1b\\"
+        );
+        assert_eq!(
+            run(&mut grid, b"\x1b[0;38;5;200m\x1bP$qm\x1b\\"),
+            b"\x1bP1$r0;38;5;200m\x1b\\"
+        );
+        assert_eq!(
+            run(&mut grid, b"\x1b[5;20r\x1bP$qr\x1b\\"),
+            b"\x1bP1$r5;20r\x1b\\"
+        );
+
+        assert_eq!(
+            run(&mut grid, b"\x1b[5 q\x1bP$q q\x1b\\"),
+            b"\x1bP1$r5 q\x1b\\"
+        );
+
+        // Unsupported settings are reported as invalid
+        assert_eq!(run(&mut grid, b"\x1bP$q\"p\x1b\\"), b"\x1bP0$r\x1b\\");
+    }
+
+    #[test]
+    fn test_xtgettcap() {
+        let mut grid = TerminalGrid::new(80, 24);
+
+        // "TN" is known, "xx" is not
+        assert_eq!(
+            run(&mut grid, b"\x1bP+q544E;7878\x1b\\"),
+            b"\x1bP1+r544E=787465726D2D323536636F6C6F72\x1b\\\x1bP0+r7878\x1b\\"
+        );
+        assert_eq!(
+            run(&mut grid, b"\x1bP+q436F\x1b\\"),
+            b"\x1bP1+r436F=323536\x1b\\"
+        );
+        // Synchronized updates are tracked but not honored by rendering
+        assert_eq!(
+            run(&mut grid, b"\x1bP+q53796E63\x1b\\"),
+            b"\x1bP0+r53796E63\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_non_queries_are_ignored() {
+        let mut grid = TerminalGrid::new(80, 24);
+        assert!(run(&mut grid, b"hello\x1b[2J\x1b]2;title\x07").is_empty());
+        assert!(run(&mut grid, b"\x1bP=1s\x1b\\\x1bPq#0\x1b\\").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pty_responder_sends_write_commands() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut responder = PtyResponder::new(tx);
+        let grid = TerminalGrid::new(80, 24);
+
+        let action = ParseAction::CsiDispatch(CsiAction::Query(TerminalQuery::OperatingStatus));
+        assert!(respond(&action, &grid, &mut responder));
+
+        match rx.recv().await {
+            Some(PtyCommand::WriteData(data)) => assert_eq!(data, b"\x1b[0n"),
+            other => panic!("Expected WriteData, got: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Terminal engine
+//!
+//! [`Terminal`] ties the escape sequence parser to the grid: shell output is
+//! parsed straight into the [`TerminalGrid`] through the [`TerminalHandler`]
+//! trait, and queries are answered through a [`ResponseSink`]. The
+//! application feeds it raw PTY output and renders from the grid.
+
+use crate::dcs::DcsAction;
+use crate::handler::{apply_osc, TerminalHandler};
+use crate::osc::{OscAction, OscTerminator, TitleTarget};
+use crate::parser::{CsiAction, EscAction, ParserState, TerminalParser};
+use crate::responder::{respond_csi, respond_dcs, respond_osc, ResponseSink};
+use quantaterm_blocks::TerminalGrid;
+
+/// Titles kept by XTPUSHTITLE before the oldest is dropped, as in xterm
+const MAX_TITLE_STACK: usize = 10;
+
+/// Parser and grid for one terminal session
+pub struct Terminal {
+    /// Escape sequence parser, kept across reads so split sequences resume
+    parser: TerminalParser,
+    /// Screen, scrollback and cursor state
+    grid: TerminalGrid,
+    /// Window title set by OSC 0/2
+    title: Option<String>,
+    /// Whether the title changed since `take_title_change` was last called
+    title_changed: bool,
+    /// Titles saved by XTPUSHTITLE, most recent last
+    title_stack: Vec<Option<String>>,
+}
+
+/// Handler for one `Terminal::process` call
+///
+/// Applies events to the grid and answers queries right after, so replies
+/// reflect everything that preceded them in the stream.
+struct Session<'a, S> {
+    grid: &'a mut TerminalGrid,
+    title: &'a mut Option<String>,
+    title_changed: &'a mut bool,
+    title_stack: &'a mut Vec<Option<String>>,
+    sink: &'a mut S,
+}
+
+impl<S: ResponseSink> TerminalHandler for Session<'_, S> {
+    fn print(&mut self, c: char) {
+        self.grid.print_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.grid.execute_control(byte);
+    }
+
+    fn sgr(&mut self, params: &[&[u16]], state: &ParserState) {
+        self.grid.sgr(params, state);
+    }
+
+    fn csi_dispatch(&mut self, action: CsiAction) {
+        match action {
+            CsiAction::PushTitle => {
+                if self.title_stack.len() == MAX_TITLE_STACK {
+                    self.title_stack.remove(0);
+                }
+                self.title_stack.push(self.title.clone());
+            }
+            CsiAction::PopTitle => {
+                if let Some(title) = self.title_stack.pop() {
+                    *self.title = title;
+                    *self.title_changed = true;
+                }
+            }
+            _ => {}
+        }
+        self.grid.handle_csi_action(&action);
+        respond_csi(&action, self.grid, self.sink);
+    }
+
+    fn esc_dispatch(&mut self, action: EscAction) {
+        if action == EscAction::Reset {
+            // RIS also drops the title and the title stack, so the window
+            // goes back to its default
+            *self.title = None;
+            *self.title_changed = true;
+            self.title_stack.clear();
+        }
+        self.grid.handle_esc_action(&action);
+    }
+
+    fn formatting(&self) -> Option<ParserState> {
+        self.grid.formatting()
+    }
+
+    fn osc_dispatch(&mut self, action: OscAction, terminator: OscTerminator) {
+        match action {
+            OscAction::SetTitle {
+                target: TitleTarget::Both | TitleTarget::Window,
+                title,
+            } => {
+                *self.title = Some(title);
+                *self.title_changed = true;
+            }
+            // Palette changes come first so queries in the same sequence see them
+            other => {
+                apply_osc(self.grid, &other);
+                respond_osc(&other, terminator, self.grid, self.sink);
+            }
+        }
+    }
+
+    fn dcs_dispatch(&mut self, action: DcsAction) {
+        // Requests leave the grid untouched, so answering first is equivalent
+        respond_dcs(&action, self.grid, self.sink);
+        self.grid.dcs_dispatch(action);
+    }
+}
+
+impl Terminal {
+    /// Create a terminal with the given dimensions
+    pub fn new(cols: u16, rows: u16) -> Self {
+        Self::with_grid(TerminalGrid::new(cols, rows))
+    }
+
+    /// Create a terminal around an existing grid
+    pub fn with_grid(grid: TerminalGrid) -> Self {
+        Self {
+            parser: TerminalParser::new(),
+            grid,
+            title: None,
+            title_changed: false,
+            title_stack: Vec::new(),
+        }
+    }
+
+    /// Parse shell output and apply it to the grid, writing any replies to `sink`
+    pub fn process(&mut self, data: &[u8], sink: &mut impl ResponseSink) {
+        let mut session = Session {
+            grid: &mut self.grid,
+            title: &mut self.title,
+            title_changed: &mut self.title_changed,
+            title_stack: &mut self.title_stack,
+            sink,
+        };
+        self.parser.advance(data, &mut session);
+    }
+
+    /// Resize the grid; the PTY has to be resized separately
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.grid.resize(cols, rows);
+    }
+
+    /// The grid output is applied to
+    pub fn grid(&self) -> &TerminalGrid {
+        &self.grid
+    }
+
+    /// Mutable access to the grid, e.g. for selection and scrolling
+    pub fn grid_mut(&mut self) -> &mut TerminalGrid {
+        &mut self.grid
+    }
+
+    /// Current window title, if the application set one
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// The new window title if it changed since the last call
+    ///
+    /// An empty title means the application cleared it, or the terminal was
+    /// reset, and the window should show its default title.
+    pub fn take_title_change(&mut self) -> Option<&str> {
+        if std::mem::take(&mut self.title_changed) {
+            Some(self.title.as_deref().unwrap_or_default())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quantaterm_blocks::{CellAttrs, CellColor, Color};
+
+    #[test]
+    fn test_output_is_applied_to_grid() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut replies = Vec::new();
+        terminal.process(b"ab\r\n\x1b[1;31mc\x1b[0md\x1b[2;5H!", &mut replies);
+
+        let text = terminal.grid().get_viewport_text();
+        assert_eq!(text, vec!["ab        ", "cd  !     ", "          "]);
+        assert!(replies.is_empty());
+
+        // Formatting is tracked per character, not per chunk
+        let c = terminal.grid().get_cell(0, 1).unwrap();
+        assert_eq!(c.attrs, CellAttrs::BOLD);
+        assert_eq!(c.fg_color, CellColor::Indexed(1));
+        let d = terminal.grid().get_cell(1, 1).unwrap();
+        assert_eq!(d.attrs, CellAttrs::empty());
+        assert_eq!(d.fg_color, CellColor::Default);
+    }
+
+    #[test]
+    fn test_split_sequences_and_queries() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut replies = Vec::new();
+        terminal.process(b"xy\x1b[", &mut replies);
+        terminal.process(b"6n", &mut replies);
+        assert_eq!(replies, b"\x1b[1;3R");
+    }
+
+    #[test]
+    fn test_title_and_synchronized_update() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut replies = Vec::new();
+        terminal.process(b"\x1b]2;build\x07\x1bP=1s\x1b\\", &mut replies);
+
+        assert_eq!(terminal.take_title_change(), Some("build"));
+        assert_eq!(terminal.take_title_change(), None);
+        assert_eq!(terminal.title(), Some("build"));
+        assert!(terminal.grid().modes().synchronized_output);
+
+        terminal.process(b"\x1bP=2s\x1b\\", &mut replies);
+        assert!(!terminal.grid().modes().synchronized_output);
+    }
+
+    #[test]
+    fn test_reset_clears_title() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut replies = Vec::new();
+        terminal.process(b"\x1b]2;vim\x07", &mut replies);
+        assert_eq!(terminal.take_title_change(), Some("vim"));
+
+        terminal.process(b"\x1bc", &mut replies);
+        assert_eq!(terminal.title(), None);
+        assert_eq!(terminal.take_title_change(), Some(""));
+        assert_eq!(terminal.take_title_change(), None);
+    }
+
+    #[test]
+    fn test_title_stack() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut replies = Vec::new();
+        terminal.process(b"\x1b]2;shell\x07\x1b[22;0t\x1b]2;vim\x07", &mut replies);
+        assert_eq!(terminal.take_title_change(), Some("vim"));
+
+        terminal.process(b"\x1b[23;0t", &mut replies);
+        assert_eq!(terminal.take_title_change(), Some("shell"));
+
+        // An empty stack leaves the title alone
+        terminal.process(b"\x1b[23t", &mut replies);
+        assert_eq!(terminal.take_title_change(), None);
+        assert_eq!(terminal.title(), Some("shell"));
+
+        // RIS empties the stack
+        terminal.process(b"\x1b[22t\x1bc\x1b]2;top\x07\x1b[23t", &mut replies);
+        assert_eq!(terminal.title(), Some("top"));
+        assert!(replies.is_empty());
+    }
+
+    #[test]
+    fn test_palette_changes_and_queries() {
+        let mut terminal = Terminal::new(10, 3);
+        let mut replies = Vec::new();
+        terminal.process(b"\x1b[31mr\x1b]4;1;#ff5555;1;?\x07", &mut replies);
+
+        // Existing cells keep the index and pick up the new entry
+        let grid = terminal.grid();
+        let cell = grid.get_cell(0, 0).unwrap();
+        assert_eq!(cell.fg_color, CellColor::Indexed(1));
+        assert_eq!(
+            grid.palette().resolve_fg(cell.fg_color),
+            Color::rgb(255, 85, 85)
+        );
+        assert_eq!(replies, b"\x1b]4;1;rgb:ffff/5555/5555\x07");
+
+        replies.clear();
+        terminal.process(b"\x1b]4;2;#010203\x07\x1b]104;1\x07", &mut replies);
+        assert_eq!(terminal.grid().palette().get(1), Color::rgb(128, 0, 0));
+        assert_eq!(terminal.grid().palette().get(2), Color::rgb(1, 2, 3));
+        terminal.process(b"\x1b]104\x07", &mut replies);
+        assert_eq!(terminal.grid().palette().get(2), Color::rgb(0, 128, 0));
+        assert!(replies.is_empty());
+    }
+}
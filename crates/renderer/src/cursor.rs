@@ -0,0 +1,182 @@
+//! Cursor geometry and blinking
+//!
+//! The grid reports where the cursor is and how it is styled; this module
+//! turns that into rectangles in physical pixels. An unfocused window shows
+//! a hollow block whatever the shape, so it is obvious keystrokes go
+//! elsewhere. On a double-size row the cursor is as wide as the row's
+//! columns.
+
+use crate::line;
+use crate::RendererColor;
+use quantaterm_core::{CursorShape, CursorStyle, LineSize};
+use std::time::Duration;
+
+/// Time the cursor spends shown, and then hidden, while blinking
+pub const CURSOR_BLINK_INTERVAL: Duration = Duration::from_millis(530);
+
+/// Cursor state handed to the renderer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RendererCursor {
+    /// Viewport column
+    pub col: u16,
+    /// Viewport row
+    pub row: u16,
+    /// Columns covered: 2 on a double-width character
+    pub width: u16,
+    /// Shape and blinking
+    pub style: CursorStyle,
+    /// Cursor color
+    pub color: RendererColor,
+}
+
+/// A filled rectangle in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    /// Left edge
+    pub x: f32,
+    /// Top edge
+    pub y: f32,
+    /// Width
+    pub width: f32,
+    /// Height
+    pub height: f32,
+}
+
+impl Rect {
+    /// Create a rectangle
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// Rectangles that draw `cursor`, on a row of `line_size`, on a grid of
+/// `cell` sized cells
+pub fn cursor_rects(
+    cursor: &RendererCursor,
+    line_size: LineSize,
+    cell: (f32, f32),
+    focused: bool,
+) -> Vec<Rect> {
+    let cell_height = cell.1;
+    let Rect { x, y, width, .. } =
+        line::cell_rect(cursor.col, cursor.row, cursor.width.max(1), line_size, cell);
+    let stroke = (cell_height / 10.0).round().max(1.0);
+
+    if !focused {
+        let inner = (cell_height - 2.0 * stroke).max(0.0);
+        return vec![
+            Rect::new(x, y, width, stroke),
+            Rect::new(x, y + cell_height - stroke, width, stroke),
+            Rect::new(x, y + stroke, stroke, inner),
+            Rect::new(x + width - stroke, y + stroke, stroke, inner),
+        ];
+    }
+
+    let rect = match cursor.style.shape {
+        CursorShape::Block => Rect::new(x, y, width, cell_height),
+        CursorShape::Underline => Rect::new(x, y + cell_height - stroke, width, stroke),
+        CursorShape::Bar => Rect::new(x, y, stroke, cell_height),
+    };
+    vec![rect]
+}
+
+/// Whether a blinking cursor is shown `elapsed` after it last moved
+pub fn blink_visible(elapsed: Duration) -> bool {
+    (elapsed.as_millis() / CURSOR_BLINK_INTERVAL.as_millis()) % 2 == 0
+}
+
+/// Time after the last move at which the blink phase next changes
+pub fn next_blink_toggle(elapsed: Duration) -> Duration {
+    let phases = elapsed.as_millis() / CURSOR_BLINK_INTERVAL.as_millis() + 1;
+    CURSOR_BLINK_INTERVAL * phases as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(shape: CursorShape) -> RendererCursor {
+        RendererCursor {
+            col: 2,
+            row: 1,
+            width: 1,
+            style: CursorStyle {
+                shape,
+                blinking: false,
+            },
+            color: RendererColor::rgb(255, 255, 255),
+        }
+    }
+
+    #[test]
+    fn test_cursor_shapes() {
+        let cell = (8.0, 20.0);
+        assert_eq!(
+            cursor_rects(
+                &cursor(CursorShape::Block),
+                LineSize::SingleWidth,
+                cell,
+                true
+            ),
+            vec![Rect::new(16.0, 20.0, 8.0, 20.0)]
+        );
+        assert_eq!(
+            cursor_rects(
+                &cursor(CursorShape::Underline),
+                LineSize::SingleWidth,
+                cell,
+                true
+            ),
+            vec![Rect::new(16.0, 38.0, 8.0, 2.0)]
+        );
+        assert_eq!(
+            cursor_rects(&cursor(CursorShape::Bar), LineSize::SingleWidth, cell, true),
+            vec![Rect::new(16.0, 20.0, 2.0, 20.0)]
+        );
+
+        // Unfocused cursors are a hollow block, two cells wide on a wide character
+        let wide = RendererCursor {
+            width: 2,
+            ..cursor(CursorShape::Bar)
+        };
+        assert_eq!(
+            cursor_rects(&wide, LineSize::SingleWidth, cell, false),
+            vec![
+                Rect::new(16.0, 20.0, 16.0, 2.0),
+                Rect::new(16.0, 38.0, 16.0, 2.0),
+                Rect::new(16.0, 22.0, 2.0, 16.0),
+                Rect::new(30.0, 22.0, 2.0, 16.0),
+            ]
+        );
+
+        // Columns on a double-width row are two cells wide
+        assert_eq!(
+            cursor_rects(
+                &cursor(CursorShape::Block),
+                LineSize::DoubleWidth,
+                cell,
+                true
+            ),
+            vec![Rect::new(32.0, 20.0, 16.0, 20.0)]
+        );
+    }
+
+    #[test]
+    fn test_blink_phases() {
+        assert!(blink_visible(Duration::ZERO));
+        assert!(!blink_visible(CURSOR_BLINK_INTERVAL));
+        assert!(blink_visible(
+            CURSOR_BLINK_INTERVAL * 2 + Duration::from_millis(1)
+        ));
+        assert_eq!(next_blink_toggle(Duration::ZERO), CURSOR_BLINK_INTERVAL);
+        assert_eq!(
+            next_blink_toggle(CURSOR_BLINK_INTERVAL + Duration::from_millis(10)),
+            CURSOR_BLINK_INTERVAL * 2
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Search match highlighting
+//!
+//! The grid marks cells inside search matches with
+//! [`SEARCH_MATCH`](crate::RendererCellAttrs::SEARCH_MATCH) and the current
+//! match with [`SEARCH_CURRENT`](crate::RendererCellAttrs::SEARCH_CURRENT).
+//! Each run of marked cells on a row is drawn as one translucent rectangle
+//! over the cell background, the current match in a stronger color.
+//!
+//! Runs are found per row with [`row_spans`] when the row is updated, so a
+//! frame that only redraws the cursor does not rescan the viewport.
+
+use crate::cursor::Rect;
+use crate::line;
+use crate::{LineSize, RendererCell, RendererCellAttrs, RendererColor};
+
+/// Color over cells of every match but the current one
+pub const SEARCH_MATCH_COLOR: RendererColor = RendererColor {
+    r: 255,
+    g: 214,
+    b: 0,
+    a: 96,
+};
+
+/// Color over cells of the current match
+pub const SEARCH_CURRENT_COLOR: RendererColor = RendererColor {
+    r: 255,
+    g: 128,
+    b: 0,
+    a: 160,
+};
+
+/// A run of highlighted cells on one row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HighlightSpan {
+    /// First column
+    pub col: u16,
+    /// Columns covered
+    pub width: u16,
+    /// Color drawn over the cells
+    pub color: RendererColor,
+}
+
+/// Highlight color for a cell with `attrs`, if it is in a match
+fn highlight_color(attrs: RendererCellAttrs) -> Option<RendererColor> {
+    if attrs.contains(RendererCellAttrs::SEARCH_CURRENT) {
+        Some(SEARCH_CURRENT_COLOR)
+    } else if attrs.contains(RendererCellAttrs::SEARCH_MATCH) {
+        Some(SEARCH_MATCH_COLOR)
+    } else {
+        None
+    }
+}
+
+/// Runs of cells in search matches on one row
+pub fn row_spans(cells: &[RendererCell]) -> Vec<HighlightSpan> {
+    let mut spans = Vec::new();
+    let mut col = 0;
+    while col < cells.len() {
+        let Some(color) = highlight_color(cells[col].attrs) else {
+            col += 1;
+            continue;
+        };
+        let start = col;
+        while col < cells.len() && highlight_color(cells[col].attrs) == Some(color) {
+            col += 1;
+        }
+        spans.push(HighlightSpan {
+            col: start as u16,
+            width: (col - start) as u16,
+            color,
+        });
+    }
+    spans
+}
+
+/// Rectangles for the spans of each viewport row, whose rows have the sizes
+/// `line_size` gives, on a grid of `cell` sized cells
+pub fn search_rects(
+    rows: &[Vec<HighlightSpan>],
+    line_size: impl Fn(usize) -> LineSize,
+    cell: (f32, f32),
+) -> Vec<(Rect, RendererColor)> {
+    let mut rects = Vec::new();
+    for (row, spans) in rows.iter().enumerate() {
+        let size = line_size(row);
+        rects.extend(spans.iter().map(|span| {
+            (
+                line::cell_rect(span.col, row as u16, span.width, size, cell),
+                span.color,
+            )
+        }));
+    }
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RendererCellRow;
+
+    #[test]
+    fn test_search_rects() {
+        let mut row: RendererCellRow = vec![RendererCell::new(b' ' as u32); 8];
+        for cell in &mut row[1..3] {
+            cell.attrs |= RendererCellAttrs::SEARCH_MATCH;
+        }
+        for cell in &mut row[3..5] {
+            cell.attrs |= RendererCellAttrs::SEARCH_MATCH | RendererCellAttrs::SEARCH_CURRENT;
+        }
+        row[7].attrs |= RendererCellAttrs::SEARCH_MATCH;
+        let spans = row_spans(&row);
+        assert_eq!(
+            spans,
+            [
+                HighlightSpan {
+                    col: 1,
+                    width: 2,
+                    color: SEARCH_MATCH_COLOR
+                },
+                HighlightSpan {
+                    col: 3,
+                    width: 2,
+                    color: SEARCH_CURRENT_COLOR
+                },
+                HighlightSpan {
+                    col: 7,
+                    width: 1,
+                    color: SEARCH_MATCH_COLOR
+                },
+            ]
+        );
+        assert!(row_spans(&vec![RendererCell::new(b'a' as u32); 8]).is_empty());
+
+        let rows = vec![Vec::new(), spans];
+        let size = |row: usize| {
+            if row == 1 {
+                LineSize::DoubleWidth
+            } else {
+                LineSize::SingleWidth
+            }
+        };
+        assert_eq!(
+            search_rects(&rows, size, (8.0, 16.0)),
+            [
+                (Rect::new(16.0, 16.0, 32.0, 16.0), SEARCH_MATCH_COLOR),
+                (Rect::new(48.0, 16.0, 32.0, 16.0), SEARCH_CURRENT_COLOR),
+                (Rect::new(112.0, 16.0, 16.0, 16.0), SEARCH_MATCH_COLOR),
+            ]
+        );
+        assert!(search_rects(&rows[..1], size, (8.0, 16.0)).is_empty());
+    }
+}
@@ -5,11 +5,21 @@
 #![warn(missing_docs)]
 #![deny(unsafe_code)]
 
+pub mod cursor;
 pub mod font;
+pub mod highlight;
+pub mod line;
+mod quad;
+
+pub use cursor::{RendererCursor, CURSOR_BLINK_INTERVAL};
+pub use highlight::HighlightSpan;
+pub use quantaterm_core::LineSize;
 
 use anyhow::{Context, Result};
 use bitflags::bitflags;
+use quad::QuadPipeline;
 use std::sync::Arc;
+use std::time::Instant;
 use tracing::{debug, info, instrument, trace, warn};
 use wgpu::{Device, Queue, Surface, SurfaceConfiguration};
 use winit::{dpi::PhysicalSize, window::Window};
@@ -57,6 +67,24 @@ bitflags! {
         const REVERSE = 1 << 5;
         /// Hidden/invisible text
         const HIDDEN = 1 << 6;
+        /// Dim (faint) text
+        const DIM = 1 << 7;
+        /// Double underline
+        const DOUBLE_UNDERLINE = 1 << 8;
+        /// Curly (wavy) underline
+        const CURLY_UNDERLINE = 1 << 9;
+        /// Dotted underline
+        const DOTTED_UNDERLINE = 1 << 10;
+        /// Dashed underline
+        const DASHED_UNDERLINE = 1 << 11;
+        /// Line above the text
+        const OVERLINE = 1 << 12;
+        /// Inside the text selection
+        const SELECTED = 1 << 13;
+        /// Part of a search match
+        const SEARCH_MATCH = 1 << 14;
+        /// Part of the current search match
+        const SEARCH_CURRENT = 1 << 15;
     }
 }
 
@@ -71,6 +99,8 @@ pub struct RendererCell {
     pub bg_color: RendererColor,
     /// Formatting attributes
     pub attrs: RendererCellAttrs,
+    /// Underline color, or `None` to use the foreground color
+    pub underline_color: Option<RendererColor>,
 }
 
 impl RendererCell {
@@ -81,6 +111,7 @@ impl RendererCell {
             fg_color: RendererColor::rgb(255, 255, 255), // White
             bg_color: RendererColor::rgb(0, 0, 0),       // Black
             attrs: RendererCellAttrs::empty(),
+            underline_color: None,
         }
     }
 
@@ -96,13 +127,30 @@ impl RendererCell {
             fg_color,
             bg_color,
             attrs,
+            underline_color: None,
         }
     }
+
+    /// Set the underline color
+    pub fn with_underline_color(mut self, color: Option<RendererColor>) -> Self {
+        self.underline_color = color;
+        self
+    }
 }
 
 /// A row of terminal cells for rendering
 pub type RendererCellRow = Vec<RendererCell>;
 
+/// Number of cells in `row` that show a glyph
+fn content_cells(row: &[RendererCell]) -> usize {
+    row.iter()
+        .filter(|cell| cell.glyph_id != b' ' as u32 && cell.glyph_id != 0)
+        .count()
+}
+
+/// Cell size in physical pixels assumed until font metrics are known
+pub const DEFAULT_CELL_SIZE: (f32, f32) = (8.0, 16.0);
+
 /// GPU-accelerated renderer for QuantaTerm
 pub struct Renderer {
     _instance: wgpu::Instance,
@@ -113,8 +161,29 @@ pub struct Renderer {
     size: PhysicalSize<u32>,
     /// Terminal viewport with full color and attribute data
     viewport: Vec<RendererCellRow>,
+    /// Non-blank cells in each viewport row, kept so that updating a few rows
+    /// does not rescan the rest
+    row_content: Vec<usize>,
+    /// Size of each viewport row; rows past the end are single width
+    line_sizes: Vec<LineSize>,
+    /// Search highlight runs in each viewport row, found as rows are updated
+    row_highlights: Vec<Vec<HighlightSpan>>,
+    /// Uploaded highlight quads, reused until the highlights or layout change
+    highlight_vertices: Option<(wgpu::Buffer, u32)>,
+    /// Whether `highlight_vertices` needs rebuilding before the next frame
+    highlights_dirty: bool,
     /// Current background color (changes when we receive shell output)
     background_color: wgpu::Color,
+    /// Pipeline for the cursor and other solid rectangles
+    quads: QuadPipeline,
+    /// Size of one cell in physical pixels
+    cell_size: (f32, f32),
+    /// Cursor to draw, or `None` while it is hidden or scrolled out of view
+    cursor: Option<RendererCursor>,
+    /// When the cursor last moved or changed; blinking restarts from here
+    cursor_changed_at: Instant,
+    /// Whether the window has keyboard focus
+    focused: bool,
 }
 
 impl Renderer {
@@ -190,6 +259,7 @@ impl Renderer {
         };
 
         surface.configure(&device, &config);
+        let quads = QuadPipeline::new(&device, surface_format);
 
         trace!(
             subsystem = "renderer",
@@ -211,12 +281,22 @@ impl Renderer {
             config,
             size,
             viewport: Vec::new(),
+            row_content: Vec::new(),
+            line_sizes: Vec::new(),
+            row_highlights: Vec::new(),
+            highlight_vertices: None,
+            highlights_dirty: false,
             background_color: wgpu::Color {
                 r: 0.1,
                 g: 0.2,
                 b: 0.3,
                 a: 1.0,
             },
+            quads,
+            cell_size: DEFAULT_CELL_SIZE,
+            cursor: None,
+            cursor_changed_at: Instant::now(),
+            focused: true,
         })
     }
 
@@ -228,6 +308,7 @@ impl Renderer {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
+            self.highlights_dirty = true;
             debug!(
                 subsystem = "renderer",
                 width = new_size.width,
@@ -261,9 +342,32 @@ impl Renderer {
                 label: Some("Render Encoder"),
             });
 
+        let surface_size = (self.size.width as f32, self.size.height as f32);
+        if self.highlights_dirty {
+            let rects = highlight::search_rects(
+                &self.row_highlights,
+                |row| self.line_size(row),
+                self.cell_size,
+            );
+            self.highlight_vertices = self.quads.prepare(&self.device, &rects, surface_size);
+            self.highlights_dirty = false;
+        }
+        let cursor_vertices = self
+            .cursor
+            .filter(|_| self.cursor_shown(Instant::now()))
+            .and_then(|cursor| {
+                let line_size = self.line_size(cursor.row as usize);
+                let quads: Vec<_> =
+                    cursor::cursor_rects(&cursor, line_size, self.cell_size, self.focused)
+                        .into_iter()
+                        .map(|rect| (rect, cursor.color))
+                        .collect();
+                self.quads.prepare(&self.device, &quads, surface_size)
+            });
+
         // Clear screen with background color (changes slightly when we have output)
         {
-            let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &view,
@@ -278,6 +382,14 @@ impl Renderer {
                 occlusion_query_set: None,
                 timestamp_writes: None,
             });
+
+            // Search highlights go under the cursor
+            for (buffer, count) in [&self.highlight_vertices, &cursor_vertices]
+                .into_iter()
+                .flatten()
+            {
+                self.quads.draw(&mut render_pass, buffer, *count);
+            }
         }
 
         // Submit commands and present
@@ -322,6 +434,13 @@ impl Renderer {
                 "Trimmed viewport to prevent memory growth"
             );
         }
+        self.row_content = self.viewport.iter().map(|row| content_cells(row)).collect();
+        self.set_highlights(
+            self.viewport
+                .iter()
+                .map(|row| highlight::row_spans(row))
+                .collect(),
+        );
 
         // Change background color slightly when we have output to show visual feedback
         let line_count = self.viewport.len() as f64;
@@ -345,15 +464,77 @@ impl Renderer {
         );
 
         self.viewport = viewport;
+        self.row_content = self.viewport.iter().map(|row| content_cells(row)).collect();
+        self.set_highlights(
+            self.viewport
+                .iter()
+                .map(|row| highlight::row_spans(row))
+                .collect(),
+        );
+        self.update_background();
+    }
+
+    /// Replace only the given viewport rows, as `(row, cells)` pairs
+    ///
+    /// The grid sends the rows damaged since its last update; the others are
+    /// left as they are.
+    pub fn update_rows(&mut self, rows: Vec<(usize, RendererCellRow)>) {
+        trace!(
+            subsystem = "renderer",
+            rows = rows.len(),
+            "Updating damaged viewport rows"
+        );
+        for (index, row) in rows {
+            if index >= self.viewport.len() {
+                self.viewport.resize(index + 1, Vec::new());
+                self.row_content.resize(index + 1, 0);
+                self.row_highlights.resize(index + 1, Vec::new());
+            }
+            self.row_content[index] = content_cells(&row);
+            let spans = highlight::row_spans(&row);
+            if spans != self.row_highlights[index] {
+                self.row_highlights[index] = spans;
+                self.highlights_dirty = true;
+            }
+            self.viewport[index] = row;
+        }
+        self.update_background();
+    }
+
+    /// Move the viewport rows up by `count`, as output scrolling the screen does
+    ///
+    /// The rows moving in at the bottom are blank until sent with
+    /// [`update_rows`](Self::update_rows).
+    pub fn scroll_viewport(&mut self, count: usize) {
+        let count = count.min(self.viewport.len());
+        if count == 0 {
+            return;
+        }
+        let rows = self.viewport.len();
+        self.viewport.drain(..count);
+        self.viewport.resize(rows, Vec::new());
+        self.row_content.drain(..count);
+        self.row_content.resize(rows, 0);
+        if self.row_highlights.iter().any(|spans| !spans.is_empty()) {
+            self.highlights_dirty = true;
+        }
+        self.row_highlights.drain(..count);
+        self.row_highlights.resize(rows, Vec::new());
+        self.update_background();
+    }
+
+    /// Replace the highlight runs of every row
+    fn set_highlights(&mut self, highlights: Vec<Vec<HighlightSpan>>) {
+        if highlights != self.row_highlights {
+            self.row_highlights = highlights;
+            self.highlights_dirty = true;
+        }
+    }
 
-        // Update background color based on content with formatted cells
+    /// Update background color based on content density
+    fn update_background(&mut self) {
         let total_cells: usize = self.viewport.iter().map(|row| row.len()).sum();
-        let non_empty_cells = self
-            .viewport
-            .iter()
-            .flat_map(|row| row.iter())
-            .filter(|cell| cell.glyph_id != b' ' as u32 && cell.glyph_id != 0)
-            .count();
+        let non_empty_cells: usize = self.row_content.iter().sum();
 
         let content_ratio = if total_cells > 0 {
             non_empty_cells as f64 / total_cells as f64
@@ -377,6 +558,70 @@ impl Renderer {
         );
     }
 
+    /// Update the size of each viewport row (DECDWL, DECDHL)
+    pub fn update_line_sizes(&mut self, line_sizes: Vec<LineSize>) {
+        if line_sizes != self.line_sizes {
+            self.line_sizes = line_sizes;
+            self.highlights_dirty = true;
+        }
+    }
+
+    /// Size of viewport row `row`
+    pub fn line_size(&self, row: usize) -> LineSize {
+        self.line_sizes.get(row).copied().unwrap_or_default()
+    }
+
+    /// Update the cursor drawn over the viewport, `None` hiding it
+    ///
+    /// A blinking cursor restarts its blink, shown, whenever it changes.
+    pub fn update_cursor(&mut self, cursor: Option<RendererCursor>) {
+        if cursor != self.cursor {
+            trace!(subsystem = "renderer", cursor = ?cursor, "Updated cursor");
+            self.cursor = cursor;
+            self.cursor_changed_at = Instant::now();
+        }
+    }
+
+    /// The cursor drawn over the viewport
+    pub fn cursor(&self) -> Option<RendererCursor> {
+        self.cursor
+    }
+
+    /// Record whether the window has keyboard focus
+    ///
+    /// Without focus the cursor is drawn as a steady hollow block.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        self.cursor_changed_at = Instant::now();
+    }
+
+    /// Set the size of one cell in physical pixels
+    pub fn set_cell_size(&mut self, width: f32, height: f32) {
+        self.cell_size = (width, height);
+        self.highlights_dirty = true;
+    }
+
+    /// When a blinking cursor next needs redrawing, if it is blinking
+    pub fn next_cursor_blink(&self, now: Instant) -> Option<Instant> {
+        let cursor = self.cursor?;
+        if !cursor.style.blinking || !self.focused {
+            return None;
+        }
+        let elapsed = now.saturating_duration_since(self.cursor_changed_at);
+        Some(self.cursor_changed_at + cursor::next_blink_toggle(elapsed))
+    }
+
+    /// Whether the cursor is in the visible phase of its blink at `now`
+    fn cursor_shown(&self, now: Instant) -> bool {
+        match self.cursor {
+            Some(cursor) if cursor.style.blinking && self.focused => {
+                cursor::blink_visible(now.saturating_duration_since(self.cursor_changed_at))
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
     /// Get a reference to the current viewport data
     pub fn get_viewport(&self) -> &[RendererCellRow] {
         &self.viewport
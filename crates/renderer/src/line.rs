@@ -0,0 +1,104 @@
+//! Geometry of double-size rows
+//!
+//! A row set to double width (DECDWL) or double height (DECDHL) keeps its
+//! place in the grid but each of its columns covers two cells. Double-height
+//! text is drawn twice as tall and clipped to the row, so the top row of the
+//! pair shows the upper half of the glyphs and the bottom row the lower half.
+
+use crate::cursor::Rect;
+use quantaterm_core::LineSize;
+
+/// How glyphs on a row are scaled and placed relative to the row
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphTransform {
+    /// Horizontal scale factor
+    pub scale_x: f32,
+    /// Vertical scale factor
+    pub scale_y: f32,
+    /// Offset of the glyph's top edge from the row's top edge, in pixels;
+    /// glyphs are clipped to the row
+    pub offset_y: f32,
+}
+
+/// Horizontal scale of columns on a row
+pub fn column_scale(line_size: LineSize) -> f32 {
+    if line_size.is_double() {
+        2.0
+    } else {
+        1.0
+    }
+}
+
+/// Number of columns shown on a row of `line_size` in a grid `cols` wide
+pub fn visible_columns(line_size: LineSize, cols: u16) -> u16 {
+    if line_size.is_double() {
+        (cols / 2).max(1)
+    } else {
+        cols
+    }
+}
+
+/// Rectangle covered by `width` columns from `col` on viewport row `row`
+pub fn cell_rect(col: u16, row: u16, width: u16, line_size: LineSize, cell: (f32, f32)) -> Rect {
+    let (cell_width, cell_height) = cell;
+    let scale = column_scale(line_size);
+    Rect::new(
+        col as f32 * cell_width * scale,
+        row as f32 * cell_height,
+        width as f32 * cell_width * scale,
+        cell_height,
+    )
+}
+
+/// Scale and placement of glyphs on a row of `line_size`
+pub fn glyph_transform(line_size: LineSize, cell_height: f32) -> GlyphTransform {
+    let (scale_y, offset_y) = match line_size {
+        LineSize::SingleWidth | LineSize::DoubleWidth => (1.0, 0.0),
+        LineSize::DoubleHeightTop => (2.0, 0.0),
+        LineSize::DoubleHeightBottom => (2.0, -cell_height),
+    };
+    GlyphTransform {
+        scale_x: column_scale(line_size),
+        scale_y,
+        offset_y,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_double_size_geometry() {
+        let cell = (8.0, 16.0);
+        assert_eq!(
+            cell_rect(3, 2, 1, LineSize::SingleWidth, cell),
+            Rect::new(24.0, 32.0, 8.0, 16.0)
+        );
+        assert_eq!(
+            cell_rect(3, 2, 1, LineSize::DoubleWidth, cell),
+            Rect::new(48.0, 32.0, 16.0, 16.0)
+        );
+        assert_eq!(visible_columns(LineSize::DoubleHeightTop, 80), 40);
+        assert_eq!(visible_columns(LineSize::DoubleWidth, 1), 1);
+        assert_eq!(visible_columns(LineSize::SingleWidth, 80), 80);
+
+        assert_eq!(
+            glyph_transform(LineSize::DoubleWidth, 16.0),
+            GlyphTransform {
+                scale_x: 2.0,
+                scale_y: 1.0,
+                offset_y: 0.0,
+            }
+        );
+        // The bottom half is the lower part of a glyph drawn from the row above
+        assert_eq!(
+            glyph_transform(LineSize::DoubleHeightBottom, 16.0),
+            GlyphTransform {
+                scale_x: 2.0,
+                scale_y: 2.0,
+                offset_y: -16.0,
+            }
+        );
+    }
+}
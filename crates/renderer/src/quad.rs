@@ -0,0 +1,202 @@
+//! Solid-colored rectangles
+//!
+//! Decorations such as the cursor are flat quads drawn through one small
+//! pipeline. The cursor's handful of vertices are rebuilt on the CPU every
+//! frame; search highlights are uploaded once and kept until they change.
+
+use crate::cursor::Rect;
+use crate::RendererColor;
+use wgpu::util::DeviceExt;
+
+/// Passes positions through and fills with the vertex color
+const SHADER: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(position, 0.0, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+/// Floats per vertex: clip-space x and y, then RGBA
+const VERTEX_FLOATS: usize = 6;
+
+/// Pipeline drawing [`Rect`]s in a single color each
+pub(crate) struct QuadPipeline {
+    pipeline: wgpu::RenderPipeline,
+    /// The target stores sRGB, so colors are linearized before blending
+    srgb: bool,
+}
+
+impl QuadPipeline {
+    /// Build the pipeline for a surface of the given format
+    pub(crate) fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Quad Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Quad Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Quad Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: (VERTEX_FLOATS * std::mem::size_of::<f32>()) as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            pipeline,
+            srgb: format.is_srgb(),
+        }
+    }
+
+    /// Upload `quads` for a surface of `size` pixels, returning the vertex
+    /// buffer and vertex count, or `None` when there is nothing to draw
+    pub(crate) fn prepare(
+        &self,
+        device: &wgpu::Device,
+        quads: &[(Rect, RendererColor)],
+        size: (f32, f32),
+    ) -> Option<(wgpu::Buffer, u32)> {
+        if quads.is_empty() {
+            return None;
+        }
+        let vertices = quad_vertices(quads, size, self.srgb);
+        let bytes: Vec<u8> = vertices.iter().flat_map(|v| v.to_ne_bytes()).collect();
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Quad Vertices"),
+            contents: &bytes,
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        Some((buffer, (vertices.len() / VERTEX_FLOATS) as u32))
+    }
+
+    /// Draw vertices uploaded by [`prepare`](Self::prepare)
+    pub(crate) fn draw(&self, pass: &mut wgpu::RenderPass<'_>, buffer: &wgpu::Buffer, count: u32) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(0, buffer.slice(..));
+        pass.draw(0..count, 0..1);
+    }
+}
+
+/// Two triangles per quad, in clip space, with the quad's color
+fn quad_vertices(quads: &[(Rect, RendererColor)], size: (f32, f32), srgb: bool) -> Vec<f32> {
+    let (width, height) = (size.0.max(1.0), size.1.max(1.0));
+    let to_clip = |x: f32, y: f32| [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0];
+
+    let mut vertices = Vec::with_capacity(quads.len() * 6 * VERTEX_FLOATS);
+    for (rect, color) in quads {
+        let channel = |value: u8| {
+            let value = value as f32 / 255.0;
+            if srgb {
+                srgb_to_linear(value)
+            } else {
+                value
+            }
+        };
+        let rgba = [
+            channel(color.r),
+            channel(color.g),
+            channel(color.b),
+            color.a as f32 / 255.0,
+        ];
+        let top_left = to_clip(rect.x, rect.y);
+        let bottom_right = to_clip(rect.x + rect.width, rect.y + rect.height);
+        let top_right = [bottom_right[0], top_left[1]];
+        let bottom_left = [top_left[0], bottom_right[1]];
+        for corner in [
+            top_left,
+            bottom_left,
+            top_right,
+            top_right,
+            bottom_left,
+            bottom_right,
+        ] {
+            vertices.extend_from_slice(&corner);
+            vertices.extend_from_slice(&rgba);
+        }
+    }
+    vertices
+}
+
+/// Convert an sRGB-encoded channel to linear light
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quad_vertices() {
+        let quads = [(
+            Rect::new(0.0, 0.0, 50.0, 25.0),
+            RendererColor::rgb(255, 0, 0),
+        )];
+        let vertices = quad_vertices(&quads, (100.0, 100.0), false);
+        assert_eq!(vertices.len(), 6 * VERTEX_FLOATS);
+        // Top left corner of the surface, then bottom left of the quad
+        assert_eq!(vertices[..6], [-1.0, 1.0, 1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(vertices[6..8], [-1.0, 0.5]);
+        // Bottom right of the quad comes last
+        assert_eq!(vertices[30..32], [0.0, 0.5]);
+
+        let gray = [(
+            Rect::new(0.0, 0.0, 1.0, 1.0),
+            RendererColor::rgb(128, 128, 128),
+        )];
+        let linear = quad_vertices(&gray, (1.0, 1.0), true)[2];
+        assert!((linear - 0.2158).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_shader_is_valid() {
+        use wgpu::naga::valid::{Capabilities, ValidationFlags, Validator};
+        let module = wgpu::naga::front::wgsl::parse_str(SHADER).unwrap();
+        Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+            .unwrap();
+    }
+}
@@ -6,10 +6,13 @@
 #![deny(unsafe_code)]
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::time::{Duration, Instant};
 use tracing::{debug, info, instrument, warn};
 
+/// Number of recent events kept for inspection
+pub const MAX_RECENT_EVENTS: usize = 256;
+
 /// Performance metrics collector
 #[derive(Debug)]
 pub struct Telemetry {
@@ -19,6 +22,8 @@ pub struct Telemetry {
     timings: HashMap<String, Vec<Duration>>,
     /// Start time for measurements
     start_times: HashMap<String, Instant>,
+    /// Most recent events, oldest first
+    events: VecDeque<TelemetryEvent>,
     /// Whether telemetry collection is enabled
     enabled: bool,
 }
@@ -54,6 +59,15 @@ pub enum TelemetryEvent {
         /// Success status
         success: bool,
     },
+    /// PTY output backpressure
+    PtyBackpressure {
+        /// Chunks coalesced because the consumer fell behind
+        chunks_merged: u64,
+        /// Chunks dropped because the consumer was gone
+        chunks_dropped: u64,
+        /// Times the reader blocked waiting for the consumer
+        stalls: u64,
+    },
     /// Terminal resize
     TerminalResize {
         /// New width in columns
@@ -68,6 +82,17 @@ pub enum TelemetryEvent {
         /// Virtual memory in bytes
         virtual_bytes: u64,
     },
+    /// Scrollback history memory snapshot
+    ScrollbackMemory {
+        /// Lines in history
+        lines: u64,
+        /// Bytes used by history
+        bytes: u64,
+        /// Average bytes per history line
+        bytes_per_line: f64,
+        /// Pages of history stored compressed
+        compressed_pages: u64,
+    },
 }
 
 impl Telemetry {
@@ -78,6 +103,7 @@ impl Telemetry {
             counters: HashMap::new(),
             timings: HashMap::new(),
             start_times: HashMap::new(),
+            events: VecDeque::new(),
             enabled: true,
         }
     }
@@ -89,6 +115,7 @@ impl Telemetry {
             counters: HashMap::new(),
             timings: HashMap::new(),
             start_times: HashMap::new(),
+            events: VecDeque::new(),
             enabled: false,
         }
     }
@@ -157,6 +184,20 @@ impl Telemetry {
                     "PTY operation completed"
                 );
             }
+            TelemetryEvent::PtyBackpressure {
+                chunks_merged,
+                chunks_dropped,
+                stalls,
+            } => {
+                debug!(
+                    subsystem = "telemetry",
+                    event_type = "pty_backpressure",
+                    chunks_merged = chunks_merged,
+                    chunks_dropped = chunks_dropped,
+                    stalls = stalls,
+                    "PTY reader backpressure"
+                );
+            }
             TelemetryEvent::TerminalResize { width, height } => {
                 info!(
                     subsystem = "telemetry",
@@ -180,7 +221,33 @@ impl Telemetry {
                     "Memory usage snapshot"
                 );
             }
+            TelemetryEvent::ScrollbackMemory {
+                lines,
+                bytes,
+                bytes_per_line,
+                compressed_pages,
+            } => {
+                debug!(
+                    subsystem = "telemetry",
+                    event_type = "scrollback_memory",
+                    lines = lines,
+                    bytes = bytes,
+                    bytes_per_line = bytes_per_line,
+                    compressed_pages = compressed_pages,
+                    "Scrollback memory snapshot"
+                );
+            }
+        }
+
+        if self.events.len() == MAX_RECENT_EVENTS {
+            self.events.pop_front();
         }
+        self.events.push_back(event);
+    }
+
+    /// Events recorded most recently, oldest first
+    pub fn recent_events(&self) -> impl Iterator<Item = &TelemetryEvent> {
+        self.events.iter()
     }
 
     /// Increment a counter
@@ -342,6 +409,7 @@ impl Telemetry {
         self.counters.clear();
         self.timings.clear();
         self.start_times.clear();
+        self.events.clear();
     }
 }
 
@@ -451,6 +519,37 @@ mod tests {
             width: 80,
             height: 24,
         });
+        assert_eq!(telemetry.recent_events().count(), 3);
+        assert!(matches!(
+            telemetry.recent_events().last(),
+            Some(TelemetryEvent::TerminalResize {
+                width: 80,
+                height: 24
+            })
+        ));
+    }
+
+    #[test]
+    fn test_recent_events_are_bounded() {
+        let mut telemetry = Telemetry::new();
+        for width in 0..MAX_RECENT_EVENTS as u16 + 10 {
+            telemetry.record_event(TelemetryEvent::TerminalResize { width, height: 24 });
+        }
+        assert_eq!(telemetry.recent_events().count(), MAX_RECENT_EVENTS);
+        assert!(matches!(
+            telemetry.recent_events().next(),
+            Some(TelemetryEvent::TerminalResize { width: 10, .. })
+        ));
+
+        telemetry.clear();
+        assert_eq!(telemetry.recent_events().count(), 0);
+
+        let mut disabled = Telemetry::disabled();
+        disabled.record_event(TelemetryEvent::TerminalResize {
+            width: 80,
+            height: 24,
+        });
+        assert_eq!(disabled.recent_events().count(), 0);
     }
 
     #[test]
@@ -5,7 +5,8 @@
 // pub mod color_rendering_demo;
 // pub mod debug_color_test;
 // pub mod sgr_integration;
-// pub mod vttest_subset;
+#[cfg(test)]
+mod vttest_subset;
 
 //#[test]
 //fn test_logging_configurations() {
@@ -1,10 +1,13 @@
-//! VTTest subset validation for SGR handling
+//! VTTest subset validation for SGR handling and autowrap
 //!
 //! This module provides tests for a subset of VTTest functionality,
-//! specifically focusing on SGR (Select Graphic Rendition) codes.
+//! specifically focusing on SGR (Select Graphic Rendition) codes and the
+//! last-column wrap behaviour and double-size lines checked by the cursor
+//! movement screens.
 
-// use quantaterm_blocks::{CellAttrs, Color, TerminalGrid};
-// use quantaterm_pty::{CsiAction, ParseAction, TerminalParser};
+use quantaterm_blocks::{CellAttrs, Color, TerminalGrid};
+use quantaterm_core::LineSize;
+use quantaterm_pty::{CsiAction, ParseAction, Terminal, TerminalParser};
 
 /// VTTest subset - basic SGR functionality
 /// This covers the most common SGR codes that a terminal emulator should support
@@ -142,7 +145,7 @@ fn vttest_sgr_colors() {
 
         // Check the result
         let cell = grid.get_cell(0, i as u16).unwrap();
-        if cell.fg_color == *expected_color {
+        if grid.palette().resolve_fg(cell.fg_color) == *expected_color {
             passed += 1;
             println!("✓ Color test {}: Correct", i + 1);
         } else {
@@ -179,6 +182,249 @@ fn vttest_comprehensive() {
 
     vttest_sgr_basic_attributes();
     vttest_sgr_colors();
+    vttest_autowrap_margins();
+    vttest_last_column_wrap();
+    vttest_wide_character_wrap();
 
-    println!("✓ All VTTest SGR subset tests passed with >= 90% success rate");
+    println!("✓ All VTTest subset tests passed");
+}
+
+/// Move the cursor to a 1-based row and column (CUP)
+fn cup(out: &mut Vec<u8>, row: u16, col: u16) {
+    out.extend_from_slice(format!("\x1b[{row};{col}H").as_bytes());
+}
+
+/// Feed output to an 80x24 terminal
+fn run(output: &[u8]) -> Terminal {
+    let mut terminal = Terminal::new(80, 24);
+    let mut replies = Vec::new();
+    terminal.process(output, &mut replies);
+    terminal
+}
+
+/// VTTest subset - autowrap mixed with control characters
+///
+/// Replays the cursor movements screen that wraps letters around the right
+/// margin of a scrolling region in origin mode. Printing in the last column
+/// must leave the wrap pending until the next character, and BS, HT, LF and
+/// CUP must cancel it; otherwise the margins get blank lines or misplaced
+/// letters.
+#[test]
+fn vttest_autowrap_margins() {
+    const COLS: u16 = 80;
+    // Last row of the scrolling region, relative to its top in origin mode
+    const BOTTOM: u16 = 22;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"\x1b[2J\x1b[H");
+    out.extend_from_slice(b"Test of autowrap, mixing control and print characters.\r\n");
+    out.extend_from_slice(b"The left/right margins should have letters in order:");
+    out.extend_from_slice(b"\x1b[3;24r\x1b[?6h");
+    cup(&mut out, 1, 1);
+
+    for i in 0..26u8 {
+        let (left, right) = (b'A' + i, b'a' + i);
+        match i % 4 {
+            0 => {
+                // Characters as-is, for reference
+                cup(&mut out, BOTTOM, 1);
+                out.push(left);
+                cup(&mut out, BOTTOM, COLS);
+                out.push(right);
+                out.push(b'\n');
+            }
+            1 => {
+                // Simple wrapping
+                cup(&mut out, BOTTOM - 1, COLS);
+                out.extend_from_slice(&[right - 1, left]);
+                // Backspace at the right margin
+                cup(&mut out, BOTTOM, COLS);
+                out.extend_from_slice(&[left, 0x08, b' ', right, b'\n']);
+            }
+            2 => {
+                // Tab to the right margin
+                cup(&mut out, BOTTOM, COLS);
+                out.extend_from_slice(&[left, 0x08, 0x08, b'\t', b'\t', right]);
+                cup(&mut out, BOTTOM, 2);
+                out.extend_from_slice(&[0x08, left, b'\n']);
+            }
+            _ => {
+                // Newline at the right margin
+                cup(&mut out, BOTTOM, COLS);
+                out.push(b'\n');
+                cup(&mut out, BOTTOM - 1, 1);
+                out.push(left);
+                cup(&mut out, BOTTOM - 1, COLS);
+                out.push(right);
+            }
+        }
+    }
+    out.extend_from_slice(b"\x1b[?6l\x1b[r");
+
+    let terminal = run(&out);
+    let screen = terminal.grid().get_viewport_text();
+    assert!(screen[1].starts_with("The left/right margins"));
+
+    // The first five lines scrolled out of the region; F..Z remain in order
+    for (k, line) in screen[2..23].iter().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let expected_left = (b'F' + k as u8) as char;
+        let expected_right = (b'f' + k as u8) as char;
+        assert_eq!(chars[0], expected_left, "left margin of row {}", k + 3);
+        assert_eq!(chars[79], expected_right, "right margin of row {}", k + 3);
+        assert!(
+            chars[1..79].iter().all(|&c| c == ' '),
+            "row {} has stray characters: {:?}",
+            k + 3,
+            line
+        );
+    }
+    assert_eq!(screen[23].trim_end(), "");
+}
+
+/// VTTest subset - a full line followed by CR LF, and DECAWM off
+#[test]
+fn vttest_last_column_wrap() {
+    // Exactly 80 characters do not wrap until another character arrives
+    let mut out = vec![b'*'; 80];
+    out.extend_from_slice(b"\r\nnext");
+    let terminal = run(&out);
+    let screen = terminal.grid().get_viewport_text();
+    assert_eq!(screen[0], "*".repeat(80));
+    assert_eq!(screen[1].trim_end(), "next");
+    assert!(!terminal.grid().is_line_wrapped(0));
+
+    // The 81st character wraps, and the row is marked as continuing
+    let mut out = vec![b'*'; 81];
+    out.extend_from_slice(b"\r\nnext");
+    let terminal = run(&out);
+    let screen = terminal.grid().get_viewport_text();
+    assert_eq!(screen[1].trim_end(), "*");
+    assert_eq!(screen[2].trim_end(), "next");
+    assert!(terminal.grid().is_line_wrapped(0));
+
+    // With DECAWM reset, extra characters overwrite the last column
+    let mut out = b"\x1b[?7l".to_vec();
+    out.extend((0..90).map(|i| b'0' + i % 10));
+    let terminal = run(&out);
+    let screen = terminal.grid().get_viewport_text();
+    assert_eq!(&screen[0][..79], &"0123456789".repeat(8)[..79]);
+    assert!(screen[0].ends_with('9'));
+    assert_eq!(screen[1].trim_end(), "");
+    assert_eq!(terminal.grid().cursor_position(), (79, 0));
+}
+
+/// VTTest subset - double-width characters at the right margin
+#[test]
+fn vttest_wide_character_wrap() {
+    // A wide character that fits the last two columns leaves a wrap pending
+    let mut out = "-".repeat(78).into_bytes();
+    out.extend_from_slice("中\r\nnext".as_bytes());
+    let terminal = run(&out);
+    let screen = terminal.grid().get_viewport_text();
+    assert_eq!(screen[0], format!("{}中", "-".repeat(78)));
+    assert_eq!(screen[1].trim_end(), "next");
+
+    // One that does not fit wraps whole, leaving the last column blank
+    let mut out = "-".repeat(79).into_bytes();
+    out.extend_from_slice("中".as_bytes());
+    let terminal = run(&out);
+    let screen = terminal.grid().get_viewport_text();
+    assert_eq!(screen[0], format!("{} ", "-".repeat(79)));
+    assert_eq!(screen[1].trim_end(), "中");
+    assert_eq!(terminal.grid().cursor_position(), (2, 1));
+}
+
+/// VTTest subset - cursor movement on double-width lines
+///
+/// Replays the cursor movements screen drawn with every line set to double
+/// width: a frame of `*` around the screen and `+` inside it, placed with
+/// CUP, CUF and CUB. The right margin of a double-width line is column 40,
+/// so addressing column 80 lands there.
+#[test]
+fn vttest_double_width_frame() {
+    const HALF: usize = 40;
+
+    let mut out = b"\x1b[2J\x1b[H".to_vec();
+    for row in 1..=24 {
+        cup(&mut out, row, 1);
+        out.extend_from_slice(b"\x1b#6");
+    }
+    for row in 1..=24 {
+        cup(&mut out, row, 1);
+        out.push(b'*');
+        cup(&mut out, row, 80);
+        out.push(b'*');
+    }
+    for col in 2..HALF as u16 {
+        cup(&mut out, 1, col);
+        out.push(b'*');
+        cup(&mut out, 24, col);
+        out.push(b'*');
+    }
+    for row in 2..=23 {
+        // CUF stops at the right margin, CUB then steps back inside the frame
+        cup(&mut out, row, 2);
+        out.extend_from_slice(b"+\x1b[99C\x1b[D+");
+    }
+
+    let terminal = run(&out);
+    let grid = terminal.grid();
+    let screen = grid.get_viewport_text();
+    for (row, line) in screen.iter().enumerate() {
+        assert_eq!(grid.line_size(row as u16), LineSize::DoubleWidth);
+        let chars: Vec<char> = line.chars().collect();
+        let expected = if row == 0 || row == 23 {
+            "*".repeat(HALF)
+        } else {
+            format!("*+{}+*", " ".repeat(HALF - 4))
+        };
+        assert_eq!(
+            chars[..HALF].iter().collect::<String>(),
+            expected,
+            "row {row}"
+        );
+        assert!(
+            chars[HALF..].iter().all(|&c| c == ' '),
+            "row {row}: {line:?}"
+        );
+    }
+    assert_eq!(grid.cursor_position(), (39, 22));
+}
+
+/// VTTest subset - double-height lines and going back to single width
+#[test]
+fn vttest_double_height_lines() {
+    let mut out = b"\x1b[2J\x1b[H".to_vec();
+    out.extend_from_slice(b"\x1b#3Double height\r\n\x1b#4Double height\r\n");
+    out.extend_from_slice(b"\x1b#6Double width\r\n");
+    out.extend_from_slice(b"\x1b#6\x1b#5Single width\r\n");
+    // Text past the new right margin is lost when a line becomes double width
+    out.extend_from_slice(&[b'x'; 60]);
+    out.extend_from_slice(b"\x1b#6");
+
+    let terminal = run(&out);
+    let grid = terminal.grid();
+    let screen = grid.get_viewport_text();
+    let sizes: Vec<LineSize> = (0..5).map(|row| grid.line_size(row)).collect();
+    assert_eq!(
+        sizes,
+        [
+            LineSize::DoubleHeightTop,
+            LineSize::DoubleHeightBottom,
+            LineSize::DoubleWidth,
+            LineSize::SingleWidth,
+            LineSize::DoubleWidth,
+        ]
+    );
+    assert_eq!(screen[0].trim_end(), "Double height");
+    assert_eq!(screen[1].trim_end(), "Double height");
+    assert_eq!(screen[3].trim_end(), "Single width");
+    assert_eq!(screen[4].trim_end(), "x".repeat(40));
+    assert_eq!(grid.cursor_position(), (39, 4));
+
+    // Clearing the screen returns every line to single width
+    let mut terminal = terminal;
+    terminal.process(b"\x1b[2J", &mut Vec::new());
+    assert!((0..24).all(|row| terminal.grid().line_size(row) == LineSize::SingleWidth));
 }